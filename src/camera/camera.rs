@@ -63,5 +63,6 @@ impl Camera {
             self.lower_left_corner + self.horizontal * s + self.vertical * t - self.origin - offset,
             self.time0 + rand::thread_rng().gen::<f64>() * (self.time1 - self.time0),
         )
+        .with_type(RayType::Camera)
     }
 }
@@ -0,0 +1,213 @@
+use super::*;
+
+/// Pose of an `AnimatedTransform` at one point in time. The object is scaled,
+/// then rotated, then translated.
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    time: f64,
+    translation: Vector3,
+    rotation: Quaternion,
+    scale: Vector3,
+}
+
+impl Keyframe {
+    pub fn new(time: f64) -> Keyframe {
+        Keyframe {
+            time,
+            translation: Vector3::zero(),
+            rotation: Quaternion::identity(),
+            scale: Vector3::one(),
+        }
+    }
+
+    pub fn with_translation(mut self, translation: Vector3) -> Keyframe {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quaternion) -> Keyframe {
+        self.rotation = rotation.normalize();
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector3) -> Keyframe {
+        self.scale = scale;
+        self
+    }
+
+    fn inverse_scale(self) -> Vector3 {
+        Vector3::new(
+            1.0 / self.scale.x(),
+            1.0 / self.scale.y(),
+            1.0 / self.scale.z(),
+        )
+    }
+
+    fn to_object(self, v: Vector3) -> Vector3 {
+        self.rotation.conjugate().rotate(v) * self.inverse_scale()
+    }
+
+    fn to_world(self, v: Vector3) -> Vector3 {
+        self.rotation.rotate(v * self.scale)
+    }
+
+    fn lerp(self, other: Keyframe, time: f64) -> Keyframe {
+        let t = (time - self.time) / (other.time - self.time);
+        Keyframe {
+            time,
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+/// Moves, rotates and scales `hittable` over the shutter interval.
+///
+/// Translation and scale are interpolated linearly between keyframes, the
+/// rotation with quaternion slerp; before the first and after the last
+/// keyframe the object holds still. Rays are moved into the object's pose at
+/// `Ray::time`, so anything wrapped in it gets motion blur.
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform<H: Hittable> {
+    hittable: H,
+    keyframes: Vec<Keyframe>,
+}
+
+impl<H: Hittable> AnimatedTransform<H> {
+    pub fn new(hittable: H, mut keyframes: Vec<Keyframe>) -> Result<AnimatedTransform<H>, String> {
+        if keyframes.is_empty() {
+            return Err("animated transform needs at least one keyframe".to_string());
+        }
+        if keyframes
+            .iter()
+            .any(|k| (0..3).any(|axis| k.scale[axis] == 0.0))
+        {
+            return Err("animated transform scale must not be zero".to_string());
+        }
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        keyframes.dedup_by(|a, b| a.time == b.time);
+        Ok(AnimatedTransform {
+            hittable,
+            keyframes,
+        })
+    }
+
+    fn pose(&self, time: f64) -> Keyframe {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0];
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1];
+        }
+        self.keyframes[next - 1].lerp(self.keyframes[next], time)
+    }
+}
+
+impl<H: Hittable> Hittable for AnimatedTransform<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let pose = self.pose(r.time());
+        let origin = pose.to_object(r.origin() - pose.translation);
+        let direction = pose.to_object(r.dir());
+
+        // the map is affine, so t carries over and the normal's side of the ray is kept
+        self.hittable
+            .hit(
+                &Ray::new(origin, direction, r.time()).with_type(r.ray_type()),
+                t_min,
+                t_max,
+            )
+            .map(|mut hit| {
+                hit.p = pose.translation + pose.to_world(hit.p);
+                hit.normal = pose
+                    .rotation
+                    .rotate(hit.normal * pose.inverse_scale())
+                    .normalize();
+                hit.tangent = pose.to_world(hit.tangent);
+                hit.bitangent = pose.to_world(hit.bitangent);
+                hit
+            })
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let bbox = self.hittable.bounding_box(time0, time1)?;
+        let mut corners = [Point::zero(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            for axis in 0..3 {
+                corner[axis] = if i >> axis & 1 == 0 {
+                    bbox.min[axis]
+                } else {
+                    bbox.max[axis]
+                };
+            }
+        }
+
+        // split the interval at the keyframes, inside which every part moves smoothly
+        let mut times = vec![time0.min(time1)];
+        times.extend(
+            self.keyframes
+                .iter()
+                .map(|k| k.time)
+                .filter(|&t| t > time0.min(time1) && t < time0.max(time1)),
+        );
+        times.push(time0.max(time1));
+
+        let mut min = Vector3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = -min;
+        for span in times.windows(2) {
+            // steps of at most two degrees of rotation
+            let angle = self
+                .pose(span[0])
+                .rotation
+                .angle_to(self.pose(span[1]).rotation);
+            let steps = (angle / degress_to_radians(2.0)).ceil().max(1.0) as usize;
+            for step in 0..steps {
+                let ta = span[0] + (span[1] - span[0]) * step as f64 / steps as f64;
+                let tb = span[0] + (span[1] - span[0]) * (step + 1) as f64 / steps as f64;
+                let (a, b) = (self.pose(ta), self.pose(tb));
+                // with a's rotation held, translation and scale move each corner along a
+                // line; letting the rotation catch up to b's moves it by at most
+                // radius * angle
+                let spin = a.rotation.angle_to(b.rotation);
+                for corner in &corners {
+                    let (scaled_a, scaled_b) = (*corner * a.scale, *corner * b.scale);
+                    let pad = spin * scaled_a.length().max(scaled_b.length());
+                    for p in [
+                        a.translation + a.rotation.rotate(scaled_a),
+                        b.translation + a.rotation.rotate(scaled_b),
+                    ] {
+                        for axis in 0..3 {
+                            min[axis] = min[axis].min(p[axis] - pad);
+                            max[axis] = max[axis].max(p[axis] + pad);
+                        }
+                    }
+                }
+            }
+        }
+        Some(Aabb::new(min, max))
+    }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        let pose = self.pose(time);
+        let direction = pose.to_object(v.normalize());
+        let pdf = self
+            .hittable
+            .pdf_value(pose.to_object(o - pose.translation), direction, time);
+        // change of solid angle under the inverse map B: |det B| / |B w|^3
+        let det = 1.0 / (pose.scale.x() * pose.scale.y() * pose.scale.z()).abs();
+        pdf * det / direction.length().powi(3)
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        let pose = self.pose(time);
+        pose.to_world(
+            self.hittable
+                .random(pose.to_object(o - pose.translation), time),
+        )
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.hittable.visibility()
+    }
+}
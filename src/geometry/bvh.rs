@@ -9,10 +9,33 @@ enum BVHNode {
 pub struct BVH {
     tree: BVHNode,
     bbox: Aabb,
+    // objects without a bounding box (e.g. InfinitePlane) are tested outside the tree
+    unbounded: Vec<Box<dyn Hittable>>,
 }
 
 impl BVH {
-    pub fn new(mut hit: Vec<Box<dyn Hittable>>, time0: f64, time1: f64) -> BVH {
+    pub fn new(hit: Vec<Box<dyn Hittable>>, time0: f64, time1: f64) -> BVH {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = hit
+            .into_iter()
+            .partition(|h| h.bounding_box(time0, time1).is_some());
+        if unbounded.is_empty() {
+            return BVH::build(bounded, time0, time1);
+        }
+
+        let mut bvh = if bounded.is_empty() {
+            BVH {
+                tree: BVHNode::Leaf(Box::new(HittableList::default())),
+                bbox: Aabb::new(Point::zero(), Point::zero()),
+                unbounded: Vec::new(),
+            }
+        } else {
+            BVH::build(bounded, time0, time1)
+        };
+        bvh.unbounded = unbounded;
+        bvh
+    }
+
+    fn build(mut hit: Vec<Box<dyn Hittable>>, time0: f64, time1: f64) -> BVH {
         fn box_compare(
             time0: f64,
             time1: f64,
@@ -58,14 +81,15 @@ impl BVH {
                     BVH {
                         tree: BVHNode::Leaf(leaf),
                         bbox,
+                        unbounded: Vec::new(),
                     }
                 } else {
                     std::panic!("no bounding box in bvh node")
                 }
             }
             _ => {
-                let right = BVH::new(hit.drain(length / 2..).collect(), time0, time1);
-                let left = BVH::new(hit, time0, time1);
+                let right = BVH::build(hit.drain(length / 2..).collect(), time0, time1);
+                let left = BVH::build(hit, time0, time1);
                 let bbox = Aabb::surrounding_box(&left.bbox, &right.bbox);
                 BVH {
                     tree: BVHNode::Branch {
@@ -73,14 +97,13 @@ impl BVH {
                         right: Box::new(right),
                     },
                     bbox,
+                    unbounded: Vec::new(),
                 }
             }
         }
     }
-}
 
-impl Hittable for BVH {
-    fn hit(&self, r: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
+    fn hit_tree(&self, r: &Ray, t_min: f64, mut t_max: f64) -> Option<HitRecord> {
         if self.bbox.hit(r, t_min, t_max) {
             match &self.tree {
                 BVHNode::Branch { left, right } => {
@@ -95,13 +118,37 @@ impl Hittable for BVH {
                         left
                     }
                 }
-                BVHNode::Leaf(leaf) => leaf.hit(&r, t_min, t_max),
+                BVHNode::Leaf(leaf) if leaf.visibility().sees(r.ray_type()) => {
+                    hit_opaque(leaf.as_ref(), r, t_min, t_max)
+                }
+                BVHNode::Leaf(_) => None,
             }
         } else {
             None
         }
     }
+}
+
+impl Hittable for BVH {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = self.hit_tree(r, t_min, t_max);
+        for object in &self.unbounded {
+            if !object.visibility().sees(r.ray_type()) {
+                continue;
+            }
+            let closest_so_far = closest.as_ref().map_or(t_max, |rec| rec.t);
+            if let Some(rec) = hit_opaque(object.as_ref(), r, t_min, closest_so_far) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        Some(self.bbox)
+        if self.unbounded.is_empty() {
+            Some(self.bbox)
+        } else {
+            None
+        }
     }
 }
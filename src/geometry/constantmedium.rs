@@ -37,9 +37,12 @@ impl<H: Hittable, M: Material> Hittable for ConstantMedium<H, M> {
                             p: r.at(t),
                             normal: Vector3::new(1.0, 0.0, 0.0),
                             t,
+                            primitive: 0,
                             u: 0.0,
                             v: 0.0,
                             front_face: false,
+                            tangent: Vector3::zero(),
+                            bitangent: Vector3::zero(),
                             mat: &self.phase_func,
                         });
                         // return None;
@@ -0,0 +1,144 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// offset used to step past a boundary before looking for the next one
+const CSG_EPSILON: f64 = 1e-7;
+// guards against operands that never report an exit
+const CSG_MAX_CROSSINGS: usize = 256;
+
+/// Constructive solid geometry over two closed hittables.
+///
+/// The ray is walked from boundary to boundary of both operands; whether it
+/// starts inside an operand is read off the `front_face` of its first hit, so
+/// operands must be closed and report outward facing normals. A crossing that
+/// changes the inside state of the combination is a hit on the result.
+pub struct Csg<L: Hittable, R: Hittable> {
+    operation: CsgOperation,
+    left: L,
+    right: R,
+}
+
+impl<L: Hittable, R: Hittable> Csg<L, R> {
+    pub fn new(operation: CsgOperation, left: L, right: R) -> Csg<L, R> {
+        Csg {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union(left: L, right: R) -> Csg<L, R> {
+        Csg::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: L, right: R) -> Csg<L, R> {
+        Csg::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: L, right: R) -> Csg<L, R> {
+        Csg::new(CsgOperation::Difference, left, right)
+    }
+}
+
+impl<L: Hittable, R: Hittable> Hittable for Csg<L, R> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut left_hit = self.left.hit(r, t_min, f64::INFINITY);
+        let mut right_hit = self.right.hit(r, t_min, f64::INFINITY);
+        let mut in_left = left_hit.as_ref().is_some_and(|hit| !hit.front_face);
+        let mut in_right = right_hit.as_ref().is_some_and(|hit| !hit.front_face);
+
+        for _ in 0..CSG_MAX_CROSSINGS {
+            let left_first = match (&left_hit, &right_hit) {
+                (Some(l), Some(r)) => l.t <= r.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let crossing = if left_first {
+                left_hit.take()
+            } else {
+                right_hit.take()
+            }
+            .unwrap();
+            if crossing.t > t_max {
+                return None;
+            }
+
+            let inside_before = self.operation.inside(in_left, in_right);
+            if left_first {
+                in_left = !in_left;
+            } else {
+                in_right = !in_right;
+            }
+            let inside_after = self.operation.inside(in_left, in_right);
+
+            if inside_before != inside_after {
+                let mut rec = crossing;
+                // the normal already faces the ray; the side depends on entering or leaving
+                rec.front_face = inside_after;
+                return Some(rec);
+            }
+
+            let next = crossing.t + CSG_EPSILON;
+            if left_first {
+                left_hit = self.left.hit(r, next, f64::INFINITY);
+            } else {
+                right_hit = self.right.hit(r, next, f64::INFINITY);
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let left = self.left.bounding_box(time0, time1);
+        let right = self.right.bounding_box(time0, time1);
+        match self.operation {
+            CsgOperation::Union => match (left, right) {
+                (Some(l), Some(r)) => Some(Aabb::surrounding_box(&l, &r)),
+                _ => None,
+            },
+            CsgOperation::Intersection => match (left, right) {
+                (Some(l), Some(r)) => {
+                    let min = Vector3::new(
+                        l.min.x().max(r.min.x()),
+                        l.min.y().max(r.min.y()),
+                        l.min.z().max(r.min.z()),
+                    );
+                    let max = Vector3::new(
+                        l.max.x().min(r.max.x()),
+                        l.max.y().min(r.max.y()),
+                        l.max.z().min(r.max.z()),
+                    );
+                    // disjoint operands leave an empty box, collapse it onto a point
+                    Some(Aabb::new(
+                        min,
+                        Vector3::new(
+                            max.x().max(min.x()),
+                            max.y().max(min.y()),
+                            max.z().max(min.z()),
+                        ),
+                    ))
+                }
+                (Some(bbox), None) | (None, Some(bbox)) => Some(bbox),
+                (None, None) => None,
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+}
@@ -9,9 +9,15 @@ pub struct Cube {
 impl Cube {
     pub fn new<M: Material + Clone + 'static>(p0: Point, p1: Point, mat: M) -> Cube {
         let mut sides = HittableList::default();
+        let size = p1 - p0;
+        let [x, y, z] = [size.x().abs(), size.y().abs(), size.z().abs()];
+        // the sides share the power of an emitter
+        let mat = mat
+            .with_emitter_surface(EmitterSurface::closed(2.0 * (x * y + x * z + y * z)).shared());
 
+        // AARect normals point along +k, so the sides facing -k are flipped to point outwards
         sides.add(AARect::new(
-            Plane::XZ,
+            Plane::XY,
             p0.x(),
             p1.x(),
             p0.y(),
@@ -19,7 +25,7 @@ impl Cube {
             p1.z(),
             mat.clone(),
         ));
-        sides.add(AARect::new(
+        sides.add(FlipFace::new(AARect::new(
             Plane::XY,
             p0.x(),
             p1.x(),
@@ -27,43 +33,43 @@ impl Cube {
             p1.y(),
             p0.z(),
             mat.clone(),
-        ));
+        )));
         sides.add(AARect::new(
             Plane::XZ,
             p0.x(),
             p1.x(),
-            p0.y(),
-            p1.y(),
+            p0.z(),
             p1.z(),
+            p1.y(),
             mat.clone(),
         ));
-        sides.add(AARect::new(
+        sides.add(FlipFace::new(AARect::new(
             Plane::XZ,
             p0.x(),
             p1.x(),
-            p0.y(),
-            p1.y(),
             p0.z(),
+            p1.z(),
+            p0.y(),
             mat.clone(),
-        ));
+        )));
         sides.add(AARect::new(
             Plane::YZ,
-            p0.x(),
-            p1.x(),
             p0.y(),
             p1.y(),
+            p0.z(),
             p1.z(),
+            p1.x(),
             mat.clone(),
         ));
-        sides.add(AARect::new(
+        sides.add(FlipFace::new(AARect::new(
             Plane::YZ,
-            p0.x(),
-            p1.x(),
             p0.y(),
             p1.y(),
             p0.z(),
+            p1.z(),
+            p0.x(),
             mat,
-        ));
+        )));
         Cube {
             cube_min: p0,
             cube_max: p1,
@@ -0,0 +1,325 @@
+use super::*;
+
+/// How the swept width of a `Curve` is turned into a surface.
+#[derive(Debug, Clone, Copy)]
+pub enum CurveMode {
+    /// Flat strip that always faces the ray, for hair and fur seen from afar.
+    Flat,
+    /// Flat strip oriented by the normals at the start and the end of the curve,
+    /// for grass blades and the like.
+    Ribbon([Vector3; 2]),
+    /// Ray facing strip shaded as a round tube, for wires and close-up hair.
+    Cylinder,
+}
+
+fn bezier(cp: &[Point; 4], u: f64) -> (Point, Vector3) {
+    let cp1 = [
+        cp[0].lerp(cp[1], u),
+        cp[1].lerp(cp[2], u),
+        cp[2].lerp(cp[3], u),
+    ];
+    let cp2 = [cp1[0].lerp(cp1[1], u), cp1[1].lerp(cp1[2], u)];
+    let derivative = cp2[1] - cp2[0];
+    // the derivative vanishes at the ends of degenerate curves, fall back to the hull
+    let derivative = if derivative.near_zero() {
+        cp[3] - cp[0]
+    } else {
+        3.0 * derivative
+    };
+    (cp2[0].lerp(cp2[1], u), derivative)
+}
+
+/// Control points of the part of the curve between `u0` and `u1`.
+fn blossom(cp: &[Point; 4], u0: f64, u1: f64) -> [Point; 4] {
+    let point = |u: [f64; 3]| {
+        let a = [
+            cp[0].lerp(cp[1], u[0]),
+            cp[1].lerp(cp[2], u[0]),
+            cp[2].lerp(cp[3], u[0]),
+        ];
+        let b = [a[0].lerp(a[1], u[1]), a[1].lerp(a[2], u[1])];
+        b[0].lerp(b[1], u[2])
+    };
+    [
+        point([u0, u0, u0]),
+        point([u0, u0, u1]),
+        point([u0, u1, u1]),
+        point([u1, u1, u1]),
+    ]
+}
+
+fn split_bezier(cp: &[Point; 4]) -> [[Point; 4]; 2] {
+    let mid = |a: Point, b: Point| 0.5 * (a + b);
+    let (m01, m12, m23) = (mid(cp[0], cp[1]), mid(cp[1], cp[2]), mid(cp[2], cp[3]));
+    let (m012, m123) = (mid(m01, m12), mid(m12, m23));
+    let center = mid(m012, m123);
+    [[cp[0], m01, m012, center], [center, m123, m23, cp[3]]]
+}
+
+fn slerp(a: Vector3, b: Vector3, t: f64) -> Vector3 {
+    let cos = a.dot(b).clamp(-1.0, 1.0);
+    let theta = cos.acos();
+    if theta < 1e-6 {
+        return a.lerp(b, t).normalize();
+    }
+    let sin = theta.sin();
+    (((1.0 - t) * theta).sin() / sin) * a + ((t * theta).sin() / sin) * b
+}
+
+/// Cubic Bezier curve swept with a width that varies linearly along it.
+///
+/// A single `Curve` covers the whole curve; `split` cuts it into segments with
+/// their own, tighter bounding boxes, which is what a BVH over long curves
+/// wants. Intersection follows pbrt: the curve is moved
+/// into a frame looking down the ray and subdivided until the segments are
+/// flat enough to be tested as lines.
+///
+/// u runs along the curve, v across the width.
+#[derive(Debug, Clone)]
+pub struct Curve<M: Material> {
+    curve: [Point; 4],
+    // control points of the segment between u_min and u_max
+    control_points: [Point; 4],
+    u_min: f64,
+    u_max: f64,
+    width: [f64; 2],
+    mode: CurveMode,
+    mat: M,
+}
+
+impl<M: Material> Curve<M> {
+    pub fn new(control_points: [Point; 4], width: [f64; 2], mode: CurveMode, mat: M) -> Curve<M> {
+        let mode = match mode {
+            CurveMode::Ribbon(normals) => {
+                CurveMode::Ribbon([normals[0].normalize(), normals[1].normalize()])
+            }
+            mode => mode,
+        };
+        let mut curve = Curve {
+            curve: control_points,
+            control_points,
+            u_min: 0.0,
+            u_max: 1.0,
+            width,
+            mode,
+            mat,
+        };
+        // segments cut off by `split` keep the surface of the whole curve
+        let surface = curve.surface();
+        curve.mat = curve.mat.with_emitter_surface(surface);
+        curve
+    }
+
+    /// Surface of the whole curve for an emitter. Strips facing the ray show
+    /// the outline of a tube, so they count as one.
+    fn surface(&self) -> EmitterSurface {
+        const STEPS: usize = 64;
+        let swept: f64 = (0..STEPS)
+            .map(|i| {
+                let u = (i as f64 + 0.5) / STEPS as f64;
+                self.width(u) * bezier(&self.curve, u).1.length() / STEPS as f64
+            })
+            .sum();
+        match self.mode {
+            CurveMode::Ribbon(_) => EmitterSurface::open(swept),
+            CurveMode::Flat | CurveMode::Cylinder => EmitterSurface::closed(PI * swept),
+        }
+    }
+
+    /// Cuts the curve into `segments` pieces of equal parameter range.
+    pub fn split(self, segments: usize) -> Vec<Curve<M>>
+    where
+        M: Clone,
+    {
+        let segments = segments.max(1);
+        (0..segments)
+            .map(|i| {
+                let u_min = self.u_min + (self.u_max - self.u_min) * i as f64 / segments as f64;
+                let u_max =
+                    self.u_min + (self.u_max - self.u_min) * (i + 1) as f64 / segments as f64;
+                Curve {
+                    control_points: blossom(&self.curve, u_min, u_max),
+                    u_min,
+                    u_max,
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        let [w0, w1] = self.width;
+        w0 * (1.0 - u) + w1 * u
+    }
+
+    fn max_width(&self) -> f64 {
+        self.width(self.u_min).max(self.width(self.u_max))
+    }
+
+    /// Closest hit of the ray space curve `cp` with the ray looking down +z,
+    /// as (z, u) with z in [z_min, z_max].
+    fn hit_recursive(
+        &self,
+        cp: &[Point; 4],
+        u: (f64, f64),
+        z_range: (f64, f64),
+        ray_dir: Vector3,
+        depth: u32,
+    ) -> Option<(f64, f64)> {
+        let half_width = 0.5 * self.max_width();
+        let outside = |cp: &[Point; 4]| {
+            let (mut min, mut max) = (cp[0], cp[0]);
+            for p in &cp[1..] {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(p[axis]);
+                    max[axis] = max[axis].max(p[axis]);
+                }
+            }
+            min.x() - half_width > 0.0
+                || max.x() + half_width < 0.0
+                || min.y() - half_width > 0.0
+                || max.y() + half_width < 0.0
+                || min.z() - half_width > z_range.1
+                || max.z() + half_width < z_range.0
+        };
+
+        if depth > 0 {
+            let u_mid = 0.5 * (u.0 + u.1);
+            let halves = split_bezier(cp);
+            let mut closest: Option<(f64, f64)> = None;
+            for (half, range) in halves.iter().zip([(u.0, u_mid), (u_mid, u.1)]) {
+                if outside(half) {
+                    continue;
+                }
+                let z_max = closest.map_or(z_range.1, |(z, _)| z);
+                if let Some(hit) =
+                    self.hit_recursive(half, range, (z_range.0, z_max), ray_dir, depth - 1)
+                {
+                    closest = Some(hit);
+                }
+            }
+            return closest;
+        }
+
+        // the ray must pass between the lines perpendicular to the curve at its ends
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0.0 {
+            return None;
+        }
+
+        let segment = Vector3::new(cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y(), 0.0);
+        let denom = segment.length_squared();
+        if denom == 0.0 {
+            return None;
+        }
+        let w = (-cp[0].x() * segment.x() - cp[0].y() * segment.y()) / denom;
+        let hit_u = (u.0 * (1.0 - w) + u.1 * w).clamp(u.0, u.1);
+        let mut hit_width = self.width(hit_u);
+        if let CurveMode::Ribbon(normals) = self.mode {
+            // a ribbon seen at an angle looks narrower
+            hit_width *= slerp(normals[0], normals[1], hit_u).dot(ray_dir).abs();
+        }
+
+        let (pc, _) = bezier(cp, w.clamp(0.0, 1.0));
+        if pc.x() * pc.x() + pc.y() * pc.y() > 0.25 * hit_width * hit_width {
+            return None;
+        }
+        if pc.z() < z_range.0 || pc.z() > z_range.1 {
+            return None;
+        }
+        Some((pc.z(), hit_u))
+    }
+}
+
+impl<M: Material> Hittable for Curve<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let dir_length = r.dir().length();
+        let ray_dir = r.dir() / dir_length;
+        let frame = ONB::build_from_w(&ray_dir);
+        let to_ray_space = |p: Point| {
+            let q = p - r.origin();
+            Vector3::new(q.dot(frame.u()), q.dot(frame.v()), q.dot(ray_dir))
+        };
+        let cp = self.control_points.map(to_ray_space);
+
+        // subdivide until the segments deviate from a line by less than a 20th of the width
+        let mut l0: f64 = 0.0;
+        for i in 0..2 {
+            let second = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            l0 = l0
+                .max(second.x().abs())
+                .max(second.y().abs())
+                .max(second.z().abs());
+        }
+        let eps = self.width[0].max(self.width[1]) * 0.05;
+        let depth = if l0 > 0.0 && eps > 0.0 {
+            ((std::f64::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() / 2.0).clamp(0.0, 10.0)
+                as u32
+        } else {
+            0
+        };
+
+        let (z, u) = self.hit_recursive(
+            &cp,
+            (self.u_min, self.u_max),
+            (t_min * dir_length, t_max * dir_length),
+            ray_dir,
+            depth,
+        )?;
+        let t = z / dir_length;
+        let p = r.at(t);
+
+        let (center, tangent) = bezier(&self.curve, u);
+        let axis = tangent.normalize();
+        let width = self.width(u);
+        let (outward_normal, across) = match self.mode {
+            CurveMode::Ribbon(normals) => {
+                let n = slerp(normals[0], normals[1], u);
+                (n, n.cross(axis).normalize())
+            }
+            CurveMode::Flat | CurveMode::Cylinder => {
+                // the strip has no inside, so its normal always faces the ray
+                let across = ray_dir.cross(axis).normalize();
+                (across.cross(axis).normalize(), across)
+            }
+        };
+        let v = (0.5 + (p - center).dot(across) / width).clamp(0.0, 1.0);
+        let outward_normal = match self.mode {
+            CurveMode::Cylinder => {
+                // bend the normal around the axis like a tube's cross section
+                let h = 2.0 * v - 1.0;
+                h * across + (1.0 - h * h).max(0.0).sqrt() * outward_normal
+            }
+            _ => outward_normal,
+        };
+
+        let mut rec = HitRecord {
+            p,
+            normal: outward_normal,
+            t,
+            primitive: 0,
+            u,
+            v,
+            front_face: false,
+            tangent,
+            bitangent: width * across,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let half_width = 0.5 * self.max_width() + 0.0001;
+        let mut bbox = Aabb::new(self.control_points[0], self.control_points[0]);
+        for p in &self.control_points[1..] {
+            bbox = Aabb::surrounding_box(&bbox, &Aabb::new(*p, *p));
+        }
+        let extent = Vector3::new(half_width, half_width, half_width);
+        Some(Aabb::new(bbox.min - extent, bbox.max + extent))
+    }
+}
@@ -0,0 +1,99 @@
+use rand::Rng;
+
+use super::*;
+
+/// Flat disk facing `normal`. u runs around the rim, v from the center outwards.
+#[derive(Debug, Clone)]
+pub struct Disk<M: Material> {
+    center: Point,
+    normal: Vector3,
+    radius: f64,
+    axis_u: Vector3,
+    axis_v: Vector3,
+    mat: M,
+}
+
+impl<M: Material> Disk<M> {
+    pub fn new(center: Point, normal: Vector3, radius: f64, mat: M) -> Disk<M> {
+        let uvw = ONB::build_from_w(&normal);
+        Disk {
+            center,
+            normal: uvw.w(),
+            radius,
+            axis_u: uvw.u(),
+            axis_v: uvw.v(),
+            mat: mat.with_emitter_surface(EmitterSurface::open(PI * radius * radius)),
+        }
+    }
+}
+
+impl<M: Material> Hittable for Disk<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.dir());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = self.normal.dot(self.center - r.origin()) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = r.at(t);
+        let offset = p - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        let phi = offset.dot(self.axis_v).atan2(offset.dot(self.axis_u));
+        // around the rim and out from the center
+        let tangent = 2.0 * PI * offset.cross(self.normal);
+        let bitangent = offset * (self.radius / distance_squared.sqrt().max(1e-12));
+        let mut rec = HitRecord {
+            p,
+            normal: self.normal,
+            t,
+            primitive: 0,
+            u: (phi + PI) / (2.0 * PI),
+            v: distance_squared.sqrt() / self.radius,
+            front_face: false,
+            tangent,
+            bitangent,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        // the rim reaches radius * sin(angle between axis and normal) along each axis
+        let mut extent = Vector3::zero();
+        for axis in 0..3 {
+            extent[axis] = self.radius * (1.0 - self.normal[axis].powi(2)).max(0.0).sqrt() + 0.0001;
+        }
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        if let Some(rec) = self.hit(&Ray::new(o, v, time), 0.001, f64::INFINITY) {
+            let area = PI * self.radius * self.radius;
+            let distance_squared = rec.t.powi(2) * v.length_squared();
+            let cosine = v.dot(rec.normal).abs() / v.length();
+            if cosine != 0.0 {
+                distance_squared / (cosine * area)
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        let mut rng = rand::thread_rng();
+        let r = self.radius * rng.gen::<f64>().sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let random_point = self.center + r * phi.cos() * self.axis_u + r * phi.sin() * self.axis_v;
+        random_point - o
+    }
+}
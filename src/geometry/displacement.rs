@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::texture::ScalarTexture;
+
+/// Screen space target for `MeshData::tessellate`.
+///
+/// Edges are split until they cover at most `max_edge_pixels` pixels as seen
+/// from `eye`. Edges shorter than the mesh's longest edge divided by
+/// 2^`max_level` are never split, which bounds the triangle count near the eye.
+#[derive(Debug, Clone, Copy)]
+pub struct Tessellation {
+    eye: Point,
+    pixel_angle: f64,
+    max_edge_pixels: f64,
+    max_level: u32,
+}
+
+impl Tessellation {
+    pub fn new(eye: Point, vfov: f64, image_height: u32, max_edge_pixels: f64) -> Tessellation {
+        Tessellation {
+            eye,
+            pixel_angle: degress_to_radians(vfov) / image_height.max(1) as f64,
+            max_edge_pixels: max_edge_pixels.max(0.1),
+            max_level: 8,
+        }
+    }
+
+    pub fn with_max_level(mut self, max_level: u32) -> Tessellation {
+        self.max_level = max_level;
+        self
+    }
+
+    /// Approximate length of the edge on screen, in pixels.
+    fn edge_pixels(&self, a: Point, b: Point) -> f64 {
+        let distance = (0.5 * (a + b) - self.eye).length().max(1e-9);
+        (b - a).length() / (distance * self.pixel_angle)
+    }
+}
+
+fn midpoint_attribute<T: Copy>(
+    values: &mut Option<Vec<T>>,
+    a: u32,
+    b: u32,
+    mix: impl Fn(T, T) -> T,
+) {
+    if let Some(values) = values {
+        let value = mix(values[a as usize], values[b as usize]);
+        values.push(value);
+    }
+}
+
+/// Area weighted vertex normals. Vertices with the same `key` share their
+/// normal, so seams in other attributes don't show up in the shading.
+fn smooth_normals<K: std::hash::Hash + Eq>(
+    positions: &[Point],
+    indices: &[u32],
+    key: impl Fn(usize) -> K,
+) -> Vec<Vector3> {
+    let mut sums: HashMap<K, Vector3> = HashMap::new();
+    for face in indices.chunks_exact(3) {
+        let [a, b, c] = [face[0], face[1], face[2]].map(|i| positions[i as usize]);
+        let normal = (b - a).cross(c - a);
+        for &i in face {
+            *sums.entry(key(i as usize)).or_insert_with(Vector3::zero) += normal;
+        }
+    }
+    (0..positions.len())
+        .map(|i| match sums.get(&key(i)) {
+            Some(normal) if !normal.near_zero() => normal.normalize(),
+            _ => Vector3::new(0.0, 1.0, 0.0),
+        })
+        .collect()
+}
+
+fn bits(v: Vector3) -> [u64; 3] {
+    [v.x().to_bits(), v.y().to_bits(), v.z().to_bits()]
+}
+
+impl MeshData {
+    /// Splits triangles until their edges meet the screen space `target`.
+    ///
+    /// Whether an edge is split depends only on its two end points, their
+    /// position and how many splits deep they are, so neighbouring triangles
+    /// always agree and the result has no cracks. New vertices interpolate all
+    /// attributes of the edge they split.
+    pub fn tessellate(&self, target: &Tessellation) -> MeshData {
+        let mut data = MeshData {
+            indices: vec![],
+            ..self.clone()
+        };
+        let longest = self
+            .faces()
+            .flat_map(|face| {
+                (0..3).map(move |k| {
+                    (self.positions[face[k]] - self.positions[face[(k + 1) % 3]]).length()
+                })
+            })
+            .fold(0.0, f64::max);
+        let min_length = longest / 2f64.powi(target.max_level as i32);
+        // the length cutoff already ends the recursion, the depth only guards slivers
+        let max_depth = 3 * target.max_level + 3;
+
+        // how many splits deep each vertex is, the input's are at 0
+        let mut depths = vec![0; self.positions.len()];
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut split = |data: &mut MeshData, a: u32, b: u32| -> Option<u32> {
+            let key = if a < b { (a, b) } else { (b, a) };
+            if let Some(&mid) = midpoints.get(&key) {
+                return Some(mid);
+            }
+            let (pa, pb) = (data.positions[a as usize], data.positions[b as usize]);
+            let depth = depths[a as usize].max(depths[b as usize]);
+            if depth >= max_depth
+                || (pb - pa).length() <= min_length
+                || target.edge_pixels(pa, pb) <= target.max_edge_pixels
+            {
+                return None;
+            }
+            // interpolate from the sorted pair so both sides create the same vertex
+            let (a, b) = key;
+            let mid = data.positions.len() as u32;
+            data.positions
+                .push(0.5 * (data.positions[a as usize] + data.positions[b as usize]));
+            midpoint_attribute(&mut data.normals, a, b, |na, nb| {
+                let n = na + nb;
+                if n.near_zero() {
+                    na
+                } else {
+                    n.normalize()
+                }
+            });
+            midpoint_attribute(&mut data.uvs, a, b, |ua, ub| {
+                (0.5 * (ua.0 + ub.0), 0.5 * (ua.1 + ub.1))
+            });
+            midpoint_attribute(&mut data.colors, a, b, |ca, cb| 0.5 * (ca + cb));
+            depths.push(depth + 1);
+            midpoints.insert(key, mid);
+            Some(mid)
+        };
+
+        // every split edge is replaced by deeper ones, which ends the loop
+        let mut stack: Vec<[u32; 3]> = self.faces().map(|face| face.map(|i| i as u32)).collect();
+        while let Some(tri) = stack.pop() {
+            let [a, b, c] = tri;
+            let mids = [
+                split(&mut data, a, b),
+                split(&mut data, b, c),
+                split(&mut data, c, a),
+            ];
+            let children = match mids {
+                [None, None, None] => {
+                    data.indices.extend(tri);
+                    continue;
+                }
+                [Some(ab), Some(bc), Some(ca)] => {
+                    vec![[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]
+                }
+                [Some(ab), None, None] => vec![[a, ab, c], [ab, b, c]],
+                [None, Some(bc), None] => vec![[a, b, bc], [a, bc, c]],
+                [None, None, Some(ca)] => vec![[a, b, ca], [ca, b, c]],
+                [Some(ab), Some(bc), None] => {
+                    let mut children = vec![[ab, b, bc]];
+                    children.extend(split_quad(&data, [a, ab, bc, c]));
+                    children
+                }
+                [None, Some(bc), Some(ca)] => {
+                    let mut children = vec![[ca, bc, c]];
+                    children.extend(split_quad(&data, [a, b, bc, ca]));
+                    children
+                }
+                [Some(ab), None, Some(ca)] => {
+                    let mut children = vec![[a, ab, ca]];
+                    children.extend(split_quad(&data, [ab, b, c, ca]));
+                    children
+                }
+            };
+            stack.extend(children);
+        }
+        data
+    }
+
+    /// Moves every vertex along its normal by `scale * (height - midlevel)`,
+    /// with the height read from `texture` at the vertex's uv and position.
+    ///
+    /// Meshes without normals get smooth ones first. Vertices at the same
+    /// position move by their mean height, so uv seams, where the texture
+    /// differs on either side, stay closed; hard edges of the input, with a
+    /// normal per side, still open up. The normals are recomputed from the
+    /// displaced surface afterwards, still shared across uv seams but not
+    /// across the hard edges. Tessellate first, the detail can't be finer than
+    /// the vertex spacing.
+    pub fn displace<T: ScalarTexture + ?Sized>(
+        &self,
+        texture: &T,
+        scale: f64,
+        midlevel: f64,
+    ) -> MeshData {
+        let normals = match &self.normals {
+            Some(normals) => normals.clone(),
+            None => smooth_normals(&self.positions, &self.indices, |i| bits(self.positions[i])),
+        };
+
+        // weld the heights by position, summed with the number of vertices
+        let mut heights: HashMap<[u64; 3], (f64, f64)> = HashMap::new();
+        for (i, p) in self.positions.iter().enumerate() {
+            let (u, v) = self.uvs.as_ref().map_or((0.0, 0.0), |uvs| uvs[i]);
+            let sum = heights.entry(bits(*p)).or_insert((0.0, 0.0));
+            sum.0 += texture.value(u, v, p);
+            sum.1 += 1.0;
+        }
+
+        let mut data = self.clone();
+        for (i, p) in data.positions.iter_mut().enumerate() {
+            let (sum, count) = heights[&bits(*p)];
+            *p += normals[i] * scale * (sum / count - midlevel);
+        }
+
+        // vertices split only for their uvs or colors share the original position and normal
+        data.normals = Some(smooth_normals(&data.positions, &data.indices, |i| {
+            (bits(self.positions[i]), bits(normals[i]))
+        }));
+        data
+    }
+}
+
+/// Two triangles for the quad `[a, b, c, d]`, cut along the shorter diagonal.
+fn split_quad(data: &MeshData, quad: [u32; 4]) -> [[u32; 3]; 2] {
+    let [a, b, c, d] = quad;
+    let p = |i: u32| data.positions[i as usize];
+    if (p(a) - p(c)).length_squared() <= (p(b) - p(d)).length_squared() {
+        [[a, b, c], [a, c, d]]
+    } else {
+        [[a, b, d], [b, c, d]]
+    }
+}
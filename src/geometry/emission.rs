@@ -0,0 +1,90 @@
+use super::*;
+
+/// Piecewise constant distribution over the (u, v) square a light samples
+/// its surface from, so that the bright parts of a textured emitter get most
+/// of the samples.
+#[derive(Debug, Clone)]
+pub struct EmissionGrid {
+    resolution: usize,
+    // running sum of the cell probabilities, row by row
+    cdf: Vec<f64>,
+    // probability density of each cell with respect to (u, v)
+    density: Vec<f64>,
+}
+
+impl EmissionGrid {
+    /// Tabulates `weight` at the cell centres of a `resolution` x
+    /// `resolution` grid, or `None` if nothing is emitted.
+    pub fn tabulate<F: FnMut(f64, f64) -> f64>(
+        resolution: usize,
+        mut weight: F,
+    ) -> Option<EmissionGrid> {
+        let resolution = resolution.max(1);
+        let mut weights = Vec::with_capacity(resolution * resolution);
+        for j in 0..resolution {
+            for i in 0..resolution {
+                let u = (i as f64 + 0.5) / resolution as f64;
+                let v = (j as f64 + 0.5) / resolution as f64;
+                weights.push(weight(u, v));
+            }
+        }
+
+        let mean = weights.iter().sum::<f64>() / weights.len() as f64;
+        if mean <= 0.0 || !mean.is_finite() {
+            return None;
+        }
+        // dim cells keep a few samples, in case the grid missed a detail
+        for w in &mut weights {
+            *w = w.max(0.0) + 0.01 * mean;
+        }
+        let total: f64 = weights.iter().sum();
+        let cells = weights.len() as f64;
+        let density = weights.iter().map(|w| w / total * cells).collect();
+        let cdf = weights
+            .iter()
+            .scan(0.0, |sum, w| {
+                *sum += w / total;
+                Some(*sum)
+            })
+            .collect();
+        Some(EmissionGrid {
+            resolution,
+            cdf,
+            density,
+        })
+    }
+
+    fn cell(&self, u: f64, v: f64) -> usize {
+        let n = self.resolution;
+        let i = ((u * n as f64).max(0.0) as usize).min(n - 1);
+        let j = ((v * n as f64).max(0.0) as usize).min(n - 1);
+        j * n + i
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        self.density[self.cell(u, v)]
+    }
+
+    pub fn sample(&self, xi: f64, xi_u: f64, xi_v: f64) -> (f64, f64) {
+        let k = self
+            .cdf
+            .partition_point(|&c| c <= xi)
+            .min(self.cdf.len() - 1);
+        let n = self.resolution as f64;
+        let (i, j) = ((k % self.resolution) as f64, (k / self.resolution) as f64);
+        ((i + xi_u) / n, (j + xi_v) / n)
+    }
+}
+
+/// Luminance `hittable` emits at `p` on its surface towards the side
+/// `normal` points to, found by looking at `p` from there.
+pub fn emitted_luminance<H: Hittable + ?Sized>(hittable: &H, p: Point, normal: Vector3) -> f64 {
+    let normal = normal.normalize();
+    match hittable.hit(&Ray::new(p + normal, -normal, 0.0), 0.5, 1.5) {
+        Some(rec) => {
+            let c = rec.mat.emitted(&rec);
+            0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b()
+        }
+        None => 0.0,
+    }
+}
@@ -0,0 +1,39 @@
+use super::*;
+
+/// Swaps the outward side of a surface, e.g. to close a box out of `AARect`s.
+#[derive(Debug, Clone)]
+pub struct FlipFace<H: Hittable> {
+    hittable: H,
+}
+
+impl<H: Hittable> FlipFace<H> {
+    pub fn new(hittable: H) -> FlipFace<H> {
+        FlipFace { hittable }
+    }
+}
+
+impl<H: Hittable> Hittable for FlipFace<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        // the normal already faces the ray, only the side it was hit from changes
+        self.hittable.hit(r, t_min, t_max).map(|mut hit| {
+            hit.front_face = !hit.front_face;
+            hit
+        })
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.hittable.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        self.hittable.pdf_value(o, v, time)
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        self.hittable.random(o, time)
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.hittable.visibility()
+    }
+}
@@ -0,0 +1,348 @@
+use super::*;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::texture::*;
+
+pub type GltfTexture = Arc<dyn Texture + Send + Sync>;
+pub type GltfMaterial = NormalMap<PBR<GltfTexture>, GltfTexture>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum GltfLightKind {
+    Directional,
+    Point,
+    Spot {
+        inner_cone_angle: f64,
+        outer_cone_angle: f64,
+    },
+}
+
+/// A KHR_lights_punctual light, already placed in world space.
+#[derive(Debug, Clone)]
+pub struct GltfLight {
+    pub kind: GltfLightKind,
+    pub color: Color,
+    pub intensity: f64,
+    pub range: Option<f64>,
+    pub position: Point,
+    pub direction: Vector3,
+}
+
+/// Everything imported from a glTF 2.0 file (`.gltf` or `.glb`).
+///
+/// Punctual lights have no area, so point and spot lights are approximated by
+/// small emissive spheres of `light_radius` added to both `world` and `lights`
+/// (spot cones are ignored). Directional lights only show up in `punctual_lights`.
+///
+/// Materials keep the base color, metallic and roughness factors, the base
+/// color texture and the normal texture. `PBR` takes scalar parameters, so the
+/// metallic-roughness texture is not sampled, and a triangle has a single uv
+/// set, so all textures of a material read that of its base color texture, or
+/// of its normal texture when it has none.
+pub struct GltfScene {
+    pub world: HittableList,
+    pub lights: HittableList,
+    pub cameras: Vec<Camera>,
+    pub punctual_lights: Vec<GltfLight>,
+}
+
+impl GltfScene {
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        aspect_ratio: f64,
+        light_radius: f64,
+    ) -> Result<GltfScene, String> {
+        let (document, buffers, images) = gltf::import(path.as_ref())
+            .map_err(|err| format!("Failed to load gltf file :{}", err))?;
+
+        let textures: Vec<GltfTexture> = images.iter().map(image_texture).collect();
+        let materials: Vec<GltfMaterial> = document
+            .materials()
+            .map(|m| pbr_material(&m, &textures))
+            .collect();
+
+        let mut scene = GltfScene {
+            world: HittableList::default(),
+            lights: HittableList::default(),
+            cameras: Vec::new(),
+            punctual_lights: Vec::new(),
+        };
+
+        let root = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(root) => root,
+            None => return Err("gltf file has no scene".to_string()),
+        };
+
+        let context = LoadContext {
+            buffers: &buffers,
+            textures: &textures,
+            materials: &materials,
+            aspect_ratio,
+            light_radius,
+        };
+        for node in root.nodes() {
+            scene.load_node(&node, Matrix4::identity(), &context)?;
+        }
+        Ok(scene)
+    }
+
+    fn load_node(
+        &mut self,
+        node: &gltf::Node,
+        parent: Matrix4,
+        context: &LoadContext,
+    ) -> Result<(), String> {
+        let transform = parent * Matrix4::from_cols(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if let Some(triangles) = load_primitive(&primitive, &transform, context)? {
+                    self.world.add(triangles);
+                }
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            if let gltf::camera::Projection::Perspective(perspective) = camera.projection() {
+                let lookfrom = transform.transform_point(Point::zero());
+                let lookat = transform.transform_point(Point::new(0.0, 0.0, -1.0));
+                let vup = transform.transform_vector(Vector3::new(0.0, 1.0, 0.0));
+                let aspect_ratio = perspective
+                    .aspect_ratio()
+                    .map_or(context.aspect_ratio, |a| a as f64);
+                self.cameras.push(Camera::new(
+                    lookfrom,
+                    lookat,
+                    vup,
+                    (perspective.yfov() as f64).to_degrees(),
+                    aspect_ratio,
+                    0.0,
+                    1.0,
+                    0.0,
+                    1.0,
+                ));
+            }
+        }
+
+        if let Some(light) = node.light() {
+            let kind = match light.kind() {
+                gltf::khr_lights_punctual::Kind::Directional => GltfLightKind::Directional,
+                gltf::khr_lights_punctual::Kind::Point => GltfLightKind::Point,
+                gltf::khr_lights_punctual::Kind::Spot {
+                    inner_cone_angle,
+                    outer_cone_angle,
+                } => GltfLightKind::Spot {
+                    inner_cone_angle: inner_cone_angle as f64,
+                    outer_cone_angle: outer_cone_angle as f64,
+                },
+            };
+            let [r, g, b] = light.color();
+            let punctual = GltfLight {
+                kind,
+                color: Color::new(r as f64, g as f64, b as f64),
+                intensity: light.intensity() as f64,
+                range: light.range().map(|range| range as f64),
+                position: transform.transform_point(Point::zero()),
+                direction: transform
+                    .transform_vector(Vector3::new(0.0, 0.0, -1.0))
+                    .normalize(),
+            };
+
+            if !matches!(punctual.kind, GltfLightKind::Directional) {
+                // a sphere of radiance L seen from afar has intensity L * pi * r^2
+                let radiance = punctual.color * punctual.intensity
+                    / (PI * context.light_radius * context.light_radius);
+                let sphere = Sphere::new(
+                    punctual.position,
+                    context.light_radius,
+                    DiffuseLight::new(SolidTexture::new(radiance)),
+                );
+                self.world.add(sphere.clone());
+                self.lights.add(sphere);
+            }
+            self.punctual_lights.push(punctual);
+        }
+
+        for child in node.children() {
+            self.load_node(&child, transform, context)?;
+        }
+        Ok(())
+    }
+}
+
+struct LoadContext<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    textures: &'a [GltfTexture],
+    materials: &'a [GltfMaterial],
+    aspect_ratio: f64,
+    light_radius: f64,
+}
+
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    transform: &Matrix4,
+    context: &LoadContext,
+) -> Result<Option<BVH>, String> {
+    if primitive.mode() != gltf::mesh::Mode::Triangles {
+        return Ok(None);
+    }
+
+    let reader = primitive.reader(|buffer| Some(&context.buffers[buffer.index()]));
+    let positions: Vec<Point> = match reader.read_positions() {
+        Some(positions) => positions
+            .map(|p| transform.transform_point(Point::new(p[0] as f64, p[1] as f64, p[2] as f64)))
+            .collect(),
+        None => return Err("gltf primitive has no POSITION attribute".to_string()),
+    };
+    let normals: Option<Vec<Vector3>> = reader.read_normals().map(|normals| {
+        normals
+            .map(|n| {
+                transform
+                    .transform_normal(Vector3::new(n[0] as f64, n[1] as f64, n[2] as f64))
+                    .normalize()
+            })
+            .collect()
+    });
+    // mirrored transforms flip the winding order and the handedness of the tangents
+    let flip = transform.determinant() < 0.0;
+    let tangents: Option<Vec<(Vector3, f64)>> = reader.read_tangents().map(|tangents| {
+        tangents
+            .map(|t| {
+                let tangent = transform
+                    .transform_vector(Vector3::new(t[0] as f64, t[1] as f64, t[2] as f64))
+                    .normalize();
+                let w = if flip { -t[3] } else { t[3] };
+                (tangent, w as f64)
+            })
+            .collect()
+    });
+    // glTF puts the uv origin at the top left, ImageTexture at the bottom left
+    let tex_coord = tex_coord_set(&primitive.material());
+    let uvs: Option<Vec<(f64, f64)>> = reader.read_tex_coords(tex_coord).map(|uvs| {
+        uvs.into_f32()
+            .map(|uv| (uv[0] as f64, 1.0 - uv[1] as f64))
+            .collect()
+    });
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let mat = match primitive.material().index() {
+        Some(index) => context.materials[index].clone(),
+        None => pbr_material(&primitive.material(), context.textures),
+    };
+
+    let mut tris: Vec<Box<dyn Hittable>> = Vec::with_capacity(indices.len() / 3);
+    for face in indices.chunks_exact(3) {
+        let face = if flip {
+            [face[0] as usize, face[2] as usize, face[1] as usize]
+        } else {
+            [face[0] as usize, face[1] as usize, face[2] as usize]
+        };
+        if face.iter().any(|&i| i >= positions.len()) {
+            return Err("gltf primitive index out of range".to_string());
+        }
+        let mut tri = Triangle::new(face.map(|i| positions[i]), mat.clone());
+        if let Some(normals) = &normals {
+            tri = tri.with_normals(face.map(|i| normals[i]));
+        }
+        if let Some(uvs) = &uvs {
+            tri = tri.with_uvs(face.map(|i| uvs[i]));
+        }
+        if let Some(tangents) = &tangents {
+            tri = tri.with_tangents(face.map(|i| tangents[i].0), face.map(|i| tangents[i].1));
+        }
+        tris.push(Box::new(tri));
+    }
+
+    if tris.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(BVH::new(tris, 0.0, 1.0)))
+    }
+}
+
+fn pbr_material(material: &gltf::Material, textures: &[GltfTexture]) -> GltfMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+    let factor = Color::new(r as f64, g as f64, b as f64);
+
+    let base_color: GltfTexture = match pbr.base_color_texture() {
+        Some(info) => Arc::new(ScaledTexture::new(
+            textures[info.texture().source().index()].clone(),
+            factor,
+        )),
+        None => Arc::new(SolidTexture::new(factor)),
+    };
+
+    let pbr = PBR::new(
+        base_color,
+        pbr.metallic_factor() as f64,
+        0.0,
+        0.5,
+        pbr.roughness_factor() as f64,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+    );
+
+    // without a normal texture the map is flat
+    match material.normal_texture() {
+        Some(normal) => NormalMap::new(pbr, textures[normal.texture().source().index()].clone())
+            .with_strength(normal.scale() as f64),
+        None => NormalMap::new(pbr, Arc::new(SolidTexture::new(Color::new(0.5, 0.5, 1.0)))),
+    }
+}
+
+/// The uv set of the base color texture, or else of the normal texture.
+fn tex_coord_set(material: &gltf::Material) -> u32 {
+    match material.pbr_metallic_roughness().base_color_texture() {
+        Some(info) => info.tex_coord(),
+        None => material
+            .normal_texture()
+            .map_or(0, |normal| normal.tex_coord()),
+    }
+}
+
+fn image_texture(image: &gltf::image::Data) -> GltfTexture {
+    use gltf::image::Format;
+
+    let (channels, wide) = match image.format {
+        Format::R8 => (1, false),
+        Format::R8G8 => (2, false),
+        Format::R8G8B8 | Format::B8G8R8 => (3, false),
+        Format::R8G8B8A8 | Format::B8G8R8A8 => (4, false),
+        Format::R16 => (1, true),
+        Format::R16G16 => (2, true),
+        Format::R16G16B16 => (3, true),
+        Format::R16G16B16A16 => (4, true),
+    };
+    let bgr = matches!(image.format, Format::B8G8R8 | Format::B8G8R8A8);
+    let bytes_per_channel = if wide { 2 } else { 1 };
+
+    let mut data = Vec::with_capacity((image.width * image.height * 3) as usize);
+    for pixel in image.pixels.chunks_exact(channels * bytes_per_channel) {
+        // 16 bit channels are little endian, keep the high byte
+        let channel = |c: usize| pixel[c * bytes_per_channel + bytes_per_channel - 1];
+        let rgb = match channels {
+            1 | 2 => [channel(0); 3],
+            _ if bgr => [channel(2), channel(1), channel(0)],
+            _ => [channel(0), channel(1), channel(2)],
+        };
+        data.extend_from_slice(&rgb);
+    }
+
+    Arc::new(ImageTexture::new(
+        data,
+        image.width as usize,
+        image.height as usize,
+    ))
+}
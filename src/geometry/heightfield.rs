@@ -0,0 +1,302 @@
+use std::path::Path;
+
+use super::*;
+use crate::texture::Perlin;
+
+/// Terrain over a regular grid of height samples, two triangles per cell.
+///
+/// The grid spans `size.x()` by `size.z()` from `corner`, and heights in [0, 1]
+/// are scaled by `size.y()`. Only the samples are stored: triangles and the
+/// smoothed vertex normals are rebuilt for the cells a ray actually visits,
+/// which are walked front to back with a 2D DDA.
+#[derive(Debug, Clone)]
+pub struct Heightfield<M: Material> {
+    corner: Point,
+    size: Vector3,
+    nx: usize,
+    nz: usize,
+    heights: Vec<f32>,
+    bbox: Aabb,
+    mat: M,
+}
+
+impl<M: Material> Heightfield<M> {
+    pub fn new(
+        corner: Point,
+        size: Vector3,
+        nx: usize,
+        nz: usize,
+        heights: Vec<f64>,
+        mat: M,
+    ) -> Result<Heightfield<M>, String> {
+        if nx < 2 || nz < 2 {
+            return Err(format!(
+                "A heightfield needs at least 2x2 samples, got {}x{}",
+                nx, nz
+            ));
+        }
+        if heights.len() != nx * nz {
+            return Err(format!(
+                "Expected {} height samples for a {}x{} grid, got {}",
+                nx * nz,
+                nx,
+                nz,
+                heights.len()
+            ));
+        }
+
+        let (low, high) = heights
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &h| {
+                (low.min(h), high.max(h))
+            });
+        let bbox = Aabb::new(
+            Point::new(corner.x(), corner.y() + low * size.y() - 0.0001, corner.z()),
+            Point::new(
+                corner.x() + size.x(),
+                corner.y() + high * size.y() + 0.0001,
+                corner.z() + size.z(),
+            ),
+        );
+        let mut field = Heightfield {
+            corner,
+            size,
+            nx,
+            nz,
+            heights: heights.into_iter().map(|h| h as f32).collect(),
+            bbox,
+            mat,
+        };
+        let surface = EmitterSurface::open(field.area());
+        field.mat = field.mat.with_emitter_surface(surface);
+        Ok(field)
+    }
+
+    /// One sample per pixel of a grayscale (or converted) image, brighter is higher.
+    pub fn from_image<P: AsRef<Path>>(
+        path: P,
+        corner: Point,
+        size: Vector3,
+        mat: M,
+    ) -> Result<Heightfield<M>, String> {
+        let img = match image::open(path.as_ref()) {
+            Ok(img) => img.to_luma8(),
+            Err(err) => return Err(format!("Failed to load heightfield image :{}", err)),
+        };
+        let (width, height) = img.dimensions();
+        let heights = img.pixels().map(|p| p[0] as f64 / 255.0).collect();
+        Heightfield::new(corner, size, width as usize, height as usize, heights, mat)
+    }
+
+    /// Samples `perlin` at `noise_scale` over the grid, mapping noise to [0, 1].
+    pub fn from_perlin(
+        perlin: &Perlin,
+        noise_scale: f64,
+        resolution: [usize; 2],
+        corner: Point,
+        size: Vector3,
+        mat: M,
+    ) -> Result<Heightfield<M>, String> {
+        let [nx, nz] = resolution;
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let p = Point::new(
+                    size.x() * i as f64 / (nx.max(2) - 1) as f64,
+                    0.0,
+                    size.z() * j as f64 / (nz.max(2) - 1) as f64,
+                );
+                heights.push((0.5 * (1.0 + perlin.noise(&p, noise_scale))).clamp(0.0, 1.0));
+            }
+        }
+        Heightfield::new(corner, size, nx, nz, heights, mat)
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.size.x() / (self.nx - 1) as f64,
+            self.size.z() / (self.nz - 1) as f64,
+        )
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.corner.y() + self.heights[j * self.nx + i] as f64 * self.size.y()
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point {
+        let (dx, dz) = self.cell_size();
+        Point::new(
+            self.corner.x() + i as f64 * dx,
+            self.height(i, j),
+            self.corner.z() + j as f64 * dz,
+        )
+    }
+
+    fn area(&self) -> f64 {
+        let mut area = 0.0;
+        for j in 0..self.nz - 1 {
+            for i in 0..self.nx - 1 {
+                let corners = [(i, j), (i, j + 1), (i + 1, j + 1), (i + 1, j)];
+                for tri in [[0, 1, 3], [1, 2, 3]] {
+                    area += triangle_area(&tri.map(|k| self.vertex(corners[k].0, corners[k].1)));
+                }
+            }
+        }
+        area
+    }
+
+    /// Normal from central differences of the neighbouring samples.
+    fn vertex_normal(&self, i: usize, j: usize) -> Vector3 {
+        let (dx, dz) = self.cell_size();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.nz - 1));
+        let slope_x = (self.height(i1, j) - self.height(i0, j)) / ((i1 - i0) as f64 * dx);
+        let slope_z = (self.height(i, j1) - self.height(i, j0)) / ((j1 - j0) as f64 * dz);
+        Vector3::new(-slope_x, 1.0, -slope_z).normalize()
+    }
+
+    fn hit_cell(
+        &self,
+        r: &Ray,
+        i: usize,
+        j: usize,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord<'_>> {
+        // counter clockwise seen from above, so the geometric normals point up
+        let corners = [(i, j), (i, j + 1), (i + 1, j + 1), (i + 1, j)];
+        let mut closest = None;
+        let mut closest_so_far = t_max;
+        for tri in [[0, 1, 3], [1, 2, 3]] {
+            let ids = tri.map(|k| corners[k]);
+            let vertices = ids.map(|(vi, vj)| self.vertex(vi, vj));
+            if let Some((t, b1, b2)) = intersect_triangle(r, &vertices, t_min, closest_so_far) {
+                closest_so_far = t;
+                closest = Some((t, b1, b2, ids, vertices));
+            }
+        }
+        let (t, b1, b2, ids, vertices) = closest?;
+
+        let b0 = 1.0 - b1 - b2;
+        let normal = (vertices[1] - vertices[0])
+            .cross(vertices[2] - vertices[0])
+            .normalize();
+        let normals = ids.map(|(vi, vj)| self.vertex_normal(vi, vj));
+        let p = r.at(t);
+        // u and v follow x and z across the field
+        let uv = |vertex: Point| {
+            (
+                (vertex.x() - self.corner.x()) / self.size.x(),
+                (vertex.z() - self.corner.z()) / self.size.z(),
+            )
+        };
+        let [uv0, uv1, uv2] = vertices.map(uv);
+        let (tangent, bitangent) = triangle_tangents(
+            vertices[1] - vertices[0],
+            vertices[2] - vertices[0],
+            (uv1.0 - uv0.0, uv1.1 - uv0.1),
+            (uv2.0 - uv0.0, uv2.1 - uv0.1),
+        );
+        let mut rec = HitRecord {
+            p,
+            normal,
+            t,
+            primitive: 0,
+            u: (p.x() - self.corner.x()) / self.size.x(),
+            v: (p.z() - self.corner.z()) / self.size.z(),
+            front_face: false,
+            tangent,
+            bitangent,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, normal);
+        let shading = (b0 * normals[0] + b1 * normals[1] + b2 * normals[2]).normalize();
+        rec.normal = if rec.front_face { shading } else { -shading };
+        Some(rec)
+    }
+}
+
+impl<M: Material> Hittable for Heightfield<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t_enter, t_exit) = self.bbox.clip(r, t_min, t_max)?;
+        let (dx, dz) = self.cell_size();
+        let (cells_x, cells_z) = (self.nx - 1, self.nz - 1);
+        let (o, d) = (r.origin(), r.dir());
+
+        let start = r.at(t_enter);
+        let mut i =
+            (((start.x() - self.corner.x()) / dx).floor().max(0.0) as usize).min(cells_x - 1);
+        let mut j =
+            (((start.z() - self.corner.z()) / dz).floor().max(0.0) as usize).min(cells_z - 1);
+
+        // ray parameter of the next cell boundary along x and z, and the step between them
+        let axis_setup = |dir: f64, origin: f64, corner: f64, cell: usize, width: f64| {
+            if dir > 0.0 {
+                let boundary = corner + (cell + 1) as f64 * width;
+                ((boundary - origin) / dir, width / dir)
+            } else if dir < 0.0 {
+                let boundary = corner + cell as f64 * width;
+                ((boundary - origin) / dir, -width / dir)
+            } else {
+                (f64::INFINITY, f64::INFINITY)
+            }
+        };
+        let (mut next_x, delta_x) = axis_setup(d.x(), o.x(), self.corner.x(), i, dx);
+        let (mut next_z, delta_z) = axis_setup(d.z(), o.z(), self.corner.z(), j, dz);
+
+        let mut t_cell = t_enter;
+        loop {
+            let t_leave = next_x.min(next_z).min(t_exit);
+
+            // skip cells whose height range the ray passes over or under
+            let cell_range = [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)]
+                .iter()
+                .map(|&(vi, vj)| self.height(vi, vj))
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), h| {
+                    (low.min(h), high.max(h))
+                });
+            let (y0, y1) = (r.at(t_cell).y(), r.at(t_leave).y());
+            if y0.min(y1) <= cell_range.1 && y0.max(y1) >= cell_range.0 {
+                if let Some(rec) = self.hit_cell(r, i, j, t_min, t_max) {
+                    return Some(rec);
+                }
+            }
+
+            if t_leave >= t_exit {
+                return None;
+            }
+            t_cell = t_leave;
+            if next_x < next_z {
+                if d.x() > 0.0 {
+                    if i + 1 >= cells_x {
+                        return None;
+                    }
+                    i += 1;
+                } else {
+                    if i == 0 {
+                        return None;
+                    }
+                    i -= 1;
+                }
+                next_x += delta_x;
+            } else {
+                if d.z() > 0.0 {
+                    if j + 1 >= cells_z {
+                        return None;
+                    }
+                    j += 1;
+                } else {
+                    if j == 0 {
+                        return None;
+                    }
+                    j -= 1;
+                }
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
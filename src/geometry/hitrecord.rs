@@ -6,9 +6,16 @@ pub struct HitRecord<'a> {
     pub normal: Vector3,
     // pub mat: Box<dyn Material>,
     pub t: f64, // hit time
+    // part of the shape that was hit, e.g. the particle of a point cloud; 0 for
+    // shapes of a single part
+    pub primitive: usize,
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    // dp/du and dp/dv along the surface, or at least their directions; zero
+    // when the shape doesn't provide them
+    pub tangent: Vector3,
+    pub bitangent: Vector3,
 
     pub mat: &'a dyn Material,
 }
@@ -5,29 +5,46 @@ use super::*;
 #[derive(Default)]
 pub struct HittableList {
     pub objects: Vec<Box<dyn Hittable>>,
+    // indices of the objects with `Visibility::LIGHT_SAMPLING`, kept up to
+    // date by `new`, `add` and `clear`
+    lights: Vec<usize>,
 }
 
 impl HittableList {
     pub fn new(objects: Vec<Box<dyn Hittable>>) -> HittableList {
-        HittableList { objects }
+        let lights = (0..objects.len())
+            .filter(|&i| is_light(objects[i].as_ref()))
+            .collect();
+        HittableList { objects, lights }
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.lights.clear();
     }
 
     pub fn add(&mut self, object: impl Hittable + 'static) {
+        if is_light(&object) {
+            self.lights.push(self.objects.len());
+        }
         self.objects.push(Box::new(object));
     }
 }
 
+fn is_light(object: &dyn Hittable) -> bool {
+    object.visibility().contains(Visibility::LIGHT_SAMPLING)
+}
+
 impl Hittable for HittableList {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let mut tmp_rec = None;
         let mut closest_so_for = t_max;
 
         for object in &self.objects {
-            if let Some(rec) = object.hit(r, t_min, closest_so_for) {
+            if !object.visibility().sees(r.ray_type()) {
+                continue;
+            }
+            if let Some(rec) = hit_opaque(object.as_ref(), r, t_min, closest_so_for) {
                 closest_so_for = rec.t;
                 tmp_rec = Some(rec);
             }
@@ -49,11 +66,23 @@ impl Hittable for HittableList {
             _ => None,
         }
     }
-    fn pdf_value(&self, o: Point, v: Vector3) -> f64 {
-        self.objects.iter().map(|h| h.pdf_value(o, v)).sum::<f64>() / self.objects.len() as f64
+    // objects hidden from light sampling are left out of both, so they stay consistent
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sum = self
+            .lights
+            .iter()
+            .map(|&i| self.objects[i].pdf_value(o, v, time))
+            .sum::<f64>();
+        sum / self.lights.len() as f64
     }
 
-    fn random(&self, o: Vector3) -> Vector3 {
-        self.objects.choose(&mut rand::thread_rng()).unwrap().random(o)
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        match self.lights.choose(&mut rand::thread_rng()) {
+            Some(&i) => self.objects[i].random(o, time),
+            None => Vector3::new(1.0, 0.0, 0.0),
+        }
     }
 }
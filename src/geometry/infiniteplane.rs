@@ -0,0 +1,95 @@
+use rand::Rng;
+
+use super::*;
+
+/// Unbounded plane through `point`. UVs repeat every unit along two in-plane axes.
+///
+/// It has no bounding box, and since it covers exactly the half of the sphere
+/// of directions facing it, light sampling picks those directions uniformly.
+#[derive(Debug, Clone)]
+pub struct InfinitePlane<M: Material> {
+    point: Point,
+    normal: Vector3,
+    axis_u: Vector3,
+    axis_v: Vector3,
+    mat: M,
+}
+
+impl<M: Material> InfinitePlane<M> {
+    /// Panics on an emitter given a power, which would be spread over an
+    /// infinite area.
+    pub fn new(point: Point, normal: Vector3, mat: M) -> InfinitePlane<M> {
+        assert!(
+            !mat.needs_emitter_surface(),
+            "an infinite plane can't spread a power over its area, give its light in nits"
+        );
+        let uvw = ONB::build_from_w(&normal);
+        InfinitePlane {
+            point,
+            normal: uvw.w(),
+            axis_u: uvw.u(),
+            axis_v: uvw.v(),
+            mat,
+        }
+    }
+}
+
+impl<M: Material> Hittable for InfinitePlane<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.dir());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = self.normal.dot(self.point - r.origin()) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = r.at(t);
+        let offset = p - self.point;
+        let u = offset.dot(self.axis_u);
+        let v = offset.dot(self.axis_v);
+        let mut rec = HitRecord {
+            p,
+            normal: self.normal,
+            t,
+            primitive: 0,
+            u: u - u.floor(),
+            v: v - v.floor(),
+            front_face: false,
+            tangent: self.axis_u,
+            bitangent: self.axis_v,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        None
+    }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        if self
+            .hit(&Ray::new(o, v, 0.0), 0.001, f64::INFINITY)
+            .is_some()
+        {
+            1.0 / (2.0 * PI)
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        let mut rng = rand::thread_rng();
+        let toward_plane = if self.normal.dot(o - self.point) > 0.0 {
+            -self.normal
+        } else {
+            self.normal
+        };
+        let z = rng.gen::<f64>();
+        let r = (1.0 - z * z).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        ONB::build_from_w(&toward_plane).local(&Vector3::new(r * phi.cos(), r * phi.sin(), z))
+    }
+}
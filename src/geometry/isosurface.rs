@@ -0,0 +1,105 @@
+use super::*;
+
+/// Zero level set of an arbitrary scalar field `f` inside `bbox`, with `f < 0`
+/// inside the surface.
+///
+/// Unlike `SdfShape` the field doesn't have to be a distance, so the ray is
+/// stepped in fixed increments until the sign changes and the crossing is then
+/// refined by bisection. Features thinner than a step can be missed; raise the
+/// step count with `with_steps` if that happens.
+pub struct Isosurface<F: Fn(Point) -> f64 + Sync, M: Material> {
+    field: F,
+    bbox: Aabb,
+    steps: usize,
+    mat: M,
+}
+
+impl<F: Fn(Point) -> f64 + Sync, M: Material> Isosurface<F, M> {
+    /// Panics on an emitter given a power, as the area of the surface isn't
+    /// known.
+    pub fn new(field: F, bbox: Aabb, mat: M) -> Isosurface<F, M> {
+        assert!(
+            !mat.needs_emitter_surface(),
+            "an isosurface has no known area to spread a power over, give its light in nits"
+        );
+        Isosurface {
+            field,
+            bbox,
+            steps: 256,
+            mat,
+        }
+    }
+
+    /// Number of samples taken along the longest possible path through `bbox`.
+    pub fn with_steps(mut self, steps: usize) -> Isosurface<F, M> {
+        self.steps = steps.max(1);
+        self
+    }
+
+    fn normal(&self, p: Point) -> Vector3 {
+        let h = 1e-4 * (self.bbox.max - self.bbox.min).length();
+        let mut gradient = Vector3::zero();
+        for axis in 0..3 {
+            let mut offset = Vector3::zero();
+            offset[axis] = h;
+            gradient[axis] = (self.field)(p + offset) - (self.field)(p - offset);
+        }
+        gradient.normalize()
+    }
+}
+
+impl<F: Fn(Point) -> f64 + Sync, M: Material> Hittable for Isosurface<F, M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (start, end) = self.bbox.clip(r, t_min, t_max)?;
+        let step =
+            (self.bbox.max - self.bbox.min).length() / (self.steps as f64 * r.dir().length());
+
+        let mut t0 = start;
+        let mut f0 = (self.field)(r.at(t0));
+        let mut crossing = None;
+        while t0 < end {
+            let t1 = (t0 + step).min(end);
+            let f1 = (self.field)(r.at(t1));
+            if (f0 < 0.0) != (f1 < 0.0) {
+                crossing = Some((t0, f0, t1));
+                break;
+            }
+            t0 = t1;
+            f0 = f1;
+        }
+
+        let (mut lo, f_lo, mut hi) = crossing?;
+        for _ in 0..32 {
+            let mid = 0.5 * (lo + hi);
+            if ((self.field)(r.at(mid)) < 0.0) == (f_lo < 0.0) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let t = 0.5 * (lo + hi);
+        let p = r.at(t);
+        let outward_normal = self.normal(p);
+        let (u, v) = Sphere::<M>::get_sphere_uv(&outward_normal);
+        let (tangent, bitangent) = Sphere::<M>::get_sphere_tangents(&outward_normal);
+        let mut rec = HitRecord {
+            p,
+            normal: outward_normal,
+            t,
+            primitive: 0,
+            u,
+            v,
+            front_face: false,
+            tangent,
+            bitangent,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
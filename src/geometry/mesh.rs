@@ -1,56 +1,219 @@
 use super::*;
+use rand::Rng;
+use std::collections::HashMap;
 use std::path::Path;
 use tobj;
 
+use crate::texture::VertexColorTexture;
+
 use crate::utility::Point;
 
 #[derive(Debug, Clone)]
 pub struct Triangle<M: Material> {
     vectices: [Point; 3],
+    normals: Option<[Vector3; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    tangents: Option<([Vector3; 3], [f64; 3])>,
     mat: M,
+    emission: Option<EmissionGrid>,
 }
 
 impl<M: Material> Triangle<M> {
     pub fn new(vectices: [Point; 3], mat: M) -> Triangle<M> {
-        Triangle { vectices, mat }
+        Triangle {
+            vectices,
+            normals: None,
+            uvs: None,
+            tangents: None,
+            mat: mat.with_emitter_surface(EmitterSurface::open(triangle_area(&vectices))),
+            emission: None,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vector3; 3]) -> Triangle<M> {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Triangle<M> {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Authored tangents and the handedness w of their bitangents,
+    /// cross(normal, tangent) * w, as glTF stores them.
+    pub fn with_tangents(mut self, tangents: [Vector3; 3], handedness: [f64; 3]) -> Triangle<M> {
+        self.tangents = Some((tangents, handedness));
+        self
+    }
+
+    /// Samples light positions in proportion to the emitted luminance,
+    /// tabulated on a `resolution` x `resolution` grid over the unit square
+    /// that `square_to_triangle` maps onto the triangle.
+    pub fn with_importance_sampling(mut self, resolution: usize) -> Triangle<M> {
+        let [v0, v1, v2] = self.vectices;
+        let normal = (v1 - v0).cross(v2 - v0);
+        self.emission = EmissionGrid::tabulate(resolution, |s, t| {
+            let (b1, b2) = square_to_triangle(s, t);
+            emitted_luminance(&self, v0 + b1 * (v1 - v0) + b2 * (v2 - v0), normal)
+        });
+        self
     }
 }
 
+pub fn triangle_area(vertices: &[Point; 3]) -> f64 {
+    0.5 * (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .length()
+}
+
+/// Surface of a set of triangles for an emitter, closed when every edge is
+/// shared by exactly two triangles. Vertices are matched by position, so
+/// seams split for their normals or texture coordinates still close.
+pub fn triangles_surface<I: Iterator<Item = [Point; 3]>>(triangles: I) -> EmitterSurface {
+    let key = |p: Point| [p.x().to_bits(), p.y().to_bits(), p.z().to_bits()];
+    let mut area = 0.0;
+    let mut edges = HashMap::new();
+    for vertices in triangles {
+        area += triangle_area(&vertices);
+        for k in 0..3 {
+            let (a, b) = (key(vertices[k]), key(vertices[(k + 1) % 3]));
+            *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+        }
+    }
+    if !edges.is_empty() && edges.values().all(|&count| count == 2) {
+        EmitterSurface::closed(area)
+    } else {
+        EmitterSurface::open(area)
+    }
+}
+
+/// Barycentric weights of the second and third vertex for a point of the
+/// unit square, preserving area, so uniform points stay uniform.
+pub fn square_to_triangle(s: f64, t: f64) -> (f64, f64) {
+    let root = s.sqrt();
+    (root * (1.0 - t), root * t)
+}
+
+/// The point of the unit square `square_to_triangle` maps to (b1, b2).
+pub fn triangle_to_square(b1: f64, b2: f64) -> (f64, f64) {
+    let sum = b1 + b2;
+    if sum > 0.0 {
+        (sum * sum, b2 / sum)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+/// Möller–Trumbore intersection, returning t and the barycentric weights of
+/// the second and third vertex. Degenerate triangles, with a repeated vertex
+/// or no area, are never hit.
+pub fn intersect_triangle(
+    r: &Ray,
+    vertices: &[Point; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let e1 = vertices[1] - vertices[0];
+    let e2 = vertices[2] - vertices[0];
+
+    let s = r.origin() - vertices[0];
+    let s1 = r.dir().cross(e2);
+    let s2 = s.cross(e1);
+    let s1_e1 = s1.dot(e1);
+    if s1_e1.abs() < 1e-12 {
+        return None;
+    }
+
+    let t = s2.dot(e2) / s1_e1;
+    let b1 = s1.dot(s) / s1_e1;
+    let b2 = s2.dot(r.dir()) / s1_e1;
+    if !t.is_finite() {
+        return None;
+    }
+
+    if t < t_min || t > t_max || b1 < 0.0 || b2 < 0.0 || (1.0 - b1 - b2) < 0.0 {
+        None
+    } else {
+        Some((t, b1, b2))
+    }
+}
+
+/// dp/du and dp/dv of a triangle with edges `e1` and `e2` from its first
+/// vertex and the differences `duv1` and `duv2` of their texture coordinates.
+/// Without usable texture coordinates the barycentric weights of the second
+/// and third vertex are (u, v), which makes the edges themselves the result.
+pub fn triangle_tangents(
+    e1: Vector3,
+    e2: Vector3,
+    duv1: (f64, f64),
+    duv2: (f64, f64),
+) -> (Vector3, Vector3) {
+    let det = duv1.0 * duv2.1 - duv1.1 * duv2.0;
+    if det.abs() < 1e-12 {
+        return (e1, e2);
+    }
+    (
+        (duv2.1 * e1 - duv1.1 * e2) / det,
+        (duv1.0 * e2 - duv2.0 * e1) / det,
+    )
+}
+
 impl<M: Material> Hittable for Triangle<M> {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t, b1, b2) = intersect_triangle(r, &self.vectices, t_min, t_max)?;
         let e1 = self.vectices[1] - self.vectices[0];
         let e2 = self.vectices[2] - self.vectices[0];
-
-        let s = r.origin() - self.vectices[0];
-        let s1 = r.dir().cross(e2);
-        let s2 = s.cross(e1);
-        let s1_e1 = s1.dot(e1);
-
-        let t = s2.dot(e2) / s1_e1;
-        let b1 = s1.dot(s) / s1_e1;
-        let b2 = s2.dot(r.dir()) / s1_e1;
-
-        if t < t_min || t > t_max {
-            None
-        } else {
-            if b1 < 0.0 || b2 > 0.0 || (1.0 - b1 - b2) < 0.0 {
-                None
-            } else {
-                let p = r.at(t);
-                let normal = e1.cross(e2).normalize();
-                let mut rec = HitRecord {
-                    p,
-                    normal,
-                    t,
-                    u: b1,
-                    v: b2,
-                    front_face: false,
-                    mat: &self.mat,
-                };
-                rec.set_face_normal(r, normal);
-                Some(rec)
+        let b0 = 1.0 - b1 - b2;
+        let p = r.at(t);
+        let normal = e1.cross(e2).normalize();
+        let (u, v) = match self.uvs {
+            Some(uvs) => (
+                b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0,
+                b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1,
+            ),
+            None => (b1, b2),
+        };
+        let (tangent, bitangent) = match self.uvs {
+            Some(uvs) => triangle_tangents(
+                e1,
+                e2,
+                (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1),
+                (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1),
+            ),
+            None => (e1, e2),
+        };
+        let shading = self
+            .normals
+            .map(|normals| (b0 * normals[0] + b1 * normals[1] + b2 * normals[2]).normalize());
+        // authored tangents win over derived ones
+        let (tangent, bitangent) = match self.tangents {
+            Some((tangents, w)) => {
+                let tangent = b0 * tangents[0] + b1 * tangents[1] + b2 * tangents[2];
+                let handedness = (b0 * w[0] + b1 * w[1] + b2 * w[2]).signum();
+                let n = shading.unwrap_or(normal);
+                (tangent, n.cross(tangent) * handedness)
             }
+            None => (tangent, bitangent),
+        };
+        let mut rec = HitRecord {
+            p,
+            normal,
+            t,
+            primitive: 0,
+            u,
+            v,
+            front_face: false,
+            tangent,
+            bitangent,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, normal);
+        // shade with the interpolated normal, keeping the side chosen by the geometric one
+        if let Some(shading) = shading {
+            rec.normal = if rec.front_face { shading } else { -shading };
         }
+        Some(rec)
     }
     fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
         let min_x = self.vectices[0]
@@ -64,19 +227,103 @@ impl<M: Material> Hittable for Triangle<M> {
             .min(self.vectices[1].z().min(self.vectices[2].z()));
         let max_x = self.vectices[0]
             .x()
-            .min(self.vectices[1].x().min(self.vectices[2].x()));
+            .max(self.vectices[1].x().max(self.vectices[2].x()));
         let max_y = self.vectices[0]
             .y()
-            .min(self.vectices[1].y().min(self.vectices[2].y()));
+            .max(self.vectices[1].y().max(self.vectices[2].y()));
         let max_z = self.vectices[0]
             .z()
-            .min(self.vectices[1].z().min(self.vectices[2].z()));
+            .max(self.vectices[1].z().max(self.vectices[2].z()));
 
-        let min = Vector3::new(min_x, min_y, min_z);
-        let max = Vector3::new(max_x, max_y, max_z);
+        // pad so that axis-aligned triangles still get a box with volume
+        let min = Vector3::new(min_x, min_y, min_z) - Vector3::one() * 0.0001;
+        let max = Vector3::new(max_x, max_y, max_z) + Vector3::one() * 0.0001;
 
         Some(Aabb { min, max })
     }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        let r = Ray::new(o, v, time);
+        match intersect_triangle(&r, &self.vectices, 0.001, f64::INFINITY) {
+            Some((t, b1, b2)) => {
+                let n = (self.vectices[1] - self.vectices[0])
+                    .cross(self.vectices[2] - self.vectices[0]);
+                let area = 0.5 * n.length();
+                let distance_squared = t.powi(2) * v.length_squared();
+                let cosine = v.dot(n).abs() / (v.length() * n.length());
+                let density = self.emission.as_ref().map_or(1.0, |grid| {
+                    let (s, t) = triangle_to_square(b1, b2);
+                    grid.pdf(s, t)
+                });
+                if cosine > 0.0 {
+                    distance_squared * density / (cosine * area)
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, o: Vector3, _time: f64) -> Vector3 {
+        let mut rng = rand::thread_rng();
+        let (s, t) = match &self.emission {
+            Some(grid) => grid.sample(rng.gen(), rng.gen(), rng.gen()),
+            None => (rng.gen(), rng.gen()),
+        };
+        let (b1, b2) = square_to_triangle(s, t);
+        let [v0, v1, v2] = self.vectices;
+        v0 + b1 * (v1 - v0) + b2 * (v2 - v0) - o
+    }
+}
+
+/// Indexed triangle data as read from a file, before it is turned into a `Mesh`.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Point>,
+    pub normals: Option<Vec<Vector3>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub colors: Option<Vec<Color>>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    fn triangle<M: Material>(&self, face: [usize; 3], mat: M) -> Triangle<M> {
+        let mut tri = Triangle::new(face.map(|i| self.positions[i]), mat);
+        if let Some(normals) = &self.normals {
+            tri = tri.with_normals(face.map(|i| normals[i]));
+        }
+        if let Some(uvs) = &self.uvs {
+            tri = tri.with_uvs(face.map(|i| uvs[i]));
+        }
+        tri
+    }
+
+    pub(crate) fn faces(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|face| [face[0] as usize, face[1] as usize, face[2] as usize])
+    }
+
+    pub(crate) fn surface(&self) -> EmitterSurface {
+        triangles_surface(self.faces().map(|face| face.map(|i| self.positions[i])))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let count = self.positions.len();
+        if self.indices.iter().any(|&i| i as usize >= count) {
+            return Err("mesh index out of range".to_string());
+        }
+        let attribute_lengths = [
+            self.normals.as_ref().map(|n| n.len()),
+            self.uvs.as_ref().map(|uv| uv.len()),
+            self.colors.as_ref().map(|c| c.len()),
+        ];
+        if attribute_lengths.iter().flatten().any(|&len| len != count) {
+            return Err("mesh attribute count does not match vertex count".to_string());
+        }
+        Ok(())
+    }
 }
 
 pub struct Mesh {
@@ -84,12 +331,19 @@ pub struct Mesh {
 }
 
 impl Mesh {
-    pub fn new<M: Material + Copy + Clone + 'static>(
+    pub fn new<M: Material + Clone + 'static>(
         positions: Vec<Vector3>,
         indices: Vec<u32>,
         mat: M,
     ) -> Mesh {
         let mut tris = HittableList::default();
+        let surface = triangles_surface(
+            indices
+                .chunks_exact(3)
+                .map(|face| [0, 1, 2].map(|k| positions[face[k] as usize])),
+        );
+        // the triangles share the power of an emitter
+        let mat = mat.with_emitter_surface(surface.shared());
 
         for iter in 0..indices.len() / 3 {
             let vertices = [
@@ -97,10 +351,44 @@ impl Mesh {
                 positions[indices[iter * 3 + 1] as usize],
                 positions[indices[iter * 3 + 2] as usize],
             ];
-            tris.add(Triangle::new(vertices, mat));
+            tris.add(Triangle::new(vertices, mat.clone()));
+        }
+        Mesh { tris }
+    }
+
+    pub fn from_data<M: Material + Clone + 'static>(data: &MeshData, mat: M) -> Mesh {
+        let mut tris = HittableList::default();
+        let mat = mat.with_emitter_surface(data.surface().shared());
+        for face in data.faces() {
+            tris.add(data.triangle(face, mat.clone()));
         }
         Mesh { tris }
     }
+
+    /// Builds each triangle's material from a texture interpolating its vertex
+    /// colors. The texture reads barycentric coordinates from the hit's (u, v),
+    /// so texture coordinates of the data are dropped. Without vertex colors
+    /// this is `from_data` with the material built from white.
+    pub fn from_data_with_vertex_colors<M, F>(data: &MeshData, mat: F) -> Mesh
+    where
+        M: Material + 'static,
+        F: Fn(VertexColorTexture) -> M,
+    {
+        let mut tris = HittableList::default();
+        let surface = data.surface().shared();
+        for face in data.faces() {
+            let colors = match &data.colors {
+                Some(colors) => face.map(|i| colors[i]),
+                None => [Color::one(); 3],
+            };
+            let mat = mat(VertexColorTexture::new(colors)).with_emitter_surface(surface);
+            let mut tri = data.triangle(face, mat);
+            tri.uvs = None;
+            tris.add(tri);
+        }
+        Mesh { tris }
+    }
+
     pub fn load_obj<'a, P: AsRef<Path>, M: Material + Copy + Clone + 'static>(
         path: P,
         mat: M,
@@ -132,4 +420,12 @@ impl Hittable for Mesh {
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
         self.tris.bounding_box(time0, time1)
     }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        self.tris.pdf_value(o, v, time)
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        self.tris.random(o, time)
+    }
 }
@@ -1,25 +1,70 @@
+pub use self::animatedtransform::*;
 pub use self::bvh::*;
 pub use self::constantmedium::*;
+pub use self::csg::*;
 pub use self::cube::*;
+pub use self::curve::*;
+pub use self::disk::*;
+pub use self::displacement::*;
+pub use self::emission::*;
+pub use self::flipface::*;
+pub use self::gltfscene::*;
+pub use self::heightfield::*;
 pub use self::hitrecord::*;
 pub use self::hittablelist::*;
+pub use self::infiniteplane::*;
+pub use self::isosurface::*;
 pub use self::mesh::*;
+pub use self::pointcloud::*;
+pub use self::quad::*;
+pub use self::quadric::*;
 pub use self::rectangular::*;
 pub use self::rotate::*;
 pub use self::rotate::*;
+pub use self::sdf::*;
 pub use self::sphere::*;
+pub use self::subdivision::*;
+pub use self::subsurface::*;
+pub use self::torus::*;
 pub use self::translate::*;
+pub use self::trianglemesh::*;
+pub use self::visible::*;
 
+pub mod animatedtransform;
 pub mod bvh;
 pub mod constantmedium;
+pub mod csg;
 pub mod cube;
+pub mod curve;
+pub mod disk;
+pub mod displacement;
+pub mod emission;
+pub mod flipface;
+pub mod gltfscene;
+pub mod heightfield;
 pub mod hitrecord;
 pub mod hittablelist;
+pub mod infiniteplane;
+pub mod isosurface;
 pub mod mesh;
+pub mod ply;
+pub mod pointcloud;
+pub mod quad;
+pub mod quadric;
 pub mod rectangular;
 pub mod rotate;
+pub mod sdf;
 pub mod sphere;
+pub mod stl;
+pub mod subdivision;
+pub mod subsurface;
+pub mod torus;
 pub mod translate;
+pub mod trianglemesh;
+pub mod visible;
+
+#[cfg(test)]
+mod tests;
 
 use crate::material::*;
 use crate::utility::*;
@@ -27,10 +72,35 @@ use crate::utility::*;
 pub trait Hittable: Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
     fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb>;
-    fn pdf_value(&self, _o: Point, _v: Vector3) -> f64 {
+    fn pdf_value(&self, _o: Point, _v: Vector3, _time: f64) -> f64 {
         0.0
     }
-    fn random(&self, o: Vector3) -> Vector3 {
+    fn random(&self, o: Vector3, _time: f64) -> Vector3 {
         Vector3::new(1.0, 0.0, 0.0)
     }
+    /// Ray types the object shows up for, see `Visible`.
+    fn visibility(&self) -> Visibility {
+        Visibility::ALL
+    }
+}
+
+// cut out layers looked through before a ray gives up, e.g. on dense foliage
+const MAX_CUT_OUT_HITS: usize = 64;
+
+/// Closest hit on `hittable` whose material doesn't cut it out (see
+/// `AlphaMask`), searching on past the holes.
+pub fn hit_opaque<'a, H: Hittable + ?Sized>(
+    hittable: &'a H,
+    r: &Ray,
+    mut t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    for _ in 0..MAX_CUT_OUT_HITS {
+        let rec = hittable.hit(r, t_min, t_max)?;
+        if !rec.mat.is_cut_out(r, &rec) {
+            return Some(rec);
+        }
+        t_min = rec.t + 1e-9 * rec.t.abs().max(1.0);
+    }
+    None
 }
@@ -0,0 +1,358 @@
+use super::*;
+use crate::texture::ParticleColorTexture;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PlyScalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> Result<PlyScalar, String> {
+        match name {
+            "char" | "int8" => Ok(PlyScalar::I8),
+            "uchar" | "uint8" => Ok(PlyScalar::U8),
+            "short" | "int16" => Ok(PlyScalar::I16),
+            "ushort" | "uint16" => Ok(PlyScalar::U16),
+            "int" | "int32" => Ok(PlyScalar::I32),
+            "uint" | "uint32" => Ok(PlyScalar::U32),
+            "float" | "float32" => Ok(PlyScalar::F32),
+            "double" | "float64" => Ok(PlyScalar::F64),
+            _ => Err(format!("unknown ply property type {}", name)),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyScalar::I8 | PlyScalar::U8 => 1,
+            PlyScalar::I16 | PlyScalar::U16 => 2,
+            PlyScalar::I32 | PlyScalar::U32 | PlyScalar::F32 => 4,
+            PlyScalar::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PlyProperty {
+    Scalar(String, PlyScalar),
+    List(String, PlyScalar, PlyScalar),
+}
+
+#[derive(Debug, Clone)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+/// Walks the body of a ply file value by value, whichever encoding it uses.
+struct PlyReader<'a> {
+    format: PlyFormat,
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PlyReader<'a> {
+    fn read(&mut self, scalar: PlyScalar) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            return self.read_token();
+        }
+        let size = scalar.size();
+        let raw = self
+            .bytes
+            .get(self.offset..self.offset + size)
+            .ok_or_else(|| "unexpected end of ply data".to_string())?;
+        self.offset += size;
+
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(raw);
+        if self.format == PlyFormat::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        let value = match scalar {
+            PlyScalar::I8 => buf[0] as i8 as f64,
+            PlyScalar::U8 => buf[0] as f64,
+            PlyScalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyScalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyScalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyScalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyScalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyScalar::F64 => f64::from_le_bytes(buf),
+        };
+        Ok(value)
+    }
+
+    fn read_token(&mut self) -> Result<f64, String> {
+        while self.offset < self.bytes.len() && self.bytes[self.offset].is_ascii_whitespace() {
+            self.offset += 1;
+        }
+        let start = self.offset;
+        while self.offset < self.bytes.len() && !self.bytes[self.offset].is_ascii_whitespace() {
+            self.offset += 1;
+        }
+        if start == self.offset {
+            return Err("unexpected end of ply data".to_string());
+        }
+        let token = std::str::from_utf8(&self.bytes[start..self.offset])
+            .map_err(|_| "invalid ply ascii data".to_string())?;
+        token
+            .parse::<f64>()
+            .map_err(|_| format!("invalid ply value {}", token))
+    }
+}
+
+fn parse_header(bytes: &[u8]) -> Result<(PlyFormat, Vec<PlyElement>, usize), String> {
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let end = bytes[offset..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| "ply header is not terminated".to_string())?;
+        let line = std::str::from_utf8(&bytes[offset..offset + end])
+            .map_err(|_| "invalid ply header".to_string())?
+            .trim();
+        let first_line = offset == 0;
+        offset += end + 1;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if first_line {
+            if words != ["ply"] {
+                return Err("not a ply file".to_string());
+            }
+            continue;
+        }
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(PlyFormat::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(PlyFormat::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(PlyFormat::BinaryBigEndian),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("invalid ply element count {}", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements
+                .last_mut()
+                .ok_or_else(|| "ply property before any element".to_string())?
+                .properties
+                .push(PlyProperty::List(
+                    name.to_string(),
+                    PlyScalar::parse(count_type)?,
+                    PlyScalar::parse(item_type)?,
+                )),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| "ply property before any element".to_string())?
+                .properties
+                .push(PlyProperty::Scalar(
+                    name.to_string(),
+                    PlyScalar::parse(scalar)?,
+                )),
+            ["end_header"] => break,
+            _ => {} // comment, obj_info or blank lines
+        }
+    }
+
+    match format {
+        Some(format) => Ok((format, elements, offset)),
+        None => Err("ply header has no format line".to_string()),
+    }
+}
+
+type PlyScalars<'a> = [(&'a str, PlyScalar, f64)];
+
+/// Reads the ply file at `path` and hands every element record, with its
+/// scalar and list properties, to `record` in file order.
+fn read_records<P, F>(path: P, mut record: F) -> Result<(), String>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str, &PlyScalars, &[(&str, Vec<f64>)]) -> Result<(), String>,
+{
+    let bytes =
+        fs::read(path.as_ref()).map_err(|err| format!("Failed to load ply file :{}", err))?;
+    let (format, elements, offset) = parse_header(&bytes)?;
+    let mut reader = PlyReader {
+        format,
+        bytes: &bytes,
+        offset,
+    };
+
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut scalars: Vec<(&str, PlyScalar, f64)> = Vec::new();
+            let mut lists: Vec<(&str, Vec<f64>)> = Vec::new();
+            for property in &element.properties {
+                match property {
+                    PlyProperty::Scalar(name, scalar) => {
+                        scalars.push((name, *scalar, reader.read(*scalar)?))
+                    }
+                    PlyProperty::List(name, count_type, item_type) => {
+                        let count = reader.read(*count_type)? as usize;
+                        let items = (0..count)
+                            .map(|_| reader.read(*item_type))
+                            .collect::<Result<Vec<f64>, String>>()?;
+                        lists.push((name, items));
+                    }
+                }
+            }
+            record(&element.name, &scalars, &lists)?;
+        }
+    }
+    Ok(())
+}
+
+/// The first of the scalar properties called one of `names`.
+fn scalar(scalars: &PlyScalars, names: &[&str]) -> Option<(PlyScalar, f64)> {
+    scalars
+        .iter()
+        .find(|(name, _, _)| names.contains(name))
+        .map(|&(_, scalar, value)| (scalar, value))
+}
+
+fn coord(scalars: &PlyScalars, name: &str) -> f64 {
+    scalar(scalars, &[name]).map_or(0.0, |(_, value)| value)
+}
+
+fn vertex_color(scalars: &PlyScalars) -> Option<Color> {
+    // integer colors are 0..255, float colors are 0..1
+    let channel = |names: &[&str]| {
+        scalar(scalars, names).map(|(scalar, value)| match scalar {
+            PlyScalar::F32 | PlyScalar::F64 => value,
+            _ => value / 255.0,
+        })
+    };
+    Some(Color::new(
+        channel(&["red", "diffuse_red"])?,
+        channel(&["green", "diffuse_green"])?,
+        channel(&["blue", "diffuse_blue"])?,
+    ))
+}
+
+impl MeshData {
+    /// Reads ascii and binary (little and big endian) ply files. Polygons are
+    /// fan-triangulated; normals (nx, ny, nz), colors (red, green, blue) and
+    /// texture coordinates (u/v, s/t or texture_u/texture_v) are kept when present.
+    pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<MeshData, String> {
+        let mut data = MeshData::default();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        let mut colors = Vec::new();
+
+        read_records(path, |element, scalars, lists| {
+            match element {
+                "vertex" => {
+                    data.positions.push(Point::new(
+                        coord(scalars, "x"),
+                        coord(scalars, "y"),
+                        coord(scalars, "z"),
+                    ));
+
+                    if let (Some(nx), Some(ny), Some(nz)) = (
+                        scalar(scalars, &["nx"]),
+                        scalar(scalars, &["ny"]),
+                        scalar(scalars, &["nz"]),
+                    ) {
+                        normals.push(Vector3::new(nx.1, ny.1, nz.1).normalize());
+                    }
+                    if let (Some(u), Some(v)) = (
+                        scalar(scalars, &["u", "s", "texture_u"]),
+                        scalar(scalars, &["v", "t", "texture_v"]),
+                    ) {
+                        uvs.push((u.1, v.1));
+                    }
+                    if let Some(color) = vertex_color(scalars) {
+                        colors.push(color);
+                    }
+                }
+                "face" => {
+                    if let Some((_, polygon)) = lists
+                        .iter()
+                        .find(|(name, _)| *name == "vertex_indices" || *name == "vertex_index")
+                    {
+                        for iter in 1..polygon.len().saturating_sub(1) {
+                            data.indices.push(polygon[0] as u32);
+                            data.indices.push(polygon[iter] as u32);
+                            data.indices.push(polygon[iter + 1] as u32);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+
+        let count = data.positions.len();
+        data.normals = Some(normals).filter(|n| n.len() == count && count > 0);
+        data.uvs = Some(uvs).filter(|uv| uv.len() == count && count > 0);
+        data.colors = Some(colors).filter(|c| c.len() == count && count > 0);
+        data.validate()?;
+        Ok(data)
+    }
+}
+
+impl Mesh {
+    pub fn load_ply<P: AsRef<Path>, M: Material + Clone + 'static>(
+        path: P,
+        mat: M,
+    ) -> Result<Mesh, String> {
+        Ok(Mesh::from_data(&MeshData::load_ply(path)?, mat))
+    }
+}
+
+impl<M: Material> PointCloud<M> {
+    /// Reads the vertices of a ply file as particles. The radius comes from a
+    /// radius, pscale or scale property, or is `radius` for files without one.
+    /// With vertex colors the material `mat` builds reads them per particle,
+    /// otherwise it is built from white.
+    pub fn load_ply<P, F>(path: P, radius: f64, mat: F) -> Result<PointCloud<M>, String>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(ParticleColorTexture) -> M,
+    {
+        let mut centers = Vec::new();
+        let mut radii = Vec::new();
+        let mut colors = Vec::new();
+        read_records(path, |element, scalars, _| {
+            if element == "vertex" {
+                centers.push(Point::new(
+                    coord(scalars, "x"),
+                    coord(scalars, "y"),
+                    coord(scalars, "z"),
+                ));
+                radii.push(
+                    scalar(scalars, &["radius", "pscale", "scale"]).map_or(radius, |(_, r)| r),
+                );
+                if let Some(color) = vertex_color(scalars) {
+                    colors.push(color);
+                }
+            }
+            Ok(())
+        })?;
+
+        if !colors.is_empty() && colors.len() == centers.len() {
+            PointCloud::with_colors(centers, radii, colors, mat)
+        } else {
+            let white = ParticleColorTexture::new(vec![Color::one()]);
+            PointCloud::new(centers, radii, mat(white))
+        }
+    }
+}
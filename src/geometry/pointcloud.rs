@@ -0,0 +1,247 @@
+use super::*;
+use crate::texture::ParticleColorTexture;
+
+// particles per BVH leaf
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy)]
+struct PointCloudNode {
+    bbox: Aabb,
+    // leaves: first particle and count, branches: index of the right child and 0
+    // (the left child directly follows its parent)
+    start: u32,
+    count: u32,
+}
+
+/// Large set of spheres, e.g. the output of a particle simulation.
+///
+/// Centers and radii are kept in flat `f32` arrays behind a BVH of their own
+/// instead of one boxed `Sphere` per particle. All particles share one
+/// material; with `with_colors` it reads each particle's color from a
+/// `ParticleColorTexture`. Hits keep the sphere uv of the particle and carry
+/// its index as their `primitive`.
+pub struct PointCloud<M: Material> {
+    centers: Vec<[f32; 3]>,
+    radii: Vec<f32>,
+    mat: M,
+    nodes: Vec<PointCloudNode>,
+}
+
+fn to_point([x, y, z]: [f32; 3]) -> Point {
+    Point::new(x as f64, y as f64, z as f64)
+}
+
+fn particle_box(center: [f32; 3], radius: f32) -> Aabb {
+    let r = radius as f64;
+    let extent = Vector3::new(r, r, r);
+    Aabb::new(to_point(center) - extent, to_point(center) + extent)
+}
+
+impl<M: Material> PointCloud<M> {
+    pub fn new(centers: Vec<Point>, radii: Vec<f64>, mat: M) -> Result<PointCloud<M>, String> {
+        PointCloud::build(centers, radii, |_| mat)
+    }
+
+    /// Particles with their own color. `mat` builds the shared material from a
+    /// texture of the colors, which looks up the particle a hit is on.
+    pub fn with_colors<F>(
+        centers: Vec<Point>,
+        radii: Vec<f64>,
+        colors: Vec<Color>,
+        mat: F,
+    ) -> Result<PointCloud<M>, String>
+    where
+        F: FnOnce(ParticleColorTexture) -> M,
+    {
+        if colors.len() != centers.len() {
+            return Err("point cloud color count does not match point count".to_string());
+        }
+        PointCloud::build(centers, radii, |order| {
+            let colors = order.iter().map(|&i| colors[i as usize]).collect();
+            mat(ParticleColorTexture::new(colors))
+        })
+    }
+
+    /// `mat` gets the particles' original indices in their stored order.
+    fn build<F>(centers: Vec<Point>, radii: Vec<f64>, mat: F) -> Result<PointCloud<M>, String>
+    where
+        F: FnOnce(&[u32]) -> M,
+    {
+        if centers.is_empty() {
+            return Err("point cloud has no points".to_string());
+        }
+        if centers.len() > u32::MAX as usize {
+            return Err("point cloud has too many points".to_string());
+        }
+        // a single radius applies to every particle
+        let radii = match radii.len() {
+            1 => vec![radii[0]; centers.len()],
+            len if len == centers.len() => radii,
+            _ => return Err("point cloud radius count does not match point count".to_string()),
+        };
+        if radii.iter().any(|&r| r.is_nan() || r <= 0.0) {
+            return Err("point cloud radius must be positive".to_string());
+        }
+
+        let centers: Vec<[f32; 3]> = centers
+            .iter()
+            .map(|c| [c.x() as f32, c.y() as f32, c.z() as f32])
+            .collect();
+        let area = radii.iter().map(|&r| 4.0 * PI * r * r).sum();
+        let radii: Vec<f32> = radii.iter().map(|&r| r as f32).collect();
+
+        let mut nodes = Vec::new();
+        let mut order: Vec<u32> = (0..centers.len() as u32).collect();
+        PointCloud::<M>::build_node(&centers, &radii, &mut nodes, &mut order, 0);
+
+        // store the particles in leaf order so that leaves address contiguous ranges
+        Ok(PointCloud {
+            centers: order.iter().map(|&i| centers[i as usize]).collect(),
+            radii: order.iter().map(|&i| radii[i as usize]).collect(),
+            mat: mat(&order).with_emitter_surface(EmitterSurface::closed(area)),
+            nodes,
+        })
+    }
+
+    fn center(&self, i: usize) -> Point {
+        to_point(self.centers[i])
+    }
+
+    /// Appends the subtree over `order` (a slice of the full order starting at
+    /// `offset`), splitting at the median of the longest axis of the centers.
+    fn build_node(
+        centers: &[[f32; 3]],
+        radii: &[f32],
+        nodes: &mut Vec<PointCloudNode>,
+        order: &mut [u32],
+        offset: usize,
+    ) {
+        let bbox = order
+            .iter()
+            .map(|&i| particle_box(centers[i as usize], radii[i as usize]))
+            .reduce(|a, b| Aabb::surrounding_box(&a, &b))
+            .unwrap();
+        let index = nodes.len();
+        nodes.push(PointCloudNode {
+            bbox,
+            start: offset as u32,
+            count: order.len() as u32,
+        });
+        if order.len() <= LEAF_SIZE {
+            return;
+        }
+
+        let (mut min, mut max) = (
+            to_point(centers[order[0] as usize]),
+            to_point(centers[order[0] as usize]),
+        );
+        for &i in order.iter() {
+            let c = to_point(centers[i as usize]);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(c[axis]);
+                max[axis] = max[axis].max(c[axis]);
+            }
+        }
+        let extent = max - min;
+        let axis = (0..3)
+            .max_by(|&a, &b| extent[a].partial_cmp(&extent[b]).unwrap())
+            .unwrap();
+        let mid = order.len() / 2;
+        order.select_nth_unstable_by(mid, |&a, &b| {
+            centers[a as usize][axis].total_cmp(&centers[b as usize][axis])
+        });
+
+        let (left, right) = order.split_at_mut(mid);
+        PointCloud::<M>::build_node(centers, radii, nodes, left, offset);
+        let right_index = nodes.len();
+        PointCloud::<M>::build_node(centers, radii, nodes, right, offset + mid);
+        nodes[index].start = right_index as u32;
+        nodes[index].count = 0;
+    }
+
+    fn hit_particle(&self, i: usize, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let center = self.center(i);
+        let radius = self.radii[i] as f64;
+        let oc = r.origin() - center;
+        let a = r.dir().length_squared();
+        let half_b = oc.dot(r.dir());
+        let c = oc.length_squared() - radius * radius;
+
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrt_d = discriminant.sqrt();
+        let mut root = (-half_b - sqrt_d) / a;
+        if root < t_min || root > t_max {
+            root = (-half_b + sqrt_d) / a;
+            if root < t_min || root > t_max {
+                return None;
+            }
+        }
+
+        let p = r.at(root);
+        let outward_normal = (p - center) / radius;
+        let (u, v) = Sphere::<M>::get_sphere_uv(&outward_normal);
+        let (tangent, bitangent) = Sphere::<M>::get_sphere_tangents(&outward_normal);
+        let mut rec = HitRecord {
+            p,
+            normal: outward_normal,
+            t: root,
+            primitive: i,
+            u,
+            v,
+            front_face: false,
+            tangent: radius * tangent,
+            bitangent: radius * bitangent,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+}
+
+impl<M: Material> Hittable for PointCloud<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest: Option<HitRecord> = None;
+        let mut t_max = t_max;
+        let mut stack = vec![0usize];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bbox.clip(r, t_min, t_max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                let start = node.start as usize;
+                for i in start..start + node.count as usize {
+                    if let Some(rec) = self.hit_particle(i, r, t_min, t_max) {
+                        t_max = rec.t;
+                        closest = Some(rec);
+                    }
+                }
+                continue;
+            }
+
+            // visit the nearer child first so it can shorten the search in the other
+            let (left, right) = (index + 1, node.start as usize);
+            let entry = |child: usize| {
+                self.nodes[child]
+                    .bbox
+                    .clip(r, t_min, t_max)
+                    .map_or(f64::INFINITY, |(t0, _)| t0)
+            };
+            if entry(left) <= entry(right) {
+                stack.push(right);
+                stack.push(left);
+            } else {
+                stack.push(left);
+                stack.push(right);
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(self.nodes[0].bbox)
+    }
+}
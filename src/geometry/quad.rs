@@ -0,0 +1,127 @@
+use rand::Rng;
+
+use super::*;
+
+/// Parallelogram spanned by two edge vectors from a corner.
+#[derive(Debug, Clone)]
+pub struct Quad<M: Material> {
+    origin: Point,
+    u: Vector3,
+    v: Vector3,
+    normal: Vector3,
+    d: f64,
+    w: Vector3,
+    area: f64,
+    mat: M,
+    emission: Option<EmissionGrid>,
+}
+
+impl<M: Material> Quad<M> {
+    pub fn new(origin: Point, u: Vector3, v: Vector3, mat: M) -> Quad<M> {
+        let n = u.cross(v);
+        let normal = n.normalize();
+        Quad {
+            origin,
+            u,
+            v,
+            normal,
+            d: normal.dot(origin),
+            w: n / n.length_squared(),
+            area: n.length(),
+            mat: mat.with_emitter_surface(EmitterSurface::open(n.length())),
+            emission: None,
+        }
+    }
+
+    /// Samples light positions in proportion to the emitted luminance,
+    /// tabulated on a `resolution` x `resolution` grid, so that the bright
+    /// parts of a textured emitter get most of the samples.
+    pub fn with_importance_sampling(mut self, resolution: usize) -> Quad<M> {
+        self.emission = EmissionGrid::tabulate(resolution, |alpha, beta| {
+            let p = self.origin + alpha * self.u + beta * self.v;
+            emitted_luminance(&self, p, self.normal)
+        });
+        self
+    }
+}
+
+impl<M: Material> Hittable for Quad<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denom = self.normal.dot(r.dir());
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // express the hit point in the (u, v) frame of the quad
+        let p = r.at(t);
+        let planar = p - self.origin;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord {
+            p,
+            normal: self.normal,
+            t,
+            primitive: 0,
+            u: alpha,
+            v: beta,
+            front_face: false,
+            tangent: self.u,
+            bitangent: self.v,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, self.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let corners = [
+            self.origin + self.u,
+            self.origin + self.v,
+            self.origin + self.u + self.v,
+        ];
+        let bbox = corners
+            .iter()
+            .fold(Aabb::new(self.origin, self.origin), |acc, &corner| {
+                Aabb::surrounding_box(&acc, &Aabb::new(corner, corner))
+            });
+        // a quad lying in an axis plane has no extent along that axis
+        let pad = Vector3::one() * 0.0001;
+        Some(Aabb::new(bbox.min - pad, bbox.max + pad))
+    }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        if let Some(rec) = self.hit(&Ray::new(o, v, time), 0.001, f64::INFINITY) {
+            let distance_squared = rec.t.powi(2) * v.length_squared();
+            let cosine = v.dot(rec.normal).abs() / v.length();
+            let density = self
+                .emission
+                .as_ref()
+                .map_or(1.0, |grid| grid.pdf(rec.u, rec.v));
+            if cosine != 0.0 {
+                distance_squared * density / (cosine * self.area)
+            } else {
+                0.0
+            }
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        let mut rng = rand::thread_rng();
+        let (alpha, beta) = match &self.emission {
+            Some(grid) => grid.sample(rng.gen(), rng.gen(), rng.gen()),
+            None => (rng.gen(), rng.gen()),
+        };
+        let random_point = self.origin + alpha * self.u + beta * self.v;
+        random_point - o
+    }
+}
@@ -0,0 +1,564 @@
+use super::*;
+
+// Quadrics are modelled around a vertical (y) axis through `base`; move and
+// tilt them with Translate / Rotate. Like pbrt's `phiMax`, `with_phi_max`
+// sweeps the surface only part of the way around the axis.
+
+/// Angle around the y axis in [0, 2pi), measured from +x towards +z.
+pub fn sweep_angle(local: Point) -> f64 {
+    let phi = local.z().atan2(local.x());
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+/// Intersection in the shape's local frame.
+pub struct LocalHit {
+    pub t: f64,
+    pub local: Point,
+    pub normal: Vector3,
+    pub u: f64,
+    pub v: f64,
+    /// dp/du and dp/dv
+    pub tangent: Vector3,
+    pub bitangent: Vector3,
+}
+
+impl LocalHit {
+    pub fn record<'a>(self, r: &Ray, base: Point, mat: &'a dyn Material) -> HitRecord<'a> {
+        let mut rec = HitRecord {
+            p: self.local + base,
+            normal: self.normal,
+            t: self.t,
+            primitive: 0,
+            u: self.u,
+            v: self.v,
+            front_face: false,
+            tangent: self.tangent,
+            bitangent: self.bitangent,
+            mat,
+        };
+        rec.set_face_normal(r, self.normal);
+        rec
+    }
+}
+
+pub fn closer(a: Option<LocalHit>, b: Option<LocalHit>) -> Option<LocalHit> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.t < a.t { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Disk closing a quadric at height `y`, facing up or down along `normal_y`.
+#[derive(Debug, Clone, Copy)]
+pub struct Cap {
+    pub y: f64,
+    pub radius: f64,
+    pub normal_y: f64,
+}
+
+pub fn hit_cap(
+    o: Point,
+    d: Vector3,
+    t_min: f64,
+    t_max: f64,
+    cap: Cap,
+    phi_max: f64,
+) -> Option<LocalHit> {
+    let Cap {
+        y,
+        radius,
+        normal_y,
+    } = cap;
+    if d.y().abs() < 1e-12 {
+        return None;
+    }
+    let t = (y - o.y()) / d.y();
+    if t < t_min || t > t_max {
+        return None;
+    }
+    let local = o + t * d;
+    let distance = (local.x().powi(2) + local.z().powi(2)).sqrt();
+    let phi = sweep_angle(local);
+    if distance > radius || phi > phi_max {
+        return None;
+    }
+    Some(LocalHit {
+        t,
+        local,
+        normal: Vector3::new(0.0, normal_y, 0.0),
+        u: phi / phi_max,
+        v: distance / radius,
+        tangent: phi_max * Vector3::new(-local.z(), 0.0, local.x()),
+        bitangent: Vector3::new(local.x(), 0.0, local.z()) * (radius / distance.max(1e-12)),
+    })
+}
+
+/// Area swept by turning the profile `radius(y)`, y from `y0` to `y1`, through
+/// `phi_max` around the axis, summed over frustums along the profile.
+pub fn swept_area<F: Fn(f64) -> f64>(radius: F, y0: f64, y1: f64, phi_max: f64) -> f64 {
+    const STEPS: usize = 256;
+    let profile = |i: usize| {
+        let y = y0 + (y1 - y0) * i as f64 / STEPS as f64;
+        (radius(y), y)
+    };
+    (0..STEPS)
+        .map(|i| {
+            let ((r0, y0), (r1, y1)) = (profile(i), profile(i + 1));
+            0.5 * phi_max * (r0 + r1) * (r1 - r0).hypot(y1 - y0)
+        })
+        .sum()
+}
+
+/// Surface of a quadric with `lateral` area, closed by cap disks of the
+/// given radii when `capped`. Only a full sweep closes it.
+pub fn quadric_surface(lateral: f64, caps: &[f64], capped: bool, phi_max: f64) -> EmitterSurface {
+    if !capped {
+        return EmitterSurface::open(lateral);
+    }
+    let area = lateral + caps.iter().map(|r| 0.5 * phi_max * r * r).sum::<f64>();
+    if phi_max >= 2.0 * PI {
+        EmitterSurface::closed(area)
+    } else {
+        EmitterSurface::open(area)
+    }
+}
+
+/// Implicit quadric surface in local coordinates.
+trait QuadricSurface {
+    /// Coefficients of a t^2 + b t + c along the local ray.
+    fn coefficients(&self, o: Point, d: Vector3) -> (f64, f64, f64);
+    fn contains(&self, local: Point) -> bool;
+    fn normal(&self, local: Point) -> Vector3;
+    fn v(&self, local: Point) -> f64;
+}
+
+fn hit_surface<S: QuadricSurface>(
+    shape: &S,
+    o: Point,
+    d: Vector3,
+    t_min: f64,
+    t_max: f64,
+    phi_max: f64,
+) -> Option<LocalHit> {
+    let (a, b, c) = shape.coefficients(o, d);
+    solve_quadratic(a, b, c)
+        .into_iter()
+        .filter(|&t| t >= t_min && t <= t_max)
+        .find_map(|t| {
+            let local = o + t * d;
+            let phi = sweep_angle(local);
+            if !shape.contains(local) || phi > phi_max {
+                return None;
+            }
+            let normal = shape.normal(local).normalize();
+            // v grows linearly up the axis, so it changes along the surface in
+            // the steepest uphill direction
+            let uphill = Vector3::new(0.0, 1.0, 0.0) - normal * normal.y();
+            let dv = shape.v(local + uphill) - shape.v(local);
+            Some(LocalHit {
+                t,
+                local,
+                normal,
+                u: phi / phi_max,
+                v: shape.v(local),
+                tangent: phi_max * Vector3::new(-local.z(), 0.0, local.x()),
+                bitangent: if dv.abs() > 1e-12 {
+                    uphill / dv
+                } else {
+                    Vector3::zero()
+                },
+            })
+        })
+}
+
+#[derive(Debug, Clone)]
+pub struct Cylinder<M: Material> {
+    base: Point,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    mat: M,
+}
+
+impl<M: Material> Cylinder<M> {
+    pub fn new(base: Point, radius: f64, height: f64, mat: M) -> Cylinder<M> {
+        Cylinder {
+            base,
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: false,
+            mat,
+        }
+        .with_emitter()
+    }
+
+    pub fn with_phi_max(mut self, degrees: f64) -> Cylinder<M> {
+        self.phi_max = degress_to_radians(degrees.clamp(0.0, 360.0));
+        self.with_emitter()
+    }
+
+    pub fn capped(mut self, capped: bool) -> Cylinder<M> {
+        self.capped = capped;
+        self.with_emitter()
+    }
+
+    // hands an emitter the surface again whenever it changes
+    fn with_emitter(mut self) -> Cylinder<M> {
+        let lateral = self.phi_max * self.radius * self.height;
+        let surface = quadric_surface(
+            lateral,
+            &[self.radius, self.radius],
+            self.capped,
+            self.phi_max,
+        );
+        self.mat = self.mat.with_emitter_surface(surface);
+        self
+    }
+}
+
+impl<M: Material> QuadricSurface for Cylinder<M> {
+    fn coefficients(&self, o: Point, d: Vector3) -> (f64, f64, f64) {
+        (
+            d.x() * d.x() + d.z() * d.z(),
+            2.0 * (o.x() * d.x() + o.z() * d.z()),
+            o.x() * o.x() + o.z() * o.z() - self.radius * self.radius,
+        )
+    }
+    fn contains(&self, local: Point) -> bool {
+        (0.0..=self.height).contains(&local.y())
+    }
+    fn normal(&self, local: Point) -> Vector3 {
+        Vector3::new(local.x(), 0.0, local.z())
+    }
+    fn v(&self, local: Point) -> f64 {
+        local.y() / self.height
+    }
+}
+
+impl<M: Material> Hittable for Cylinder<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = (r.origin() - self.base, r.dir());
+        let mut hit = hit_surface(self, o, d, t_min, t_max, self.phi_max);
+        if self.capped {
+            for (y, normal_y) in [(0.0, -1.0), (self.height, 1.0)] {
+                let cap = Cap {
+                    y,
+                    radius: self.radius,
+                    normal_y,
+                };
+                hit = closer(hit, hit_cap(o, d, t_min, t_max, cap, self.phi_max));
+            }
+        }
+        hit.map(|hit| hit.record(r, self.base, &self.mat))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let extent = Vector3::new(self.radius, 0.0, self.radius);
+        Some(Aabb::new(
+            self.base - extent,
+            self.base + extent + Vector3::new(0.0, self.height, 0.0),
+        ))
+    }
+}
+
+/// Cone with its base disk at `base` and its apex `height` above it.
+#[derive(Debug, Clone)]
+pub struct Cone<M: Material> {
+    base: Point,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    mat: M,
+}
+
+impl<M: Material> Cone<M> {
+    pub fn new(base: Point, radius: f64, height: f64, mat: M) -> Cone<M> {
+        Cone {
+            base,
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: false,
+            mat,
+        }
+        .with_emitter()
+    }
+
+    pub fn with_phi_max(mut self, degrees: f64) -> Cone<M> {
+        self.phi_max = degress_to_radians(degrees.clamp(0.0, 360.0));
+        self.with_emitter()
+    }
+
+    pub fn capped(mut self, capped: bool) -> Cone<M> {
+        self.capped = capped;
+        self.with_emitter()
+    }
+
+    // hands an emitter the surface again whenever it changes
+    fn with_emitter(mut self) -> Cone<M> {
+        let lateral = 0.5 * self.phi_max * self.radius * self.radius.hypot(self.height);
+        let surface = quadric_surface(lateral, &[self.radius], self.capped, self.phi_max);
+        self.mat = self.mat.with_emitter_surface(surface);
+        self
+    }
+}
+
+impl<M: Material> QuadricSurface for Cone<M> {
+    fn coefficients(&self, o: Point, d: Vector3) -> (f64, f64, f64) {
+        // x^2 + z^2 = k^2 (h - y)^2
+        let k2 = (self.radius / self.height).powi(2);
+        let h = self.height - o.y();
+        (
+            d.x() * d.x() + d.z() * d.z() - k2 * d.y() * d.y(),
+            2.0 * (o.x() * d.x() + o.z() * d.z() + k2 * h * d.y()),
+            o.x() * o.x() + o.z() * o.z() - k2 * h * h,
+        )
+    }
+    fn contains(&self, local: Point) -> bool {
+        (0.0..=self.height).contains(&local.y())
+    }
+    fn normal(&self, local: Point) -> Vector3 {
+        let k2 = (self.radius / self.height).powi(2);
+        Vector3::new(local.x(), k2 * (self.height - local.y()), local.z())
+    }
+    fn v(&self, local: Point) -> f64 {
+        local.y() / self.height
+    }
+}
+
+impl<M: Material> Hittable for Cone<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = (r.origin() - self.base, r.dir());
+        let mut hit = hit_surface(self, o, d, t_min, t_max, self.phi_max);
+        if self.capped {
+            let cap = Cap {
+                y: 0.0,
+                radius: self.radius,
+                normal_y: -1.0,
+            };
+            hit = closer(hit, hit_cap(o, d, t_min, t_max, cap, self.phi_max));
+        }
+        hit.map(|hit| hit.record(r, self.base, &self.mat))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let extent = Vector3::new(self.radius, 0.0, self.radius);
+        Some(Aabb::new(
+            self.base - extent,
+            self.base + extent + Vector3::new(0.0, self.height, 0.0),
+        ))
+    }
+}
+
+/// Paraboloid opening upwards from its apex at `base`, `radius` wide at `height`.
+#[derive(Debug, Clone)]
+pub struct Paraboloid<M: Material> {
+    base: Point,
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    capped: bool,
+    mat: M,
+}
+
+impl<M: Material> Paraboloid<M> {
+    pub fn new(base: Point, radius: f64, height: f64, mat: M) -> Paraboloid<M> {
+        Paraboloid {
+            base,
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: false,
+            mat,
+        }
+        .with_emitter()
+    }
+
+    pub fn with_phi_max(mut self, degrees: f64) -> Paraboloid<M> {
+        self.phi_max = degress_to_radians(degrees.clamp(0.0, 360.0));
+        self.with_emitter()
+    }
+
+    pub fn capped(mut self, capped: bool) -> Paraboloid<M> {
+        self.capped = capped;
+        self.with_emitter()
+    }
+
+    // hands an emitter the surface again whenever it changes
+    fn with_emitter(mut self) -> Paraboloid<M> {
+        let lateral = swept_area(
+            |y| self.radius * (y / self.height).max(0.0).sqrt(),
+            0.0,
+            self.height,
+            self.phi_max,
+        );
+        let surface = quadric_surface(lateral, &[self.radius], self.capped, self.phi_max);
+        self.mat = self.mat.with_emitter_surface(surface);
+        self
+    }
+}
+
+impl<M: Material> QuadricSurface for Paraboloid<M> {
+    fn coefficients(&self, o: Point, d: Vector3) -> (f64, f64, f64) {
+        // y = k (x^2 + z^2)
+        let k = self.height / (self.radius * self.radius);
+        (
+            k * (d.x() * d.x() + d.z() * d.z()),
+            2.0 * k * (o.x() * d.x() + o.z() * d.z()) - d.y(),
+            k * (o.x() * o.x() + o.z() * o.z()) - o.y(),
+        )
+    }
+    fn contains(&self, local: Point) -> bool {
+        (0.0..=self.height).contains(&local.y())
+    }
+    fn normal(&self, local: Point) -> Vector3 {
+        let k = self.height / (self.radius * self.radius);
+        Vector3::new(2.0 * k * local.x(), -1.0, 2.0 * k * local.z())
+    }
+    fn v(&self, local: Point) -> f64 {
+        local.y() / self.height
+    }
+}
+
+impl<M: Material> Hittable for Paraboloid<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = (r.origin() - self.base, r.dir());
+        let mut hit = hit_surface(self, o, d, t_min, t_max, self.phi_max);
+        if self.capped {
+            let cap = Cap {
+                y: self.height,
+                radius: self.radius,
+                normal_y: 1.0,
+            };
+            hit = closer(hit, hit_cap(o, d, t_min, t_max, cap, self.phi_max));
+        }
+        hit.map(|hit| hit.record(r, self.base, &self.mat))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let extent = Vector3::new(self.radius, 0.0, self.radius);
+        Some(Aabb::new(
+            self.base - extent,
+            self.base + extent + Vector3::new(0.0, self.height, 0.0),
+        ))
+    }
+}
+
+/// Hyperboloid of one sheet centered on `center`, `waist_radius` wide in the
+/// middle and `end_radius` wide at both ends, `height / 2` above and below.
+#[derive(Debug, Clone)]
+pub struct Hyperboloid<M: Material> {
+    center: Point,
+    waist_radius: f64,
+    end_radius: f64,
+    height: f64,
+    c2: f64,
+    phi_max: f64,
+    capped: bool,
+    mat: M,
+}
+
+impl<M: Material> Hyperboloid<M> {
+    pub fn new(
+        center: Point,
+        waist_radius: f64,
+        end_radius: f64,
+        height: f64,
+        mat: M,
+    ) -> Hyperboloid<M> {
+        // end_radius^2 = waist_radius^2 (1 + (height / 2)^2 / c^2)
+        let flare = (end_radius * end_radius - waist_radius * waist_radius).max(1e-12);
+        Hyperboloid {
+            center,
+            waist_radius,
+            end_radius,
+            height,
+            c2: (height / 2.0).powi(2) * waist_radius * waist_radius / flare,
+            phi_max: 2.0 * PI,
+            capped: false,
+            mat,
+        }
+        .with_emitter()
+    }
+
+    pub fn with_phi_max(mut self, degrees: f64) -> Hyperboloid<M> {
+        self.phi_max = degress_to_radians(degrees.clamp(0.0, 360.0));
+        self.with_emitter()
+    }
+
+    pub fn capped(mut self, capped: bool) -> Hyperboloid<M> {
+        self.capped = capped;
+        self.with_emitter()
+    }
+
+    // hands an emitter the surface again whenever it changes
+    fn with_emitter(mut self) -> Hyperboloid<M> {
+        let lateral = swept_area(
+            |y| self.waist_radius * (1.0 + y * y / self.c2).sqrt(),
+            -self.height / 2.0,
+            self.height / 2.0,
+            self.phi_max,
+        );
+        let surface = quadric_surface(
+            lateral,
+            &[self.end_radius, self.end_radius],
+            self.capped,
+            self.phi_max,
+        );
+        self.mat = self.mat.with_emitter_surface(surface);
+        self
+    }
+}
+
+impl<M: Material> QuadricSurface for Hyperboloid<M> {
+    fn coefficients(&self, o: Point, d: Vector3) -> (f64, f64, f64) {
+        // (x^2 + z^2) / a^2 - y^2 / c^2 = 1
+        let a2 = self.waist_radius * self.waist_radius;
+        (
+            (d.x() * d.x() + d.z() * d.z()) / a2 - d.y() * d.y() / self.c2,
+            2.0 * ((o.x() * d.x() + o.z() * d.z()) / a2 - o.y() * d.y() / self.c2),
+            (o.x() * o.x() + o.z() * o.z()) / a2 - o.y() * o.y() / self.c2 - 1.0,
+        )
+    }
+    fn contains(&self, local: Point) -> bool {
+        local.y().abs() <= self.height / 2.0
+    }
+    fn normal(&self, local: Point) -> Vector3 {
+        let a2 = self.waist_radius * self.waist_radius;
+        Vector3::new(local.x() / a2, -local.y() / self.c2, local.z() / a2)
+    }
+    fn v(&self, local: Point) -> f64 {
+        local.y() / self.height + 0.5
+    }
+}
+
+impl<M: Material> Hittable for Hyperboloid<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = (r.origin() - self.center, r.dir());
+        let mut hit = hit_surface(self, o, d, t_min, t_max, self.phi_max);
+        if self.capped {
+            let half = self.height / 2.0;
+            for (y, normal_y) in [(-half, -1.0), (half, 1.0)] {
+                let cap = Cap {
+                    y,
+                    radius: self.end_radius,
+                    normal_y,
+                };
+                hit = closer(hit, hit_cap(o, d, t_min, t_max, cap, self.phi_max));
+            }
+        }
+        hit.map(|hit| hit.record(r, self.center, &self.mat))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let extent = Vector3::new(self.end_radius, self.height / 2.0, self.end_radius);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
@@ -18,6 +18,7 @@ pub struct AARect<M: Material> {
     b1: f64,
     k: f64,
     material: M,
+    emission: Option<EmissionGrid>,
 }
 
 fn get_axis_index(plane: &Plane) -> (usize, usize, usize) {
@@ -37,9 +38,33 @@ impl<M: Material> AARect<M> {
             b0,
             b1,
             k,
-            material,
+            material: material.with_emitter_surface(EmitterSurface::open((a1 - a0) * (b1 - b0))),
+            emission: None,
         }
     }
+
+    /// Samples light positions in proportion to the emitted luminance,
+    /// tabulated on a `resolution` x `resolution` grid, so that the bright
+    /// parts of a textured emitter get most of the samples.
+    pub fn with_importance_sampling(mut self, resolution: usize) -> AARect<M> {
+        let (k_axis, _, _) = get_axis_index(&self.plane);
+        let mut normal = Vector3::zero();
+        normal[k_axis] = 1.0;
+        self.emission = EmissionGrid::tabulate(resolution, |u, v| {
+            emitted_luminance(&self, self.point(u, v), normal)
+        });
+        self
+    }
+
+    /// Point at (u, v) of the rectangle.
+    fn point(&self, u: f64, v: f64) -> Point {
+        let (k_axis, a_axis, b_axis) = get_axis_index(&self.plane);
+        let mut p = Vector3::zero();
+        p[a_axis] = self.a0 + u * (self.a1 - self.a0);
+        p[b_axis] = self.b0 + v * (self.b1 - self.b0);
+        p[k_axis] = self.k;
+        p
+    }
 }
 
 impl<M: Material> Hittable for AARect<M> {
@@ -59,14 +84,21 @@ impl<M: Material> Hittable for AARect<M> {
                 let p = r.at(t);
                 let mut normal = Vector3::new(0.0, 0.0, 0.0);
                 normal[k_axis_index] = 1.0;
+                let mut tangent = Vector3::zero();
+                tangent[a_axis_index] = self.a1 - self.a0;
+                let mut bitangent = Vector3::zero();
+                bitangent[b_axis_index] = self.b1 - self.b0;
 
                 let mut rec = HitRecord {
                     p,
                     normal,
                     t,
+                    primitive: 0,
                     u,
                     v,
                     front_face: false,
+                    tangent,
+                    bitangent,
                     mat: &self.material,
                 };
 
@@ -78,21 +110,32 @@ impl<M: Material> Hittable for AARect<M> {
     }
 
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
-        // the bounding box must have non-zero width in each dimension, so pad the Z dimension a small amount.
-        let min = Vector3::new(self.a0, self.b0, self.k - 0.0001);
-        let max = Vector3::new(self.a1, self.b1, self.k + 0.0001);
+        // the bounding box must have non-zero width in each dimension, so pad the k dimension a small amount.
+        let (k_axis, a_axis, b_axis) = get_axis_index(&self.plane);
+        let mut min = Vector3::zero();
+        let mut max = Vector3::zero();
+        min[a_axis] = self.a0;
+        max[a_axis] = self.a1;
+        min[b_axis] = self.b0;
+        max[b_axis] = self.b1;
+        min[k_axis] = self.k - 0.0001;
+        max[k_axis] = self.k + 0.0001;
 
         Some(Aabb::new(min, max))
     }
 
-    fn pdf_value(&self, o: Point, v: Vector3) -> f64 {
-        if let Some(rec) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::INFINITY) {
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        if let Some(rec) = self.hit(&Ray::new(o, v, time), 0.001, f64::INFINITY) {
             // integration by substitution
             let area = (self.a1 - self.a0) * (self.b1 - self.b0);
             let distance_squared = rec.t.powi(2) * v.length_squared();
             let cosine = v.dot(rec.normal).abs() / v.length();
+            let density = self
+                .emission
+                .as_ref()
+                .map_or(1.0, |grid| grid.pdf(rec.u, rec.v));
             if cosine != 0.0 {
-                distance_squared / (cosine * area)
+                distance_squared * density / (cosine * area)
             } else {
                 0.0
             }
@@ -100,13 +143,12 @@ impl<M: Material> Hittable for AARect<M> {
             0.0
         }
     }
-    fn random(&self, o: Vector3) -> Vector3 {
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
         let mut rng = rand::thread_rng();
-        let (k_axis, a_axis, b_axis) = get_axis_index(&self.plane);
-        let mut random_point = Vector3::zero();
-        random_point[a_axis] = rng.gen_range(self.a0..self.a1);
-        random_point[b_axis] = rng.gen_range(self.b0..self.b1);
-        random_point[k_axis] = self.k;
-        random_point - o
+        let (u, v) = match &self.emission {
+            Some(grid) => grid.sample(rng.gen(), rng.gen(), rng.gen()),
+            None => (rng.gen(), rng.gen()),
+        };
+        self.point(u, v) - o
     }
 }
@@ -23,90 +23,92 @@ pub struct Rotate<H: Hittable> {
     sin_theta: f64,
     cos_theta: f64,
     hittable: H,
-    rbox: Option<Aabb>,
 }
 
 impl<H: Hittable> Rotate<H> {
     pub fn new(axis: RotateAxis, hittable: H, angle: f64) -> Rotate<H> {
-        let (r_axis, a_axis, b_axis) = RotateAxis::get_axis_index(&axis);
         let radiants = degress_to_radians(angle);
         let sin_theta = radiants.sin();
         let cos_theta = radiants.cos();
 
-        let rbox = hittable.bounding_box(0.0, 1.0).map(|mut rbox| {
-            let mut min = Vector3::new(f64::MIN, f64::MIN, f64::MIN);
-            let mut max = Vector3::new(f64::MAX, f64::MAX, -f64::MAX);
-
-            for iter in 0..=1 {
-                for jter in 0..=1 {
-                    for kter in 0..=1 {
-                        let r =
-                            kter as f64 * rbox.max[r_axis] + (1 - kter) as f64 * rbox.min[r_axis];
-                        let a =
-                            iter as f64 * rbox.max[a_axis] + (1 - iter) as f64 * rbox.min[a_axis];
-                        let b =
-                            jter as f64 * rbox.max[b_axis] + (1 - jter) as f64 * rbox.min[b_axis];
-
-                        let new_a = cos_theta * a + sin_theta * b;
-                        let new_b = -sin_theta * a + cos_theta * b;
-
-                        min[a_axis] = min[a_axis].min(new_a);
-                        min[b_axis] = min[b_axis].min(new_b);
-                        min[r_axis] = min[r_axis].min(r);
-
-                        max[a_axis] = max[a_axis].max(new_a);
-                        max[b_axis] = max[b_axis].max(new_b);
-                        max[r_axis] = max[r_axis].max(r);
-                    }
-                }
-            }
-            rbox.min = min;
-            rbox.max = max;
-            rbox
-        });
-
         Rotate {
             axis,
             sin_theta,
             cos_theta,
             hittable,
-            rbox,
         }
     }
-}
-impl<H: Hittable> Hittable for Rotate<H> {
-    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let (_, a_axis, b_axis) = RotateAxis::get_axis_index(&self.axis);
-        let mut origin = r.origin();
-        let mut direction = r.dir();
-
-        origin[a_axis] =
-            &self.cos_theta * r.origin()[a_axis] - &self.sin_theta * r.origin()[b_axis];
-        origin[b_axis] =
-            &self.sin_theta * r.origin()[a_axis] + &self.cos_theta * r.origin()[b_axis];
 
-        direction[a_axis] = &self.cos_theta * r.dir()[a_axis] - &self.sin_theta * r.dir()[b_axis];
-        direction[b_axis] = &self.sin_theta * r.dir()[a_axis] + &self.cos_theta * r.dir()[b_axis];
+    /// Rotates a world space point or direction into the space of the wrapped object.
+    fn to_object(&self, v: Vector3) -> Vector3 {
+        let (_, a_axis, b_axis) = RotateAxis::get_axis_index(&self.axis);
+        let mut rotated = v;
+        rotated[a_axis] = self.cos_theta * v[a_axis] - self.sin_theta * v[b_axis];
+        rotated[b_axis] = self.sin_theta * v[a_axis] + self.cos_theta * v[b_axis];
+        rotated
+    }
 
-        let rotate_ray = Ray::new(origin, direction, r.time());
+    fn to_world(&self, v: Vector3) -> Vector3 {
+        let (_, a_axis, b_axis) = RotateAxis::get_axis_index(&self.axis);
+        let mut rotated = v;
+        rotated[a_axis] = self.cos_theta * v[a_axis] + self.sin_theta * v[b_axis];
+        rotated[b_axis] = -self.sin_theta * v[a_axis] + self.cos_theta * v[b_axis];
+        rotated
+    }
+}
 
+impl<H: Hittable> Hittable for Rotate<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let rotate_ray = Ray::new(
+            self.to_object(r.origin()),
+            self.to_object(r.dir()),
+            r.time(),
+        )
+        .with_type(r.ray_type());
+
+        // a rotation keeps t and the side of the surface the ray is on
         self.hittable.hit(&rotate_ray, t_min, t_max).map(|mut hit| {
-            let mut position = hit.p;
-            let mut normal = hit.normal;
-            position[a_axis] = &self.cos_theta * hit.p[a_axis] + &self.sin_theta * hit.p[b_axis];
-            position[b_axis] = -&self.sin_theta * hit.p[a_axis] + &self.cos_theta * hit.p[b_axis];
-            normal[a_axis] =
-                &self.cos_theta * hit.normal[a_axis] + &self.sin_theta * hit.normal[b_axis];
-            normal[b_axis] =
-                -&self.sin_theta * hit.normal[a_axis] + &self.cos_theta * hit.normal[b_axis];
-
-            hit.p = position;
-            hit.set_face_normal(&rotate_ray, normal);
+            hit.p = self.to_world(hit.p);
+            hit.normal = self.to_world(hit.normal);
+            hit.tangent = self.to_world(hit.tangent);
+            hit.bitangent = self.to_world(hit.bitangent);
             hit
         })
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        self.rbox.clone()
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let rbox = self.hittable.bounding_box(time0, time1)?;
+        let mut min = Vector3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = -min;
+        for corner in 0..8 {
+            let mut p = Vector3::zero();
+            for axis in 0..3 {
+                p[axis] = if corner >> axis & 1 == 0 {
+                    rbox.min[axis]
+                } else {
+                    rbox.max[axis]
+                };
+            }
+            let p = self.to_world(p);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        Some(Aabb::new(min, max))
+    }
+
+    // rotations keep solid angles, so the densities carry over unchanged
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        self.hittable
+            .pdf_value(self.to_object(o), self.to_object(v), time)
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        self.to_world(self.hittable.random(self.to_object(o), time))
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.hittable.visibility()
     }
 }
@@ -0,0 +1,356 @@
+use super::*;
+
+/// Signed distance to a surface: negative inside, positive outside.
+///
+/// `distance` may underestimate the true distance but must never overestimate
+/// it, otherwise sphere tracing steps through the surface. `bounding_box` has
+/// to contain every point where the distance is not positive.
+pub trait Sdf: Sync {
+    fn distance(&self, p: Point) -> f64;
+    fn bounding_box(&self) -> Aabb;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SdfSphere {
+    center: Point,
+    radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(center: Point, radius: f64) -> SdfSphere {
+        SdfSphere { center, radius }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Point) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vector3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+}
+
+/// Axis aligned box, optionally with rounded edges of `rounding` radius.
+#[derive(Debug, Clone, Copy)]
+pub struct SdfBox {
+    center: Point,
+    half_extents: Vector3,
+    rounding: f64,
+}
+
+impl SdfBox {
+    pub fn new(center: Point, half_extents: Vector3) -> SdfBox {
+        SdfBox {
+            center,
+            half_extents,
+            rounding: 0.0,
+        }
+    }
+
+    pub fn rounded(mut self, rounding: f64) -> SdfBox {
+        self.rounding = rounding.max(0.0);
+        self
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Point) -> f64 {
+        let p = p - self.center;
+        let mut q = Vector3::zero();
+        for axis in 0..3 {
+            q[axis] = p[axis].abs() - self.half_extents[axis] + self.rounding;
+        }
+        let outside = Vector3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+        let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+        outside.length() + inside - self.rounding
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(
+            self.center - self.half_extents,
+            self.center + self.half_extents,
+        )
+    }
+}
+
+/// Torus around the vertical axis through `center`, like `Torus`.
+#[derive(Debug, Clone, Copy)]
+pub struct SdfTorus {
+    center: Point,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl SdfTorus {
+    pub fn new(center: Point, major_radius: f64, minor_radius: f64) -> SdfTorus {
+        SdfTorus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Point) -> f64 {
+        let p = p - self.center;
+        let ring = (p.x().powi(2) + p.z().powi(2)).sqrt() - self.major_radius;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = Vector3::new(outer, self.minor_radius, outer);
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+}
+
+/// Segment from `a` to `b` thickened by `radius`.
+#[derive(Debug, Clone, Copy)]
+pub struct SdfCapsule {
+    a: Point,
+    b: Point,
+    radius: f64,
+}
+
+impl SdfCapsule {
+    pub fn new(a: Point, b: Point, radius: f64) -> SdfCapsule {
+        SdfCapsule { a, b, radius }
+    }
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(&self, p: Point) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = if ba.near_zero() {
+            0.0
+        } else {
+            (pa.dot(ba) / ba.length_squared()).clamp(0.0, 1.0)
+        };
+        (pa - h * ba).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let extent = Vector3::new(self.radius, self.radius, self.radius);
+        let ends = Aabb::surrounding_box(&Aabb::new(self.a, self.a), &Aabb::new(self.b, self.b));
+        Aabb::new(ends.min - extent, ends.max + extent)
+    }
+}
+
+/// Union that blends the two shapes together over a distance of `smoothness`.
+#[derive(Debug, Clone, Copy)]
+pub struct SmoothUnion<A: Sdf, B: Sdf> {
+    a: A,
+    b: B,
+    smoothness: f64,
+}
+
+impl<A: Sdf, B: Sdf> SmoothUnion<A, B> {
+    pub fn new(a: A, b: B, smoothness: f64) -> SmoothUnion<A, B> {
+        SmoothUnion {
+            a,
+            b,
+            smoothness: smoothness.max(0.0),
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: Point) -> f64 {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.smoothness == 0.0 {
+            return da.min(db);
+        }
+        // polynomial smooth minimum, never above min(da, db)
+        let h = (0.5 + 0.5 * (db - da) / self.smoothness).clamp(0.0, 1.0);
+        db + (da - db) * h - self.smoothness * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // the blend bulges out by at most a quarter of the smoothness
+        let bulge = 0.25 * self.smoothness;
+        let extent = Vector3::new(bulge, bulge, bulge);
+        let union = Aabb::surrounding_box(&self.a.bounding_box(), &self.b.bounding_box());
+        Aabb::new(union.min - extent, union.max + extent)
+    }
+}
+
+/// Twists the shape around the y axis by `rate` radians per unit of height.
+#[derive(Debug, Clone, Copy)]
+pub struct Twist<S: Sdf> {
+    sdf: S,
+    rate: f64,
+    lipschitz: f64,
+}
+
+impl<S: Sdf> Twist<S> {
+    pub fn new(sdf: S, rate: f64) -> Twist<S> {
+        // the twist stretches space by up to sqrt(1 + (rate * r)^2) at radius r,
+        // so distances are scaled down to stay conservative
+        let bbox = sdf.bounding_box();
+        let radius = twist_radius(&bbox);
+        Twist {
+            sdf,
+            rate,
+            lipschitz: (1.0 + (rate * radius).powi(2)).sqrt(),
+        }
+    }
+}
+
+fn twist_radius(bbox: &Aabb) -> f64 {
+    let x = bbox.min.x().abs().max(bbox.max.x().abs());
+    let z = bbox.min.z().abs().max(bbox.max.z().abs());
+    (x * x + z * z).sqrt()
+}
+
+impl<S: Sdf> Sdf for Twist<S> {
+    fn distance(&self, p: Point) -> f64 {
+        let (sin, cos) = (-self.rate * p.y()).sin_cos();
+        let q = Vector3::new(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z());
+        self.sdf.distance(q) / self.lipschitz
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.sdf.bounding_box();
+        let radius = twist_radius(&bbox);
+        Aabb::new(
+            Vector3::new(-radius, bbox.min.y(), -radius),
+            Vector3::new(radius, bbox.max.y(), radius),
+        )
+    }
+}
+
+/// Copies of the shape on a grid of `spacing`, `count` copies to each side of
+/// the original along every axis. The shape should fit within one cell.
+#[derive(Debug, Clone, Copy)]
+pub struct Repetition<S: Sdf> {
+    sdf: S,
+    spacing: Vector3,
+    count: [u32; 3],
+}
+
+impl<S: Sdf> Repetition<S> {
+    pub fn new(sdf: S, spacing: Vector3, count: [u32; 3]) -> Repetition<S> {
+        Repetition {
+            sdf,
+            spacing,
+            count,
+        }
+    }
+}
+
+impl<S: Sdf> Sdf for Repetition<S> {
+    fn distance(&self, p: Point) -> f64 {
+        let mut q = p;
+        for axis in 0..3 {
+            if self.spacing[axis] > 0.0 {
+                let n = self.count[axis] as f64;
+                let cell = (p[axis] / self.spacing[axis]).round().clamp(-n, n);
+                q[axis] -= self.spacing[axis] * cell;
+            }
+        }
+        self.sdf.distance(q)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = self.sdf.bounding_box();
+        let mut extent = Vector3::zero();
+        for axis in 0..3 {
+            extent[axis] = self.spacing[axis].max(0.0) * self.count[axis] as f64;
+        }
+        Aabb::new(bbox.min - extent, bbox.max + extent)
+    }
+}
+
+const SDF_MAX_STEPS: usize = 512;
+const SDF_EPSILON: f64 = 1e-5;
+
+/// Renders an `Sdf` by sphere tracing inside its bounding box.
+pub struct SdfShape<S: Sdf, M: Material> {
+    sdf: S,
+    bbox: Aabb,
+    mat: M,
+}
+
+impl<S: Sdf, M: Material> SdfShape<S, M> {
+    /// Panics on an emitter given a power, as the area of the surface isn't
+    /// known.
+    pub fn new(sdf: S, mat: M) -> SdfShape<S, M> {
+        assert!(
+            !mat.needs_emitter_surface(),
+            "an SDF shape has no known area to spread a power over, give its light in nits"
+        );
+        // pad so the surface is never exactly on the box when clipping rays
+        let pad = Vector3::new(0.0001, 0.0001, 0.0001);
+        let bbox = sdf.bounding_box();
+        SdfShape {
+            bbox: Aabb::new(bbox.min - pad, bbox.max + pad),
+            sdf,
+            mat,
+        }
+    }
+
+    fn normal(&self, p: Point) -> Vector3 {
+        let h = 1e-4;
+        let mut gradient = Vector3::zero();
+        for axis in 0..3 {
+            let mut offset = Vector3::zero();
+            offset[axis] = h;
+            gradient[axis] = self.sdf.distance(p + offset) - self.sdf.distance(p - offset);
+        }
+        gradient.normalize()
+    }
+}
+
+impl<S: Sdf, M: Material> Hittable for SdfShape<S, M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (start, end) = self.bbox.clip(r, t_min, t_max)?;
+        let speed = r.dir().length();
+
+        // march on the unsigned distance so rays starting inside find the exit
+        let mut t = start;
+        let mut hit_t = None;
+        for _ in 0..SDF_MAX_STEPS {
+            if t > end {
+                break;
+            }
+            let distance = self.sdf.distance(r.at(t)).abs();
+            if distance < SDF_EPSILON * t.max(1.0) {
+                hit_t = Some(t);
+                break;
+            }
+            t += distance / speed;
+        }
+        let t = hit_t?;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let p = r.at(t);
+        let outward_normal = self.normal(p);
+        let (u, v) = Sphere::<M>::get_sphere_uv(&outward_normal);
+        let (tangent, bitangent) = Sphere::<M>::get_sphere_tangents(&outward_normal);
+        let mut rec = HitRecord {
+            p,
+            normal: outward_normal,
+            t,
+            primitive: 0,
+            u,
+            v,
+            front_face: false,
+            tangent,
+            bitangent,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, outward_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
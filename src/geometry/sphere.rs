@@ -1,11 +1,12 @@
 use super::*;
 use rand::Rng;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Sphere<M: Material> {
     center: Point,
     radius: f64,
     mat: M,
+    emission: Option<EmissionGrid>,
 }
 
 impl<M: Material> Sphere<M> {
@@ -13,10 +14,32 @@ impl<M: Material> Sphere<M> {
         Sphere {
             center,
             radius,
-            mat,
+            mat: mat.with_emitter_surface(EmitterSurface::closed(4.0 * PI * radius * radius)),
+            emission: None,
         }
     }
 
+    /// Samples light positions on the whole sphere in proportion to the
+    /// emitted luminance, tabulated on a `resolution` x `resolution` grid,
+    /// instead of uniformly over the cone the sphere is seen in. The grid
+    /// spans u and the height along the axis, so its cells have equal areas.
+    pub fn with_importance_sampling(mut self, resolution: usize) -> Sphere<M> {
+        self.emission = EmissionGrid::tabulate(resolution, |u, h| {
+            let normal = Sphere::<M>::sphere_point(u, h);
+            emitted_luminance(&self, self.center + self.radius * normal, normal)
+        });
+        self
+    }
+
+    /// Point on the unit sphere at `u` and the height `h` from the bottom,
+    /// both in [0, 1].
+    fn sphere_point(u: f64, h: f64) -> Vector3 {
+        let cos_theta = 1.0 - 2.0 * h;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u;
+        Vector3::new(-sin_theta * phi.cos(), -cos_theta, sin_theta * phi.sin())
+    }
+
     pub fn get_sphere_uv(p: &Point) -> (f64, f64) {
         let theta = (-p.y()).acos();
         let phi = (-p.z()).atan2(p.x()) + PI;
@@ -27,6 +50,18 @@ impl<M: Material> Sphere<M> {
         (u, v)
     }
 
+    /// dp/du and dp/dv of `get_sphere_uv` on the unit sphere at `p`; scale
+    /// them by the radius for a larger sphere. Both vanish at the poles.
+    pub fn get_sphere_tangents(p: &Point) -> (Vector3, Vector3) {
+        let rho = (p.x() * p.x() + p.z() * p.z()).sqrt();
+        if rho < 1e-12 {
+            return (Vector3::zero(), Vector3::zero());
+        }
+        let dpdu = 2.0 * PI * Vector3::new(p.z(), 0.0, -p.x());
+        let dpdv = PI * Vector3::new(-p.y() * p.x() / rho, rho, -p.y() * p.z() / rho);
+        (dpdu, dpdv)
+    }
+
     pub fn random_to_sphere(radius: f64, distance_squared: f64) -> Vector3 {
         let mut rng = rand::thread_rng();
         let r1 = rng.gen::<f64>();
@@ -63,13 +98,17 @@ impl<M: Material> Hittable for Sphere<M> {
 
         let p = r.at(root);
         let outward_normal = (p - self.center) / self.radius;
+        let (tangent, bitangent) = Sphere::<M>::get_sphere_tangents(&outward_normal);
         let mut rec = HitRecord {
             p: p,
             normal: Vector3::default(),
             t: root,
+            primitive: 0,
             u: 0.0,
             v: 0.0,
             front_face: false,
+            tangent: self.radius * tangent,
+            bitangent: self.radius * bitangent,
             mat: &self.mat,
         };
         rec.set_face_normal(r, outward_normal);
@@ -85,8 +124,29 @@ impl<M: Material> Hittable for Sphere<M> {
         Some(Aabb { min, max })
     }
 
-    fn pdf_value(&self, o: Point, v: Vector3) -> f64 {
-        if let Some(_hit) = self.hit(&Ray::new(o, v, 0.0), 0.001, f64::INFINITY) {
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        if let Some(grid) = &self.emission {
+            // samples land anywhere on the sphere, so the far side counts too
+            let r = Ray::new(o, v, time);
+            let mut t_min = 0.001;
+            let mut pdf = 0.0;
+            for _ in 0..2 {
+                let rec = match self.hit(&r, t_min, f64::INFINITY) {
+                    Some(rec) => rec,
+                    None => break,
+                };
+                let distance_squared = rec.t.powi(2) * v.length_squared();
+                let cosine = v.dot(rec.normal).abs() / v.length();
+                let area = 4.0 * PI * self.radius.powi(2);
+                let h = (1.0 - (PI * rec.v).cos()) / 2.0;
+                if cosine > 0.0 {
+                    pdf += grid.pdf(rec.u, h) * distance_squared / (cosine * area);
+                }
+                t_min = rec.t + 1e-9 * rec.t.abs().max(1.0);
+            }
+            return pdf;
+        }
+        if let Some(_hit) = self.hit(&Ray::new(o, v, time), 0.001, f64::INFINITY) {
             let cos_theta_max =
                 (1.0 - self.radius.powi(2) / (self.center - o).length_squared()).sqrt();
             let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
@@ -96,7 +156,12 @@ impl<M: Material> Hittable for Sphere<M> {
         }
     }
 
-    fn random(&self, o: Vector3) -> Vector3 {
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        if let Some(grid) = &self.emission {
+            let mut rng = rand::thread_rng();
+            let (u, h) = grid.sample(rng.gen(), rng.gen(), rng.gen());
+            return self.center + self.radius * Sphere::<M>::sphere_point(u, h) - o;
+        }
         let direction = self.center - o;
         let distance_squared = direction.length_squared();
         let uvw = ONB::build_from_w(&direction);
@@ -129,12 +194,13 @@ impl<M: Material> MovingSphere<M> {
             time0,
             time1,
             radius,
-            mat,
+            mat: mat.with_emitter_surface(EmitterSurface::closed(4.0 * PI * radius * radius)),
         }
     }
 
     pub fn get_center(&self, t: f64) -> Point {
-        self.center0 + ((t - self.time0) / (t / self.time1)) * (self.center1 - self.center0)
+        self.center0
+            + ((t - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
     }
 }
 
@@ -162,13 +228,17 @@ impl<M: Material> Hittable for MovingSphere<M> {
 
         let p = r.at(root);
         let outward_normal = (p - MovingSphere::get_center(self, r.time())) / self.radius;
+        let (tangent, bitangent) = Sphere::<M>::get_sphere_tangents(&outward_normal);
         let mut rec = HitRecord {
             p: p,
             normal: Vector3::default(),
             t: root,
+            primitive: 0,
             u: 0.0,
             v: 0.0,
             front_face: false,
+            tangent: self.radius * tangent,
+            bitangent: self.radius * bitangent,
             mat: &self.mat,
         };
         rec.set_face_normal(r, outward_normal);
@@ -178,11 +248,14 @@ impl<M: Material> Hittable for MovingSphere<M> {
         Some(rec)
     }
 
-    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
-        let min0 = self.center0 - Vector3::new(self.radius, self.radius, self.radius);
-        let max0 = self.center0 + Vector3::new(self.radius, self.radius, self.radius);
-        let min1 = self.center1 - Vector3::new(self.radius, self.radius, self.radius);
-        let max1 = self.center1 + Vector3::new(self.radius, self.radius, self.radius);
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        // the center moves linearly, so its positions at the ends of the interval bound it
+        let center0 = self.get_center(time0);
+        let center1 = self.get_center(time1);
+        let min0 = center0 - Vector3::new(self.radius, self.radius, self.radius);
+        let max0 = center0 + Vector3::new(self.radius, self.radius, self.radius);
+        let min1 = center1 - Vector3::new(self.radius, self.radius, self.radius);
+        let max1 = center1 + Vector3::new(self.radius, self.radius, self.radius);
 
         let box0 = Aabb::new(min0, max0);
         let box1 = Aabb::new(min1, max1);
@@ -0,0 +1,167 @@
+use super::*;
+use std::fs;
+use std::path::Path;
+
+fn read_f32(bytes: &[u8], offset: usize) -> f64 {
+    f32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ]) as f64
+}
+
+fn read_vector(bytes: &[u8], offset: usize) -> Vector3 {
+    Vector3::new(
+        read_f32(bytes, offset),
+        read_f32(bytes, offset + 4),
+        read_f32(bytes, offset + 8),
+    )
+}
+
+/// STL stores one normal per facet; fall back to the winding order when it is zero.
+fn facet_normal(normal: Vector3, vertices: &[Point; 3]) -> Vector3 {
+    if normal.near_zero() {
+        (vertices[1] - vertices[0])
+            .cross(vertices[2] - vertices[0])
+            .normalize()
+    } else {
+        normal.normalize()
+    }
+}
+
+fn push_facet(
+    data: &mut MeshData,
+    normals: &mut Vec<Vector3>,
+    normal: Vector3,
+    vertices: [Point; 3],
+) {
+    let normal = facet_normal(normal, &vertices);
+    for vertex in vertices {
+        data.indices.push(data.positions.len() as u32);
+        data.positions.push(vertex);
+        normals.push(normal);
+    }
+}
+
+fn load_binary(bytes: &[u8]) -> Result<MeshData, String> {
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    if bytes.len() < 84 + 50 * count {
+        return Err("binary stl file is truncated".to_string());
+    }
+
+    let mut data = MeshData::default();
+    let mut normals = Vec::with_capacity(count * 3);
+    let mut colors = Vec::with_capacity(count * 3);
+    let mut has_color = false;
+
+    for iter in 0..count {
+        let offset = 84 + 50 * iter;
+        let vertices = [
+            read_vector(bytes, offset + 12),
+            read_vector(bytes, offset + 24),
+            read_vector(bytes, offset + 36),
+        ];
+        push_facet(
+            &mut data,
+            &mut normals,
+            read_vector(bytes, offset),
+            vertices,
+        );
+
+        // VisCAM / SolidView: bit 15 marks a valid 5-5-5 color, red in the high bits
+        let attribute = u16::from_le_bytes([bytes[offset + 48], bytes[offset + 49]]);
+        let color = if attribute & 0x8000 != 0 {
+            has_color = true;
+            Color::new(
+                ((attribute >> 10) & 0x1f) as f64 / 31.0,
+                ((attribute >> 5) & 0x1f) as f64 / 31.0,
+                (attribute & 0x1f) as f64 / 31.0,
+            )
+        } else {
+            Color::one()
+        };
+        colors.extend_from_slice(&[color; 3]);
+    }
+
+    data.normals = Some(normals);
+    data.colors = Some(colors).filter(|_| has_color);
+    Ok(data)
+}
+
+fn load_ascii(text: &str) -> Result<MeshData, String> {
+    let mut data = MeshData::default();
+    let mut normals = Vec::new();
+    let mut normal = Vector3::zero();
+    let mut vertices = Vec::with_capacity(3);
+
+    let parse = |words: &[&str]| -> Result<Vector3, String> {
+        let value = |word: &str| {
+            word.parse::<f64>()
+                .map_err(|_| format!("invalid stl value {}", word))
+        };
+        match words {
+            [x, y, z] => Ok(Vector3::new(value(x)?, value(y)?, value(z)?)),
+            _ => Err("stl vector needs three components".to_string()),
+        }
+    };
+
+    for line in text.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["facet", "normal", rest @ ..] => {
+                normal = parse(rest)?;
+                vertices.clear();
+            }
+            ["vertex", rest @ ..] => vertices.push(parse(rest)?),
+            ["endfacet"] => {
+                if vertices.len() != 3 {
+                    return Err("stl facet does not have three vertices".to_string());
+                }
+                push_facet(
+                    &mut data,
+                    &mut normals,
+                    normal,
+                    [vertices[0], vertices[1], vertices[2]],
+                );
+            }
+            _ => {} // solid, outer loop, endloop, endsolid
+        }
+    }
+
+    data.normals = Some(normals);
+    Ok(data)
+}
+
+impl MeshData {
+    /// Reads ascii and binary STL files. Facets do not share vertices, every
+    /// vertex carries its facet normal, and binary files with VisCAM / SolidView
+    /// facet colors also get vertex colors.
+    pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<MeshData, String> {
+        let bytes =
+            fs::read(path.as_ref()).map_err(|err| format!("Failed to load stl file :{}", err))?;
+
+        // binary files may also start with "solid", so trust the size they announce
+        let is_binary = bytes.len() >= 84 && {
+            let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+            bytes.len() == 84 + 50 * count || !bytes.starts_with(b"solid")
+        };
+
+        let data = if is_binary {
+            load_binary(&bytes)?
+        } else {
+            load_ascii(&String::from_utf8_lossy(&bytes))?
+        };
+        data.validate()?;
+        Ok(data)
+    }
+}
+
+impl Mesh {
+    pub fn load_stl<P: AsRef<Path>, M: Material + Clone + 'static>(
+        path: P,
+        mat: M,
+    ) -> Result<Mesh, String> {
+        Ok(Mesh::from_data(&MeshData::load_stl(path)?, mat))
+    }
+}
@@ -0,0 +1,579 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubdivisionScheme {
+    /// Loop subdivision, polygons are fanned into triangles first.
+    Loop,
+    /// Catmull-Clark subdivision, every polygon turns into quads.
+    CatmullClark,
+}
+
+/// Polygon control mesh to be refined before rendering.
+///
+/// Texture coordinates are face-varying: `uv_faces` indexes `uvs` the way
+/// `faces` indexes `positions`, so UV seams are edges that are shared in
+/// `faces` but not in `uv_faces`. They are subdivided as boundaries of the UV
+/// layout and therefore never slide across a seam.
+///
+/// Creases are stored per edge as a sharpness, the number of subdivision
+/// levels the edge keeps the sharp rules before it starts to smooth out.
+/// Use `f64::INFINITY` for a crease that stays sharp. Mesh boundaries are
+/// always sharp.
+#[derive(Debug, Clone, Default)]
+pub struct ControlCage {
+    pub positions: Vec<Point>,
+    pub faces: Vec<Vec<u32>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub uv_faces: Option<Vec<Vec<u32>>>,
+    pub creases: HashMap<(u32, u32), f64>,
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn check_faces(faces: &[Vec<u32>], count: usize) -> Result<(), String> {
+    if faces.iter().any(|face| face.len() < 3) {
+        return Err("subdivision faces need at least 3 vertices".to_string());
+    }
+    if faces.iter().flatten().any(|&i| i as usize >= count) {
+        return Err("subdivision face index out of range".to_string());
+    }
+    Ok(())
+}
+
+// position faces also need distinct corners; a repeated one leaves an edge
+// without an opposite vertex
+fn check_cage_faces(faces: &[Vec<u32>], count: usize) -> Result<(), String> {
+    check_faces(faces, count)?;
+    let repeats = |face: &Vec<u32>| {
+        face.iter()
+            .enumerate()
+            .any(|(k, i)| face[k + 1..].contains(i))
+    };
+    if faces.iter().any(repeats) {
+        return Err("subdivision face repeats a vertex".to_string());
+    }
+    Ok(())
+}
+
+/// Adjacency of a polygon mesh, with edges numbered in order of appearance.
+struct Topology {
+    edges: Vec<(u32, u32)>,
+    edge_index: HashMap<(u32, u32), usize>,
+    edge_faces: Vec<Vec<usize>>,
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(faces: &[Vec<u32>], vertex_count: usize) -> Topology {
+        let mut topology = Topology {
+            edges: vec![],
+            edge_index: HashMap::new(),
+            edge_faces: vec![],
+            vertex_edges: vec![vec![]; vertex_count],
+            vertex_faces: vec![vec![]; vertex_count],
+        };
+        for (f, face) in faces.iter().enumerate() {
+            for (k, &a) in face.iter().enumerate() {
+                let b = face[(k + 1) % face.len()];
+                let key = edge_key(a, b);
+                let e = match topology.edge_index.get(&key) {
+                    Some(&e) => e,
+                    None => {
+                        let e = topology.edges.len();
+                        topology.edges.push(key);
+                        topology.edge_index.insert(key, e);
+                        topology.edge_faces.push(vec![]);
+                        topology.vertex_edges[a as usize].push(e);
+                        topology.vertex_edges[b as usize].push(e);
+                        e
+                    }
+                };
+                topology.edge_faces[e].push(f);
+                topology.vertex_faces[a as usize].push(f);
+            }
+        }
+        topology
+    }
+
+    fn edge(&self, a: u32, b: u32) -> usize {
+        self.edge_index[&edge_key(a, b)]
+    }
+
+    fn is_boundary(&self, e: usize) -> bool {
+        self.edge_faces[e].len() != 2
+    }
+
+    fn sharpness(&self, e: usize, creases: &HashMap<(u32, u32), f64>) -> f64 {
+        if self.is_boundary(e) {
+            f64::INFINITY
+        } else {
+            creases.get(&self.edges[e]).copied().unwrap_or(0.0)
+        }
+    }
+
+    fn other(&self, e: usize, v: u32) -> u32 {
+        let (a, b) = self.edges[e];
+        if a == v {
+            b
+        } else {
+            a
+        }
+    }
+
+    /// Vertex position from the crease rules when two or more sharp edges meet
+    /// it. Boundary vertices of a single face, like the corners of a UV island,
+    /// are kept in place.
+    fn sharp_vertex(
+        &self,
+        points: &[Vector3],
+        v: usize,
+        creases: &HashMap<(u32, u32), f64>,
+    ) -> Option<Vector3> {
+        let sharp: Vec<usize> = self.vertex_edges[v]
+            .iter()
+            .copied()
+            .filter(|&e| self.sharpness(e, creases) > 0.0)
+            .collect();
+        match sharp.len() {
+            _ if self.vertex_faces[v].len() == 1 => Some(points[v]),
+            0 | 1 => None,
+            2 => {
+                let a = points[self.other(sharp[0], v as u32) as usize];
+                let b = points[self.other(sharp[1], v as u32) as usize];
+                Some(0.75 * points[v] + 0.125 * (a + b))
+            }
+            _ => Some(points[v]),
+        }
+    }
+}
+
+/// Creases of the refined mesh: both halves of a crease edge lose one level of sharpness.
+fn child_creases(
+    topology: &Topology,
+    creases: &HashMap<(u32, u32), f64>,
+    edge_point: impl Fn(usize) -> u32,
+) -> HashMap<(u32, u32), f64> {
+    let mut children = HashMap::new();
+    for (&(a, b), &sharpness) in creases {
+        let e = match topology.edge_index.get(&(a, b)) {
+            Some(&e) => e,
+            None => continue,
+        };
+        if sharpness > 1.0 && !topology.is_boundary(e) {
+            let mid = edge_point(e);
+            children.insert(edge_key(a, mid), sharpness - 1.0);
+            children.insert(edge_key(mid, b), sharpness - 1.0);
+        }
+    }
+    children
+}
+
+/// Result of one subdivision step.
+struct Refined {
+    points: Vec<Vector3>,
+    faces: Vec<Vec<u32>>,
+    creases: HashMap<(u32, u32), f64>,
+}
+
+/// One Catmull-Clark step. New points are the vertex points, then one point per
+/// edge, then one per face.
+fn catmull_clark(
+    points: &[Vector3],
+    faces: &[Vec<u32>],
+    creases: &HashMap<(u32, u32), f64>,
+) -> Refined {
+    let topology = Topology::new(faces, points.len());
+    let face_points: Vec<Vector3> = faces
+        .iter()
+        .map(|face| {
+            face.iter()
+                .fold(Vector3::zero(), |sum, &i| sum + points[i as usize])
+                / face.len() as f64
+        })
+        .collect();
+
+    let edge_points = topology.edges.iter().enumerate().map(|(e, &(a, b))| {
+        let (a, b) = (points[a as usize], points[b as usize]);
+        if topology.sharpness(e, creases) > 0.0 {
+            0.5 * (a + b)
+        } else {
+            let faces = &topology.edge_faces[e];
+            0.25 * (a + b + face_points[faces[0]] + face_points[faces[1]])
+        }
+    });
+
+    let vertex_points = (0..points.len()).map(|v| {
+        if let Some(p) = topology.sharp_vertex(points, v, creases) {
+            return p;
+        }
+        let n = topology.vertex_edges[v].len() as f64;
+        if n == 0.0 {
+            return points[v];
+        }
+        let adjacent_faces = &topology.vertex_faces[v];
+        let f = adjacent_faces
+            .iter()
+            .fold(Vector3::zero(), |sum, &f| sum + face_points[f])
+            / adjacent_faces.len() as f64;
+        let r = topology.vertex_edges[v]
+            .iter()
+            .fold(Vector3::zero(), |sum, &e| {
+                let (a, b) = topology.edges[e];
+                sum + 0.5 * (points[a as usize] + points[b as usize])
+            })
+            / n;
+        (f + 2.0 * r + (n - 3.0) * points[v]) / n
+    });
+
+    let mut new_points: Vec<Vector3> = vertex_points.collect();
+    new_points.extend(edge_points);
+    new_points.extend(face_points.iter().copied());
+
+    let edge_offset = points.len() as u32;
+    let face_offset = edge_offset + topology.edges.len() as u32;
+    let mut new_faces = Vec::with_capacity(faces.len() * 4);
+    for (f, face) in faces.iter().enumerate() {
+        let len = face.len();
+        for k in 0..len {
+            let (prev, v, next) = (face[(k + len - 1) % len], face[k], face[(k + 1) % len]);
+            new_faces.push(vec![
+                v,
+                edge_offset + topology.edge(v, next) as u32,
+                face_offset + f as u32,
+                edge_offset + topology.edge(prev, v) as u32,
+            ]);
+        }
+    }
+
+    let new_creases = child_creases(&topology, creases, |e| edge_offset + e as u32);
+    Refined {
+        points: new_points,
+        faces: new_faces,
+        creases: new_creases,
+    }
+}
+
+/// One Loop step on a triangle mesh. New points are the vertex points, then one
+/// point per edge.
+fn loop_subdivision(
+    points: &[Vector3],
+    faces: &[Vec<u32>],
+    creases: &HashMap<(u32, u32), f64>,
+) -> Refined {
+    let topology = Topology::new(faces, points.len());
+
+    let edge_points = topology.edges.iter().enumerate().map(|(e, &(a, b))| {
+        let (pa, pb) = (points[a as usize], points[b as usize]);
+        if topology.sharpness(e, creases) > 0.0 {
+            0.5 * (pa + pb)
+        } else {
+            let opposite = topology.edge_faces[e].iter().filter_map(|&f| {
+                let apex = faces[f].iter().find(|&&i| i != a && i != b)?;
+                Some(points[*apex as usize])
+            });
+            0.375 * (pa + pb) + 0.125 * opposite.fold(Vector3::zero(), |sum, p| sum + p)
+        }
+    });
+
+    let vertex_points = (0..points.len()).map(|v| {
+        if let Some(p) = topology.sharp_vertex(points, v, creases) {
+            return p;
+        }
+        let neighbours = &topology.vertex_edges[v];
+        let n = neighbours.len();
+        if n == 0 {
+            return points[v];
+        }
+        let beta = if n == 3 {
+            3.0 / 16.0
+        } else {
+            3.0 / (8.0 * n as f64)
+        };
+        let ring = neighbours.iter().fold(Vector3::zero(), |sum, &e| {
+            sum + points[topology.other(e, v as u32) as usize]
+        });
+        (1.0 - n as f64 * beta) * points[v] + beta * ring
+    });
+
+    let mut new_points: Vec<Vector3> = vertex_points.collect();
+    new_points.extend(edge_points);
+
+    let edge_offset = points.len() as u32;
+    let mut new_faces = Vec::with_capacity(faces.len() * 4);
+    for face in faces {
+        let [a, b, c] = [face[0], face[1], face[2]];
+        let ab = edge_offset + topology.edge(a, b) as u32;
+        let bc = edge_offset + topology.edge(b, c) as u32;
+        let ca = edge_offset + topology.edge(c, a) as u32;
+        new_faces.push(vec![a, ab, ca]);
+        new_faces.push(vec![b, bc, ab]);
+        new_faces.push(vec![c, ca, bc]);
+        new_faces.push(vec![ab, bc, ca]);
+    }
+
+    let new_creases = child_creases(&topology, creases, |e| edge_offset + e as u32);
+    Refined {
+        points: new_points,
+        faces: new_faces,
+        creases: new_creases,
+    }
+}
+
+type Step = fn(&[Vector3], &[Vec<u32>], &HashMap<(u32, u32), f64>) -> Refined;
+
+fn fan(faces: &[Vec<u32>]) -> Vec<Vec<u32>> {
+    faces
+        .iter()
+        .flat_map(|face| (1..face.len() - 1).map(move |k| vec![face[0], face[k], face[k + 1]]))
+        .collect()
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+impl ControlCage {
+    pub fn new(positions: Vec<Point>, faces: Vec<Vec<u32>>) -> Result<ControlCage, String> {
+        check_cage_faces(&faces, positions.len())?;
+        Ok(ControlCage {
+            positions,
+            faces,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_uvs(
+        mut self,
+        uvs: Vec<(f64, f64)>,
+        uv_faces: Vec<Vec<u32>>,
+    ) -> Result<ControlCage, String> {
+        check_faces(&uv_faces, uvs.len())?;
+        let matching = uv_faces.len() == self.faces.len()
+            && uv_faces
+                .iter()
+                .zip(&self.faces)
+                .all(|(uv_face, face)| uv_face.len() == face.len());
+        if !matching {
+            return Err("uv faces do not match the position faces".to_string());
+        }
+        self.uvs = Some(uvs);
+        self.uv_faces = Some(uv_faces);
+        Ok(self)
+    }
+
+    /// Marks the edge between vertices `a` and `b` as a crease.
+    pub fn with_crease(mut self, a: u32, b: u32, sharpness: f64) -> ControlCage {
+        self.creases.insert(edge_key(a, b), sharpness.max(0.0));
+        self
+    }
+
+    /// Loads all objects of an OBJ file, keeping quads and n-gons intact.
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<ControlCage, String> {
+        let options = tobj::LoadOptions {
+            single_index: false,
+            triangulate: false,
+            ignore_points: true,
+            ignore_lines: true,
+        };
+        let models = match tobj::load_obj(path.as_ref(), &options) {
+            Ok((models, _)) => models,
+            Err(err) => return Err(format!("Failed to load obj file :{}", err)),
+        };
+
+        let mut cage = ControlCage::default();
+        let mut uvs = vec![];
+        let mut uv_faces = vec![];
+        let mut has_uvs = true;
+        for model in &models {
+            let mesh = &model.mesh;
+            let offset = cage.positions.len() as u32;
+            let uv_offset = uvs.len() as u32;
+            cage.positions.extend(
+                mesh.positions
+                    .chunks(3)
+                    .map(|p| Point::new(p[0] as f64, p[1] as f64, p[2] as f64)),
+            );
+            uvs.extend(mesh.texcoords.chunks(2).map(|t| (t[0] as f64, t[1] as f64)));
+            has_uvs &= !mesh.texcoord_indices.is_empty();
+
+            let arities: Vec<usize> = if mesh.face_arities.is_empty() {
+                vec![3; mesh.indices.len() / 3]
+            } else {
+                mesh.face_arities.iter().map(|&n| n as usize).collect()
+            };
+            let mut start = 0;
+            for arity in arities {
+                let range = start..start + arity;
+                cage.faces.push(
+                    mesh.indices[range.clone()]
+                        .iter()
+                        .map(|&i| i + offset)
+                        .collect(),
+                );
+                if has_uvs {
+                    uv_faces.push(
+                        mesh.texcoord_indices[range]
+                            .iter()
+                            .map(|&i| i + uv_offset)
+                            .collect(),
+                    );
+                }
+                start += arity;
+            }
+        }
+
+        check_cage_faces(&cage.faces, cage.positions.len())?;
+        if has_uvs && !models.is_empty() {
+            cage.with_uvs(uvs, uv_faces)
+        } else {
+            Ok(cage)
+        }
+    }
+
+    /// Refines the cage `levels` times.
+    pub fn subdivide(&self, scheme: SubdivisionScheme, levels: u32) -> ControlCage {
+        let step: Step = match scheme {
+            SubdivisionScheme::Loop => loop_subdivision,
+            SubdivisionScheme::CatmullClark => catmull_clark,
+        };
+        let (mut faces, mut uv_faces) = match scheme {
+            SubdivisionScheme::Loop => (fan(&self.faces), self.uv_faces.as_deref().map(fan)),
+            SubdivisionScheme::CatmullClark => (self.faces.clone(), self.uv_faces.clone()),
+        };
+        let mut positions = self.positions.clone();
+        let mut creases = self.creases.clone();
+        let mut uvs: Option<Vec<Vector3>> = self
+            .uvs
+            .as_ref()
+            .map(|uvs| uvs.iter().map(|&(u, v)| Vector3::new(u, v, 0.0)).collect());
+
+        for _ in 0..levels {
+            let refined = step(&positions, &faces, &creases);
+            // both steps emit child faces in the same order, so the layouts stay matched
+            if let (Some(points), Some(topology)) = (&uvs, &uv_faces) {
+                let refined_uvs = step(points, topology, &HashMap::new());
+                uvs = Some(refined_uvs.points);
+                uv_faces = Some(refined_uvs.faces);
+            }
+            positions = refined.points;
+            faces = refined.faces;
+            creases = refined.creases;
+        }
+
+        ControlCage {
+            positions,
+            faces,
+            uvs: uvs.map(|uvs| uvs.iter().map(|uv| (uv.x(), uv.y())).collect()),
+            uv_faces,
+            creases,
+        }
+    }
+
+    /// Triangulates the cage with smooth normals, split along creases and
+    /// with vertices duplicated along UV seams.
+    pub fn to_mesh_data(&self) -> MeshData {
+        let topology = Topology::new(&self.faces, self.positions.len());
+
+        // every face corner starts in its own normal group, merged across smooth edges
+        let mut corner_start = Vec::with_capacity(self.faces.len());
+        let mut corner_count = 0;
+        for face in &self.faces {
+            corner_start.push(corner_count);
+            corner_count += face.len();
+        }
+        let corner = |f: usize, v: u32| {
+            corner_start[f] + self.faces[f].iter().position(|&i| i == v).unwrap()
+        };
+        let mut parent: Vec<usize> = (0..corner_count).collect();
+        for (e, &(a, b)) in topology.edges.iter().enumerate() {
+            if topology.sharpness(e, &self.creases) > 0.0 {
+                continue;
+            }
+            let (f1, f2) = (topology.edge_faces[e][0], topology.edge_faces[e][1]);
+            for v in [a, b] {
+                let (r1, r2) = (
+                    find(&mut parent, corner(f1, v)),
+                    find(&mut parent, corner(f2, v)),
+                );
+                parent[r1] = r2;
+            }
+        }
+
+        // area weighted face normals (Newell's method) summed per group
+        let mut group_normals = vec![Vector3::zero(); corner_count];
+        for (f, face) in self.faces.iter().enumerate() {
+            let mut normal = Vector3::zero();
+            for (k, &a) in face.iter().enumerate() {
+                let b = face[(k + 1) % face.len()];
+                normal += self.positions[a as usize].cross(self.positions[b as usize]);
+            }
+            for k in 0..face.len() {
+                let root = find(&mut parent, corner_start[f] + k);
+                group_normals[root] += normal;
+            }
+        }
+
+        let mut data = MeshData {
+            uvs: self.uvs.as_ref().map(|_| vec![]),
+            normals: Some(vec![]),
+            ..Default::default()
+        };
+        let mut vertices: HashMap<(u32, u32, usize), u32> = HashMap::new();
+        let mut face_vertices = vec![];
+        for (f, face) in self.faces.iter().enumerate() {
+            face_vertices.clear();
+            for (k, &v) in face.iter().enumerate() {
+                let group = find(&mut parent, corner_start[f] + k);
+                let uv_index = self.uv_faces.as_ref().map_or(0, |uv_faces| uv_faces[f][k]);
+                let index = *vertices.entry((v, uv_index, group)).or_insert_with(|| {
+                    data.positions.push(self.positions[v as usize]);
+                    let normal = group_normals[group];
+                    let normal = if normal.near_zero() {
+                        Vector3::new(0.0, 1.0, 0.0)
+                    } else {
+                        normal.normalize()
+                    };
+                    data.normals.as_mut().unwrap().push(normal);
+                    if let (Some(uvs), Some(out)) = (&self.uvs, data.uvs.as_mut()) {
+                        out.push(uvs[uv_index as usize]);
+                    }
+                    (data.positions.len() - 1) as u32
+                });
+                face_vertices.push(index);
+            }
+            for k in 1..face_vertices.len() - 1 {
+                data.indices
+                    .extend([face_vertices[0], face_vertices[k], face_vertices[k + 1]]);
+            }
+        }
+        data
+    }
+}
+
+impl Mesh {
+    /// Loads an OBJ control cage and subdivides it `levels` times before building the mesh.
+    pub fn load_obj_subdivided<P: AsRef<Path>, M: Material + Clone + 'static>(
+        path: P,
+        scheme: SubdivisionScheme,
+        levels: u32,
+        mat: M,
+    ) -> Result<Mesh, String> {
+        let cage = ControlCage::load_obj(path)?;
+        let data = cage.subdivide(scheme, levels).to_mesh_data();
+        Ok(Mesh::from_data(&data, mat))
+    }
+}
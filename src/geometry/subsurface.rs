@@ -0,0 +1,202 @@
+use rand::Rng;
+
+use super::*;
+
+// scattering events inside before a path is given up as absorbed
+const MAX_WALK_STEPS: usize = 1024;
+
+/// Translucent object that scatters light inside its `boundary`, like skin,
+/// wax or marble. Light enters through a dielectric surface, takes a random
+/// walk through the volume with free flights sampled as in `ConstantMedium`,
+/// and leaves wherever the walk reaches the surface again.
+///
+/// `color` is what the surface looks like once light has bounced around in
+/// it; the single scattering albedo producing it is found as in Chiang et
+/// al., "Practical and Controllable Subsurface Scattering for Production
+/// Path Tracing". `mean_free_path` is the average distance light travels
+/// between scattering events, per channel; in skin red goes furthest.
+///
+/// The boundary must be closed with its normals pointing out; its own
+/// material is not used. The whole walk happens in a single `scatter`, so
+/// it doesn't use up the bounces of the path.
+#[derive(Debug, Clone)]
+pub struct Subsurface<H: Hittable> {
+    boundary: H,
+    interface: RoughDielectric,
+    sigma_t: Color,
+    albedo: Color,
+    anisotropy: f64,
+}
+
+impl<H: Hittable> Subsurface<H> {
+    pub fn new(boundary: H, color: Color, mean_free_path: Color) -> Subsurface<H> {
+        let mut sigma_t = Color::zero();
+        let mut albedo = Color::zero();
+        for i in 0..3 {
+            sigma_t[i] = 1.0 / mean_free_path[i].max(1e-6);
+            let a = color[i].clamp(0.0, 0.999);
+            albedo[i] = 1.0 - (a * (-5.09406 + a * (2.61188 - a * 4.31805))).exp();
+        }
+        Subsurface {
+            boundary,
+            interface: RoughDielectric::new(1.4, 0.0),
+            sigma_t,
+            albedo,
+            anisotropy: 0.0,
+        }
+    }
+
+    /// Surface light enters and leaves through, smooth with an index of
+    /// refraction of 1.4 by default. It should not absorb.
+    pub fn with_interface(mut self, interface: RoughDielectric) -> Subsurface<H> {
+        self.interface = interface;
+        self
+    }
+
+    /// Henyey-Greenstein asymmetry of the scattering inside, in (-1, 1);
+    /// positive values scatter forward, as in skin.
+    pub fn with_anisotropy(mut self, g: f64) -> Subsurface<H> {
+        self.anisotropy = g.clamp(-0.99, 0.99);
+        self
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        Color::new(
+            (-self.sigma_t.x() * distance).exp(),
+            (-self.sigma_t.y() * distance).exp(),
+            (-self.sigma_t.z() * distance).exp(),
+        )
+    }
+
+    // new direction for light travelling along the unit vector `dir`
+    fn sample_phase(&self, dir: Vector3, u1: f64, u2: f64) -> Vector3 {
+        let g = self.anisotropy;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u1
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u1);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        ONB::build_from_w(&dir).local(&Vector3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let mut rng = rand::thread_rng();
+        let mut weight = Color::one();
+
+        // a ray from inside, e.g. from a camera within the object, has been
+        // walking through the volume all along
+        let (mut ray, mut exit) = if rec.front_face {
+            let (attenuation, ray) = self.interface.scatter(r_in, rec)?;
+            let ray = ray.inherit_wavelengths(r_in);
+            if ray.dir().dot(rec.normal) > 0.0 {
+                return Some((attenuation, ray));
+            }
+            weight *= attenuation;
+            let exit = self.boundary.hit(&ray, 0.0001, f64::INFINITY);
+            (ray, exit)
+        } else {
+            (
+                Ray::new(r_in.origin(), r_in.dir(), r_in.time())
+                    .with_type(r_in.ray_type())
+                    .with_wavelengths(r_in.wavelengths()),
+                Some(HitRecord { mat: self, ..*rec }),
+            )
+        };
+
+        for _ in 0..MAX_WALK_STEPS {
+            let exit_rec = exit?;
+            let length = ray.dir().length();
+            let distance = exit_rec.t * length;
+
+            // the flight is sampled for one channel, picked by how much light
+            // it still carries, and weighted by the pdf of all three combined,
+            // so each channel gets its own falloff
+            let total = weight.x() + weight.y() + weight.z();
+            if total <= 0.0 {
+                return None;
+            }
+            let pick = weight / total;
+            let u = rng.gen::<f64>();
+            let channel = if u < pick.x() {
+                0
+            } else if u < pick.x() + pick.y() {
+                1
+            } else {
+                2
+            };
+            let flight = -(1.0 - rng.gen::<f64>()).ln() / self.sigma_t[channel];
+            if flight < distance {
+                let tr = self.transmittance(flight);
+                let density = self.sigma_t * tr;
+                let pdf = pick.dot(density);
+                weight = weight * self.albedo * density / pdf;
+                // Russian roulette, so walks that have lost their light end early
+                let survival = weight.x().max(weight.y()).max(weight.z()).min(1.0);
+                if survival < 0.1 {
+                    if rng.gen::<f64>() >= survival {
+                        return None;
+                    }
+                    weight /= survival;
+                }
+
+                let direction = self.sample_phase(ray.dir() / length, rng.gen(), rng.gen());
+                ray = Ray::new(ray.at(flight / length), direction, ray.time())
+                    .with_type(ray.ray_type())
+                    .inherit_wavelengths(&ray);
+                exit = self.boundary.hit(&ray, 1e-9, f64::INFINITY);
+            } else {
+                let tr = self.transmittance(distance);
+                let pdf = pick.dot(tr);
+                weight = weight * tr / pdf;
+
+                // the surface seen from inside, leaving or reflecting back in
+                let (attenuation, out) = self.interface.scatter(&ray, &exit_rec)?;
+                let out = out.inherit_wavelengths(&ray);
+                weight *= attenuation;
+                if out.dir().dot(exit_rec.normal) < 0.0 {
+                    return Some((weight, out));
+                }
+                ray = out;
+                exit = self.boundary.hit(&ray, 0.0001, f64::INFINITY);
+            }
+        }
+        None
+    }
+}
+
+impl<H: Hittable> Hittable for Subsurface<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut rec = self.boundary.hit(r, t_min, t_max)?;
+        rec.mat = self;
+        Some(rec)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.boundary.bounding_box(time0, time1)
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.boundary.visibility()
+    }
+}
+
+impl<H: Hittable> Material for Subsurface<H> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.sample(r_in, rec)
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        let (attenuation, specular_ray) = self.sample(r_in, rec)?;
+        Some(ScatterRecord::Specular {
+            specular_ray,
+            attenuation,
+        })
+    }
+}
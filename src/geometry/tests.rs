@@ -0,0 +1,983 @@
+use rand::Rng;
+
+use super::*;
+use crate::texture::{SolidTexture, Texture};
+use std::sync::Arc;
+
+const SAMPLES: usize = 200_000;
+
+fn light() -> DiffuseLight<SolidTexture> {
+    DiffuseLight::new(SolidTexture::new(Color::one()))
+}
+
+// jittered strata over the sphere, uniform in z and the azimuth
+const STRATA: usize = 400;
+
+fn stratified_direction(i: usize) -> Vector3 {
+    let mut rng = rand::thread_rng();
+    let rows = SAMPLES / STRATA;
+    let z = 1.0 - 2.0 * ((i / STRATA) as f64 + rng.gen::<f64>()) / rows as f64;
+    let phi = 2.0 * PI * ((i % STRATA) as f64 + rng.gen::<f64>()) / STRATA as f64;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Integral of `pdf_value` over the sphere of directions seen from `o`, and
+/// the solid angle the light covers.
+fn integrate(light: &dyn Hittable, o: Point, time: f64) -> (f64, f64) {
+    let (mut integral, mut covered) = (0.0, 0.0);
+    for i in 0..SAMPLES {
+        let pdf = light.pdf_value(o, stratified_direction(i), time);
+        integral += pdf;
+        if pdf > 0.0 {
+            covered += 1.0;
+        }
+    }
+    let sphere = 4.0 * PI / SAMPLES as f64;
+    (integral * sphere, covered * sphere)
+}
+
+/// Solid angle of the light estimated from its own samples, E[1 / pdf].
+fn sampled_solid_angle(light: &dyn Hittable, o: Point, time: f64) -> f64 {
+    let mut sum = 0.0;
+    for _ in 0..SAMPLES {
+        let direction = light.random(o, time);
+        let pdf = light.pdf_value(o, direction, time);
+        assert!(
+            pdf > 0.0,
+            "sampled direction {:?} misses the light",
+            direction
+        );
+        sum += 1.0 / pdf;
+    }
+    sum / SAMPLES as f64
+}
+
+fn check(name: &str, light: &dyn Hittable, o: Point, time: f64) {
+    check_within(name, light, o, time, 0.01);
+}
+
+/// `check` for a pdf too spiky for the integral to get within 1%.
+fn check_within(name: &str, light: &dyn Hittable, o: Point, time: f64, tolerance: f64) {
+    let (integral, covered) = integrate(light, o, time);
+    assert!(
+        (integral - 1.0).abs() < tolerance,
+        "{}: pdf integrates to {}",
+        name,
+        integral
+    );
+    let sampled = sampled_solid_angle(light, o, time);
+    assert!(
+        (sampled - covered).abs() < 0.03 * covered,
+        "{}: samples cover {} sr, pdf covers {} sr",
+        name,
+        sampled,
+        covered
+    );
+}
+
+fn quad() -> Quad<DiffuseLight<SolidTexture>> {
+    Quad::new(
+        Point::new(-1.0, 1.0, -1.0),
+        Vector3::new(2.0, 0.0, 0.0),
+        Vector3::new(0.6, 0.4, 2.0),
+        light(),
+    )
+}
+
+fn rect() -> AARect<DiffuseLight<SolidTexture>> {
+    AARect::new(Plane::XZ, -1.0, 1.0, -1.5, 1.5, 1.0, light())
+}
+
+fn sphere() -> Sphere<DiffuseLight<SolidTexture>> {
+    Sphere::new(Point::new(0.0, 1.5, 0.0), 1.0, light())
+}
+
+#[test]
+fn translated_lights_pdf_integrates_to_one() {
+    let o = Point::new(0.2, -0.3, 0.1);
+    let offset = Vector3::new(0.5, 0.3, -0.4);
+    check("quad", &Translate::new(quad(), offset), o, 0.0);
+    check("rect", &Translate::new(rect(), offset), o, 0.0);
+    check("sphere", &Translate::new(sphere(), offset), o, 0.0);
+}
+
+#[test]
+fn rotated_lights_pdf_integrates_to_one() {
+    let o = Point::new(0.2, -0.3, 0.1);
+    check("quad", &Rotate::new(RotateAxis::X, quad(), 30.0), o, 0.0);
+    check("rect", &Rotate::new(RotateAxis::Z, rect(), -40.0), o, 0.0);
+    check(
+        "sphere",
+        &Rotate::new(RotateAxis::Y, sphere(), 75.0),
+        o,
+        0.0,
+    );
+}
+
+#[test]
+fn flipped_lights_pdf_integrates_to_one() {
+    let o = Point::new(0.2, -0.3, 0.1);
+    check("quad", &FlipFace::new(quad()), o, 0.0);
+    check("rect", &FlipFace::new(rect()), o, 0.0);
+    check("sphere", &FlipFace::new(sphere()), o, 0.0);
+}
+
+#[test]
+fn animated_lights_pdf_integrates_to_one_between_keyframes() {
+    let o = Point::new(0.2, -0.3, 0.1);
+    let keyframes = vec![
+        Keyframe::new(0.0),
+        Keyframe::new(1.0)
+            .with_translation(Vector3::new(1.0, 0.5, 0.0))
+            .with_rotation(Quaternion::from_axis_angle(
+                Vector3::new(1.0, 1.0, 0.0),
+                60.0,
+            ))
+            .with_scale(Vector3::new(1.5, 0.5, 1.0)),
+    ];
+    let quad = AnimatedTransform::new(quad(), keyframes.clone()).unwrap();
+    let rect = AnimatedTransform::new(rect(), keyframes.clone()).unwrap();
+    let sphere = AnimatedTransform::new(sphere(), keyframes).unwrap();
+    for time in [0.0, 0.7] {
+        check("quad", &quad, o, time);
+        check("rect", &rect, o, time);
+        check("sphere", &sphere, o, time);
+    }
+}
+
+#[test]
+fn animated_light_samples_follow_the_pose() {
+    let o = Point::zero();
+    let keyframes = vec![
+        Keyframe::new(0.0),
+        Keyframe::new(1.0).with_translation(Vector3::new(0.0, -3.0, 0.0)),
+    ];
+    // at the end of the shutter the light has moved from above to below
+    let light = AnimatedTransform::new(rect(), keyframes).unwrap();
+    for _ in 0..1000 {
+        assert!(light.random(o, 0.0).y() > 0.0);
+        assert!(light.random(o, 1.0).y() < 0.0);
+    }
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    assert!(light.pdf_value(o, up, 0.0) > 0.0);
+    assert_eq!(light.pdf_value(o, up, 1.0), 0.0);
+}
+
+#[test]
+fn nested_cut_outs_keep_the_opacity() {
+    let mut rng = rand::thread_rng();
+    let leaf = AlphaMask::new(
+        Lambertian::new(SolidTexture::new(Color::one())),
+        SolidTexture::new(Color::one() * 0.5),
+    );
+    let quad = Quad::new(
+        Point::new(-1.0, -1.0, 0.0),
+        Vector3::new(2.0, 0.0, 0.0),
+        Vector3::new(0.0, 2.0, 0.0),
+        leaf,
+    );
+    // each level tests the hit again, with the ray it was handed
+    let inner = HittableList::new(vec![Box::new(quad)]);
+    let moved = Translate::new(inner, Vector3::new(0.3, -0.2, 1.0));
+    let world = HittableList::new(vec![Box::new(moved)]);
+
+    let rays = 20_000;
+    let mut kept = 0;
+    for _ in 0..rays {
+        let target = Point::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5), 1.0);
+        let origin = Point::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5), -2.0);
+        let r = Ray::new(origin, target - origin, 0.0);
+        if world.hit(&r, 0.001, f64::INFINITY).is_some() {
+            kept += 1;
+        }
+    }
+    let kept = kept as f64 / rays as f64;
+    assert!((kept - 0.5).abs() < 0.02, "kept {} of the hits", kept);
+}
+
+/// Bright where u is below 0.3 and dim elsewhere.
+#[derive(Clone, Copy)]
+struct Stripe;
+
+impl Texture for Stripe {
+    fn get_color(&self, u: f64, _v: f64, _p: &Point) -> Color {
+        Color::one() * if u < 0.3 { 10.0 } else { 0.1 }
+    }
+}
+
+fn striped_light() -> DiffuseLight<Stripe> {
+    DiffuseLight::new(Stripe)
+}
+
+fn triangle<M: Material>(mat: M) -> Triangle<M> {
+    Triangle::new(
+        [
+            Point::new(-1.0, 1.0, -1.0),
+            Point::new(1.5, 1.2, -0.5),
+            Point::new(-0.5, 1.5, 1.5),
+        ],
+        mat,
+    )
+}
+
+/// Two triangles folded into a roof, so that directions cross both.
+fn roof<M: Material>(mat: M) -> TriangleMesh<M> {
+    let positions = vec![
+        [-1.0, 1.0, -1.0],
+        [1.0, 1.0, -1.0],
+        [-1.0, 2.0, 0.0],
+        [1.0, 2.0, 0.0],
+        [-1.0, 1.0, 1.0],
+        [1.0, 1.0, 1.0],
+    ];
+    TriangleMesh::new(
+        Arc::new(positions),
+        vec![[0, 1, 3], [3, 2, 0], [2, 3, 5], [5, 4, 2]],
+        mat,
+    )
+    .unwrap()
+}
+
+#[test]
+fn shapes_without_importance_sampling_pdf_integrates_to_one() {
+    let o = Point::new(0.2, -0.3, 0.1);
+    check("triangle", &triangle(light()), o, 0.0);
+    check("mesh", &roof(light()), o, 0.0);
+}
+
+#[test]
+fn importance_sampled_lights_pdf_integrates_to_one() {
+    let o = Point::new(0.2, -0.3, 0.1);
+    let quad = Quad::new(
+        Point::new(-1.0, 1.0, -1.0),
+        Vector3::new(2.0, 0.0, 0.0),
+        Vector3::new(0.6, 0.4, 2.0),
+        striped_light(),
+    );
+    check("quad", &quad.with_importance_sampling(16), o, 0.0);
+    let rect = AARect::new(Plane::XZ, -1.0, 1.0, -1.5, 1.5, 1.0, striped_light());
+    check("rect", &rect.with_importance_sampling(16), o, 0.0);
+    let sphere = Sphere::new(Point::new(0.0, 1.5, 0.0), 1.0, striped_light());
+    // area samples on a sphere crowd towards its outline, and the
+    // integral is noisy there
+    check_within("sphere", &sphere.with_importance_sampling(16), o, 0.0, 0.05);
+    check(
+        "triangle",
+        &triangle(striped_light()).with_importance_sampling(16),
+        o,
+        0.0,
+    );
+    check(
+        "mesh",
+        &roof(striped_light()).with_importance_sampling(4),
+        o,
+        0.0,
+    );
+}
+
+#[test]
+fn importance_sampling_favours_the_bright_stripe() {
+    let o = Point::zero();
+    let center = Point::new(0.0, 3.0, 0.0);
+    let sphere = Sphere::new(center, 1.0, striped_light()).with_importance_sampling(16);
+    let bright = (0..10_000)
+        .filter(|_| {
+            let p = o + sphere.random(o, 0.0);
+            let (u, _) = Sphere::<DiffuseLight<Stripe>>::get_sphere_uv(&(p - center));
+            u < 0.3
+        })
+        .count();
+    // the stripe covers 30% of the sphere but emits almost all of its power
+    assert!(bright > 9_000, "{} of 10000 samples on the stripe", bright);
+}
+
+/// Radiance `shape` emits straight down the z axis towards the origin.
+fn radiance_towards_origin(shape: &dyn Hittable) -> f64 {
+    let r = Ray::new(Point::zero(), Vector3::new(0.01, 0.02, 1.0), 0.0);
+    let rec = shape.hit(&r, 0.001, f64::INFINITY).unwrap();
+    rec.mat.emitted(&rec).x()
+}
+
+#[test]
+fn a_given_power_spreads_over_the_whole_shape() {
+    let watts = || {
+        DiffuseLight::new(SolidTexture::new(Color::one())).with_intensity(Intensity::Watts(100.0))
+    };
+    let one_sided = || watts().with_two_sided(false);
+    // 6 square meters, as a single quad and in parts; closed shapes only
+    // let out the power of their outside, like a quad lit on one side
+    let quad = Quad::new(
+        Point::new(-1.0, -1.5, 2.0),
+        Vector3::new(0.0, 3.0, 0.0),
+        Vector3::new(2.0, 0.0, 0.0),
+        one_sided(),
+    );
+    let reference = radiance_towards_origin(&quad);
+    let cube = Cube::new(
+        Point::new(-0.5, -0.5, 2.0),
+        Point::new(0.5, 0.5, 3.0),
+        watts(),
+    );
+    let mesh = Mesh::new(
+        vec![
+            Point::new(-1.0, -1.5, 2.0),
+            Point::new(1.0, -1.5, 2.0),
+            Point::new(1.0, 1.5, 2.0),
+            Point::new(-1.0, 1.5, 2.0),
+        ],
+        vec![0, 2, 1, 2, 0, 3],
+        one_sided(),
+    );
+    let sphere = Sphere::new(
+        Point::new(0.0, 0.0, 3.0),
+        (6.0 / (4.0 * PI)).sqrt(),
+        watts(),
+    );
+    let disk = Disk::new(
+        Point::new(0.0, 0.0, 2.0),
+        Vector3::new(0.0, 0.0, -1.0),
+        (6.0 / PI).sqrt(),
+        one_sided(),
+    );
+    let torus = Torus::new(
+        Point::new(0.0, 0.0, 3.0),
+        1.0,
+        6.0 / (4.0 * PI * PI),
+        watts(),
+    );
+    // 2 pi r h + 2 pi r^2 with r = 0.5
+    let height = 6.0 / PI - 0.5;
+    let cylinder = Cylinder::new(Point::new(0.0, -height / 2.0, 3.0), 0.5, height, watts());
+    for (name, shape) in [
+        ("cube", &cube as &dyn Hittable),
+        ("mesh", &mesh),
+        ("sphere", &sphere),
+        ("disk", &disk),
+        ("torus", &torus),
+        ("cylinder", &cylinder.capped(true)),
+    ] {
+        let radiance = radiance_towards_origin(shape);
+        assert!(
+            (radiance - reference).abs() < 1e-9 * reference,
+            "{}: {} against {}",
+            name,
+            radiance,
+            reference
+        );
+    }
+
+    // four times the area at the same power is a quarter as bright
+    let small = Quad::new(
+        Point::new(-0.5, -0.75, 2.0),
+        Vector3::new(0.0, 1.5, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+        one_sided(),
+    );
+    let ratio = radiance_towards_origin(&small) / reference;
+    assert!(
+        (ratio - 4.0).abs() < 1e-9,
+        "quarter the area is {} times as bright",
+        ratio
+    );
+}
+
+#[test]
+fn meshes_close_when_every_edge_has_two_triangles() {
+    let [a, b, c, d] = [
+        Point::zero(),
+        Point::new(1.0, 0.0, 0.0),
+        Point::new(0.0, 1.0, 0.0),
+        Point::new(0.0, 0.0, 1.0),
+    ];
+    let tetrahedron = [[a, c, b], [a, b, d], [b, c, d], [c, a, d]];
+    let surface = triangles_surface(tetrahedron.into_iter());
+    assert!(surface.closed);
+    assert!((surface.area - (1.5 + 0.5 * 3f64.sqrt())).abs() < 1e-12);
+    assert!(!triangles_surface(tetrahedron[..3].iter().copied()).closed);
+}
+
+#[test]
+#[should_panic(expected = "no known area")]
+fn shapes_of_unknown_area_refuse_a_power() {
+    let watts =
+        DiffuseLight::new(SolidTexture::new(Color::one())).with_intensity(Intensity::Watts(100.0));
+    SdfShape::new(SdfSphere::new(Point::zero(), 1.0), watts);
+}
+
+#[test]
+fn degenerate_triangles_are_never_hit() {
+    let positions = vec![
+        [-1.0, -1.0, 1.0],
+        [1.0, -1.0, 1.0],
+        [0.0, 1.0, 1.0],
+        [-1.0, -1.0, 3.0],
+        [1.0, -1.0, 3.0],
+        [0.0, 1.0, 3.0],
+    ];
+    // the first triangle repeats a vertex, the far one comes after it
+    let mesh = TriangleMesh::new(
+        Arc::new(positions),
+        vec![[0, 0, 2], [3, 4, 5], [0, 1, 2]],
+        light(),
+    )
+    .unwrap();
+    let r = Ray::new(Point::zero(), Vector3::new(0.0, 0.0, 1.0), 0.0);
+    let rec = mesh.hit(&r, 0.001, f64::INFINITY).unwrap();
+    assert_eq!(rec.t, 1.0);
+
+    let collapsed = [Point::zero(), Point::zero(), Point::new(0.0, 0.0, 2.0)];
+    assert!(intersect_triangle(&r, &collapsed, 0.001, f64::INFINITY).is_none());
+}
+
+#[test]
+fn colored_particles_keep_their_sphere_uv() {
+    let centers = vec![Point::new(0.0, 0.0, 5.0), Point::new(3.0, 0.0, 5.0)];
+    let colors = vec![Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0)];
+    let cloud =
+        PointCloud::with_colors(centers, vec![1.0, 1.0], colors, DiffuseLight::new).unwrap();
+
+    let r = Ray::new(Point::new(3.0, 0.5, 0.0), Vector3::new(0.0, 0.0, 1.0), 0.0);
+    let rec = cloud.hit(&r, 0.001, f64::INFINITY).unwrap();
+    let outward = rec.p - Point::new(3.0, 0.0, 5.0);
+    let (u, v) = Sphere::<DiffuseLight<SolidTexture>>::get_sphere_uv(&outward);
+    assert!((rec.u - u).abs() < 1e-6 && (rec.v - v).abs() < 1e-6);
+    let color = rec.mat.emitted(&rec);
+    assert_eq!((color.x(), color.y(), color.z()), (0.0, 1.0, 0.0));
+}
+
+#[test]
+fn shadow_rays_pass_a_housing_hidden_from_them() {
+    let housing = AARect::new(Plane::XZ, -2.0, 2.0, -2.0, 2.0, 0.3, light());
+    let mut world = HittableList::default();
+    world.add(Visible::new(
+        housing,
+        Visibility::ALL.without(Visibility::SHADOW | Visibility::LIGHT_SAMPLING),
+    ));
+    world.add(sphere());
+    let world: Box<dyn Hittable> = Box::new(world);
+
+    // only the sphere is sampled, and the sampled directions are shadow rays
+    let towards_light = PDF::hittable_pdf(Point::zero(), 0.0, &world);
+    let (direction, ray_type) = towards_light.sample();
+    assert_eq!(ray_type, RayType::Shadow);
+    assert!(sphere().pdf_value(Point::zero(), direction, 0.0) > 0.0);
+
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    let shadow = Ray::new(Point::zero(), up, 0.0).with_type(RayType::Shadow);
+    let rec = world.hit(&shadow, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 0.5).abs() < 1e-9);
+    let reflection = Ray::new(Point::zero(), up, 0.0);
+    let rec = world.hit(&reflection, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 0.3).abs() < 1e-9);
+}
+
+// one triangle under a scaled and moved node, textured through its second uv
+// set, the buffer and both images embedded as data uris
+const GLTF_FIXTURE: &str = r#"{
+  "asset": { "version": "2.0" },
+  "scene": 0,
+  "scenes": [{ "nodes": [0] }],
+  "nodes": [{ "mesh": 0, "translation": [0, 0, -5], "scale": [2, 2, 2] }],
+  "meshes": [{
+    "primitives": [{
+      "attributes": { "POSITION": 0, "NORMAL": 1, "TANGENT": 2, "TEXCOORD_0": 3, "TEXCOORD_1": 4 },
+      "material": 0
+    }]
+  }],
+  "materials": [{
+    "pbrMetallicRoughness": {
+      "baseColorFactor": [0.8, 0.6, 0.4, 1.0],
+      "baseColorTexture": { "index": 0, "texCoord": 1 },
+      "metallicFactor": 0.3,
+      "roughnessFactor": 0.6
+    },
+    "normalTexture": { "index": 1, "texCoord": 1, "scale": 0.5 }
+  }],
+  "textures": [{ "source": 0 }, { "source": 1 }],
+  "images": [
+    { "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAIAAAB7QOjdAAAAD0lEQVR4nGP4z8DA8J8BAAf/Af8Bf4mnAAAAAElFTkSuQmCC" },
+    { "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAIAAACQd1PeAAAADElEQVR4nGM40/AMAAROAjP3YBB7AAAAAElFTkSuQmCC" }
+  ],
+  "buffers": [{
+    "byteLength": 168,
+    "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAAAAAACAPwAAgL8AAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AACAPwAAAAAAAAAAAACAvwAAgD8AAAAAAAAAAAAAgL8AAIA/AAAAAAAAAAAAAIC/zczMPQAAAD/NzMw9AAAAP83MzD0AAAA/ZmZmPwAAAD9mZmY/AAAAP2ZmZj8AAAA/"
+  }],
+  "bufferViews": [
+    { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+    { "buffer": 0, "byteOffset": 36, "byteLength": 36 },
+    { "buffer": 0, "byteOffset": 72, "byteLength": 48 },
+    { "buffer": 0, "byteOffset": 120, "byteLength": 24 },
+    { "buffer": 0, "byteOffset": 144, "byteLength": 24 }
+  ],
+  "accessors": [
+    { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [-1, -1, 0], "max": [1, 1, 0] },
+    { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" },
+    { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4" },
+    { "bufferView": 3, "componentType": 5126, "count": 3, "type": "VEC2" },
+    { "bufferView": 4, "componentType": 5126, "count": 3, "type": "VEC2" }
+  ]
+}"#;
+
+#[test]
+fn gltf_meshes_keep_their_transform_and_material() {
+    let path = std::env::temp_dir().join("gltf_meshes_keep_their_transform_and_material.gltf");
+    std::fs::write(&path, GLTF_FIXTURE).unwrap();
+    let scene = GltfScene::load(&path, 1.0, 0.1).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let r = Ray::new(Point::zero(), Vector3::new(0.0, 0.0, -1.0), 0.0);
+    let rec = scene.world.hit(&r, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 5.0).abs() < 1e-6);
+    // only the scaled triangle reaches out to here
+    let corner = Ray::new(Point::zero(), Vector3::new(1.5, -1.5, -5.0), 0.0);
+    assert!(scene.world.hit(&corner, 0.001, f64::INFINITY).is_some());
+    // the tangent's w of -1 turns the bitangent around
+    assert!((rec.bitangent.normalize() - Vector3::new(0.0, -1.0, 0.0)).length() < 1e-6);
+
+    // the right, green texel of the base color, read through the second uv set
+    let expected = NormalMap::new(
+        PBR::new(
+            SolidTexture::new(Color::new(0.0, 0.6, 0.0)),
+            0.3,
+            0.0,
+            0.5,
+            0.6,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+        ),
+        SolidTexture::new(Color::new(204.0, 128.0, 230.0) / 255.0),
+    )
+    .with_strength(0.5);
+    let out = Ray::new(rec.p, Vector3::new(0.3, 0.2, 1.0), 0.0);
+    let brdf = rec.mat.brdf(&r, &out, &rec);
+    assert!(brdf.length() > 0.0);
+    assert!((brdf - expected.brdf(&r, &out, &rec)).length() < 1e-6);
+}
+
+#[test]
+fn tessellated_closed_meshes_stay_closed() {
+    // a sliver seen from close up, split far more along its length than across
+    let sliver = MeshData {
+        positions: vec![
+            Point::zero(),
+            Point::new(40.0, 0.0, 0.0),
+            Point::new(20.0, 0.05, 0.0),
+            Point::new(20.0, 0.0, 0.05),
+        ],
+        indices: vec![0, 2, 1, 0, 1, 3, 1, 2, 3, 2, 0, 3],
+        ..MeshData::default()
+    };
+    let target = Tessellation::new(Point::new(20.0, 0.0, 1.0), 90.0, 1000, 0.5).with_max_level(2);
+    let tessellated = sliver.tessellate(&target);
+    assert!(tessellated.indices.len() > sliver.indices.len());
+    assert!(tessellated.surface().closed);
+}
+
+#[test]
+fn displacement_keeps_uv_seams_closed() {
+    // two triangles sharing the edge from (1, 0) to (0, 1), on either side of
+    // a seam where u jumps across the bright stripe
+    let corners = [
+        (0.0, 0.0),
+        (1.0, 0.0),
+        (0.0, 1.0),
+        (1.0, 0.0),
+        (1.0, 1.0),
+        (0.0, 1.0),
+    ];
+    let seam = MeshData {
+        positions: corners.map(|(x, y)| Point::new(x, y, 0.0)).to_vec(),
+        normals: Some(vec![Vector3::new(0.0, 0.0, 1.0); 6]),
+        uvs: Some(vec![
+            (0.1, 0.0),
+            (0.1, 0.0),
+            (0.1, 0.0),
+            (0.9, 0.0),
+            (0.9, 0.0),
+            (0.9, 0.0),
+        ]),
+        indices: vec![0, 1, 2, 3, 4, 5],
+        ..MeshData::default()
+    };
+    let displaced = seam.displace(&Stripe, 0.1, 0.0);
+    let p = &displaced.positions;
+    assert!((p[1] - p[3]).length() < 1e-12 && (p[2] - p[5]).length() < 1e-12);
+    assert!(p[1].z() > p[4].z() && p[1].z() < p[0].z());
+}
+
+#[test]
+fn tori_are_hit_at_any_scale() {
+    for scale in [1e-3, 1.0, 500.0] {
+        let torus = Torus::new(Point::zero(), 2.0 * scale, scale, light());
+        // through the middle of the tube, and just inside its top
+        let across = Ray::new(
+            Point::new(-10.0 * scale, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        let t = torus.hit(&across, 0.001, f64::INFINITY).unwrap().t;
+        assert!(
+            (t - 7.0 * scale).abs() < 1e-9 * scale,
+            "{} at scale {}",
+            t,
+            scale
+        );
+
+        let y = 0.999 * scale;
+        let top = Ray::new(
+            Point::new(-10.0 * scale, y, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        let t = torus.hit(&top, 0.001, f64::INFINITY).unwrap().t;
+        let expected = 10.0 * scale - 2.0 * scale - (scale * scale - y * y).sqrt();
+        assert!(
+            (t - expected).abs() < 1e-9 * scale,
+            "{} at scale {}",
+            t,
+            scale
+        );
+    }
+}
+
+/// Distances and sides of every hit along `r`.
+fn crossings(hittable: &dyn Hittable, r: &Ray) -> Vec<(f64, bool)> {
+    let mut hits = Vec::new();
+    let mut t_min = 0.001;
+    while let Some(rec) = hittable.hit(r, t_min, f64::INFINITY) {
+        hits.push((rec.t, rec.front_face));
+        t_min = rec.t + 1e-6;
+    }
+    hits
+}
+
+#[test]
+fn csg_of_overlapping_spheres_is_hit_at_the_right_boundaries() {
+    let spheres = || {
+        (
+            Sphere::new(Point::zero(), 1.0, light()),
+            Sphere::new(Point::new(1.0, 0.0, 0.0), 1.0, light()),
+        )
+    };
+    // along the x axis the left sphere spans [-1, 1], the right one [0, 2]
+    let r = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 0.0);
+    let expect = |csg: &dyn Hittable, expected: &[(f64, bool)]| {
+        let hits = crossings(csg, &r);
+        assert_eq!(hits.len(), expected.len(), "{:?}", hits);
+        for (hit, expected) in hits.iter().zip(expected) {
+            assert!(
+                (hit.0 - expected.0).abs() < 1e-6 && hit.1 == expected.1,
+                "{:?}",
+                hits
+            );
+        }
+    };
+
+    let (left, right) = spheres();
+    expect(&Csg::union(left, right), &[(4.0, true), (7.0, false)]);
+    let (left, right) = spheres();
+    expect(
+        &Csg::intersection(left, right),
+        &[(5.0, true), (6.0, false)],
+    );
+    let (left, right) = spheres();
+    expect(&Csg::difference(left, right), &[(4.0, true), (5.0, false)]);
+    let (left, right) = spheres();
+    expect(&Csg::difference(right, left), &[(6.0, true), (7.0, false)]);
+}
+
+fn same(a: Vector3, b: Vector3) -> bool {
+    (a - b).length() < 1e-9
+}
+
+/// Writes `bytes` to a file of the temporary directory, named after a test.
+fn fixture(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+#[test]
+fn ascii_and_binary_ply_files_give_the_same_mesh() {
+    let ascii = "ply
+format ascii 1.0
+comment a unit square as one quad
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float u
+property float v
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 0 0 255 0 0
+1 0 0 0 0 1 1 0 0 255 0
+1 1 0 0 0 1 1 1 0 0 255
+0 1 0 0 0 1 0 1 255 255 255
+4 0 1 2 3
+";
+    let mut binary = b"ply
+format binary_little_endian 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float u
+property float v
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+"
+    .to_vec();
+    let vertices = [
+        ([0.0f32, 0.0], [255u8, 0, 0]),
+        ([1.0, 0.0], [0, 255, 0]),
+        ([1.0, 1.0], [0, 0, 255]),
+        ([0.0, 1.0], [255, 255, 255]),
+    ];
+    for ([x, y], color) in vertices {
+        for value in [x, y, 0.0, 0.0, 0.0, 1.0, x, y] {
+            binary.extend_from_slice(&value.to_le_bytes());
+        }
+        binary.extend_from_slice(&color);
+    }
+    binary.push(4);
+    for index in [0i32, 1, 2, 3] {
+        binary.extend_from_slice(&index.to_le_bytes());
+    }
+
+    for (name, bytes) in [
+        ("ascii_fixture.ply", ascii.as_bytes()),
+        ("binary_fixture.ply", &binary[..]),
+    ] {
+        let path = fixture(name, bytes);
+        let data = MeshData::load_ply(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.positions.len(), 4, "{}", name);
+        assert!(
+            same(data.positions[2], Point::new(1.0, 1.0, 0.0)),
+            "{}",
+            name
+        );
+        // the quad is fanned into two triangles
+        assert_eq!(data.indices, vec![0, 1, 2, 0, 2, 3], "{}", name);
+        assert!(
+            same(data.normals.unwrap()[3], Vector3::new(0.0, 0.0, 1.0)),
+            "{}",
+            name
+        );
+        assert_eq!(data.uvs.unwrap()[1], (1.0, 0.0), "{}", name);
+        assert!(
+            same(data.colors.unwrap()[2], Color::new(0.0, 0.0, 1.0)),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn ascii_and_binary_stl_files_give_the_same_mesh() {
+    let ascii = "solid square
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 0
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid square
+";
+    let mut binary = vec![0u8; 80];
+    binary.extend_from_slice(&2u32.to_le_bytes());
+    let facets = [
+        ([0.0f32, 0.0, 1.0], [[0.0f32, 0.0], [1.0, 0.0], [1.0, 1.0]]),
+        ([0.0, 0.0, 0.0], [[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]]),
+    ];
+    for (normal, vertices) in facets {
+        for value in normal {
+            binary.extend_from_slice(&value.to_le_bytes());
+        }
+        for [x, y] in vertices {
+            for value in [x, y, 0.0] {
+                binary.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        binary.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    for (name, bytes) in [
+        ("ascii_fixture.stl", ascii.as_bytes()),
+        ("binary_fixture.stl", &binary[..]),
+    ] {
+        let path = fixture(name, bytes);
+        let data = MeshData::load_stl(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // facets don't share vertices
+        assert_eq!(data.positions.len(), 6, "{}", name);
+        assert_eq!(data.indices, vec![0, 1, 2, 3, 4, 5], "{}", name);
+        assert!(
+            same(data.positions[4], Point::new(1.0, 1.0, 0.0)),
+            "{}",
+            name
+        );
+        // the zero normal of the second facet comes from its winding
+        let normals = data.normals.unwrap();
+        assert!(same(normals[0], Vector3::new(0.0, 0.0, 1.0)), "{}", name);
+        assert!(same(normals[5], Vector3::new(0.0, 0.0, 1.0)), "{}", name);
+        assert!(data.colors.is_none(), "{}", name);
+    }
+}
+
+fn unit_cube_cage() -> ControlCage {
+    let positions = (0..8)
+        .map(|i| Point::new((i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64))
+        .collect();
+    let faces = vec![
+        vec![0, 2, 3, 1],
+        vec![4, 5, 7, 6],
+        vec![0, 1, 5, 4],
+        vec![2, 6, 7, 3],
+        vec![0, 4, 6, 2],
+        vec![1, 3, 7, 5],
+    ];
+    ControlCage::new(positions, faces).unwrap()
+}
+
+#[test]
+fn one_subdivision_step_adds_a_vertex_per_edge_and_face() {
+    let cube = unit_cube_cage();
+
+    // 8 corners, 12 edge points and 6 face points, each quad split in four
+    let catmull_clark = cube.subdivide(SubdivisionScheme::CatmullClark, 1);
+    assert_eq!(catmull_clark.positions.len(), 26);
+    assert_eq!(catmull_clark.faces.len(), 24);
+    assert!(catmull_clark.faces.iter().all(|face| face.len() == 4));
+
+    // the quads are fanned into 12 triangles with 18 edges first
+    let loop_cube = cube.subdivide(SubdivisionScheme::Loop, 1);
+    assert_eq!(loop_cube.positions.len(), 26);
+    assert_eq!(loop_cube.faces.len(), 48);
+    assert!(loop_cube.faces.iter().all(|face| face.len() == 3));
+
+    // both pull the corners in, and the result stays closed
+    for refined in [catmull_clark, loop_cube] {
+        let corner = refined.positions[0];
+        assert!(corner.x() > 0.0 && corner.y() > 0.0 && corner.z() > 0.0);
+        assert!(refined.to_mesh_data().surface().closed);
+    }
+}
+
+#[test]
+fn curves_are_hit_within_their_width() {
+    // a straight curve along x, 0.2 wide, with evenly spaced control points so u
+    // follows x
+    let control_points = [-2.0, -2.0 / 3.0, 2.0 / 3.0, 2.0].map(|x| Point::new(x, 0.0, 0.0));
+    let towards =
+        |x: f64, y: f64| Ray::new(Point::new(x, y, -5.0), Vector3::new(0.0, 0.0, 1.0), 0.0);
+    for mode in [CurveMode::Flat, CurveMode::Cylinder] {
+        let curve = Curve::new(control_points, [0.2, 0.2], mode, light());
+        let rec = curve
+            .hit(&towards(0.9, 0.05), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-6);
+        assert!((rec.u - 0.725).abs() < 1e-3, "u {}", rec.u);
+        assert!(rec.front_face);
+        assert!(curve
+            .hit(&towards(1.0, 0.15), 0.001, f64::INFINITY)
+            .is_none());
+        assert!(curve
+            .hit(&towards(2.5, 0.0), 0.001, f64::INFINITY)
+            .is_none());
+
+        // the segments of a split curve are hit where the whole one is
+        let segments = curve.clone().split(4);
+        let hits: Vec<f64> = segments
+            .iter()
+            .filter_map(|segment| segment.hit(&towards(0.9, 0.05), 0.001, f64::INFINITY))
+            .map(|rec| rec.u)
+            .collect();
+        assert_eq!(hits.len(), 1);
+        assert!((hits[0] - rec.u).abs() < 1e-9);
+    }
+
+    // a ribbon lying in the xz plane is only as wide as it looks from the ray
+    let up = Vector3::new(0.0, 1.0, 0.0);
+    let ribbon = Curve::new(
+        control_points,
+        [0.2, 0.2],
+        CurveMode::Ribbon([up, up]),
+        light(),
+    );
+    let from_below = Ray::new(Point::new(1.0, -5.0, 0.05), up, 0.0);
+    let rec = ribbon.hit(&from_below, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 5.0).abs() < 1e-6);
+    assert!((rec.normal - Vector3::new(0.0, -1.0, 0.0)).length() < 1e-6);
+    assert!(ribbon
+        .hit(&towards(0.9, 0.05), 0.001, f64::INFINITY)
+        .is_none());
+}
+
+#[test]
+fn keyframes_pose_the_object_at_the_ends_and_in_between() {
+    let z = Vector3::new(0.0, 0.0, 1.0);
+    let keyframes = vec![
+        Keyframe::new(0.0).with_translation(Vector3::new(0.0, 0.0, 5.0)),
+        Keyframe::new(1.0)
+            .with_translation(Vector3::new(2.0, 0.0, 5.0))
+            .with_rotation(Quaternion::from_axis_angle(z, 90.0))
+            .with_scale(Vector3::new(3.0, 3.0, 3.0)),
+    ];
+    let square = AARect::new(Plane::XY, 0.0, 1.0, 0.0, 1.0, 0.0, light());
+    let animated = AnimatedTransform::new(square, keyframes).unwrap();
+
+    // the point at uv (0.5, 0.25) of the square, turned, grown and moved
+    // along with the shutter time, which holds still outside the keyframes
+    for (time, pose) in [(-1.0, 0.0), (0.0, 0.0), (0.5, 0.5), (1.0, 1.0), (2.0, 1.0)] {
+        let angle = degress_to_radians(90.0 * pose);
+        let scale = 1.0 + 2.0 * pose;
+        let (x, y) = (0.5 * scale, 0.25 * scale);
+        let target = Point::new(
+            2.0 * pose + x * angle.cos() - y * angle.sin(),
+            x * angle.sin() + y * angle.cos(),
+            0.0,
+        );
+        let rec = animated
+            .hit(&Ray::new(target, z, time), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-9, "t {} at {}", rec.t, time);
+        assert!(
+            (rec.u - 0.5).abs() < 1e-9 && (rec.v - 0.25).abs() < 1e-9,
+            "uv ({}, {}) at {}",
+            rec.u,
+            rec.v,
+            time
+        );
+    }
+}
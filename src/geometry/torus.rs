@@ -0,0 +1,101 @@
+use super::*;
+
+/// Torus around the vertical axis through `center`: a tube of `minor_radius`
+/// swept along a circle of `major_radius` in the xz plane.
+#[derive(Debug, Clone)]
+pub struct Torus<M: Material> {
+    center: Point,
+    major_radius: f64,
+    minor_radius: f64,
+    phi_max: f64,
+    mat: M,
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(center: Point, major_radius: f64, minor_radius: f64, mat: M) -> Torus<M> {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            phi_max: 2.0 * PI,
+            mat,
+        }
+        .with_emitter()
+    }
+
+    pub fn with_phi_max(mut self, degrees: f64) -> Torus<M> {
+        self.phi_max = degress_to_radians(degrees.clamp(0.0, 360.0));
+        self.with_emitter()
+    }
+
+    // hands an emitter the surface again whenever it changes; the tube is
+    // open at its ends unless it goes all the way around
+    fn with_emitter(mut self) -> Torus<M> {
+        let area = self.phi_max * self.major_radius * 2.0 * PI * self.minor_radius;
+        let surface = if self.phi_max >= 2.0 * PI {
+            EmitterSurface::closed(area)
+        } else {
+            EmitterSurface::open(area)
+        };
+        self.mat = self.mat.with_emitter_surface(surface);
+        self
+    }
+}
+
+impl<M: Material> Hittable for Torus<M> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let o = r.origin() - self.center;
+        let d = r.dir();
+        let (r2, a2) = (self.minor_radius.powi(2), self.major_radius.powi(2));
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) expanded along the ray
+        let sum_d_sqrd = d.length_squared();
+        let e = o.length_squared() - a2 - r2;
+        let f = o.dot(d);
+        let four_a_sqrd = 4.0 * a2;
+        let roots = solve_quartic(
+            sum_d_sqrd * sum_d_sqrd,
+            4.0 * sum_d_sqrd * f,
+            2.0 * sum_d_sqrd * e + 4.0 * f * f + four_a_sqrd * d.y() * d.y(),
+            4.0 * f * e + 2.0 * four_a_sqrd * o.y() * d.y(),
+            e * e - four_a_sqrd * (r2 - o.y() * o.y()),
+        );
+
+        let hit = roots
+            .into_iter()
+            .filter(|&t| t >= t_min && t <= t_max)
+            .find_map(|t| {
+                let local = o + t * d;
+                let phi = sweep_angle(local);
+                if phi > self.phi_max {
+                    return None;
+                }
+                let s = local.length_squared();
+                let normal = (4.0 * (s - a2 - r2) * local
+                    + Vector3::new(0.0, 8.0 * a2 * local.y(), 0.0))
+                .normalize();
+                let distance = (local.x().powi(2) + local.z().powi(2)).sqrt();
+                let ring = distance - self.major_radius;
+                let theta = local.y().atan2(ring);
+                // v runs around the tube
+                let outward = Vector3::new(local.x(), 0.0, local.z()) / distance.max(1e-12);
+                let bitangent = 2.0 * PI * (Vector3::new(0.0, ring, 0.0) - local.y() * outward);
+                Some(LocalHit {
+                    t,
+                    local,
+                    normal,
+                    u: phi / self.phi_max,
+                    v: (theta + PI) / (2.0 * PI),
+                    tangent: self.phi_max * Vector3::new(-local.z(), 0.0, local.x()),
+                    bitangent,
+                })
+            })?;
+        Some(hit.record(r, self.center, &self.mat))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = Vector3::new(outer, self.minor_radius, outer);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
@@ -13,7 +13,7 @@ impl<H: Hittable> Translate<H> {
 
 impl<H: Hittable> Hittable for Translate<H> {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let moved_r = Ray::new(r.origin() - self.offset, r.dir(), r.time());
+        let moved_r = Ray::new(r.origin() - self.offset, r.dir(), r.time()).with_type(r.ray_type());
         self.hptr.hit(&moved_r, t_min, t_max).map(|mut hit| {
             hit.p += self.offset;
             hit
@@ -27,4 +27,16 @@ impl<H: Hittable> Hittable for Translate<H> {
             aabb
         })
     }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        self.hptr.pdf_value(o - self.offset, v, time)
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        self.hptr.random(o - self.offset, time)
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.hptr.visibility()
+    }
 }
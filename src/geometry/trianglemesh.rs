@@ -0,0 +1,571 @@
+use rand::Rng;
+
+use super::*;
+use std::sync::Arc;
+
+/// Storage type of vertex attributes; `f32` halves the size of large meshes.
+pub trait VertexScalar: Copy + Send + Sync + 'static {
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl VertexScalar for f32 {
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl VertexScalar for f64 {
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+fn to_vector<S: VertexScalar>(v: [S; 3]) -> Vector3 {
+    Vector3::new(v[0].to_f64(), v[1].to_f64(), v[2].to_f64())
+}
+
+fn from_vector<S: VertexScalar>(v: Vector3) -> [S; 3] {
+    [S::from_f64(v.x()), S::from_f64(v.y()), S::from_f64(v.z())]
+}
+
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+// triangles a light sampling direction is followed through
+const MAX_LIGHT_CROSSINGS: usize = 64;
+
+/// A leaf references `count` triangles starting at `start`, a branch has
+/// `count == 0` and its children at `start` and `start + 1`.
+#[derive(Debug, Clone, Copy)]
+struct MeshNode {
+    bbox: Aabb,
+    start: u32,
+    count: u32,
+}
+
+/// Vertex positions (and normals, if the mesh has them) at evenly spaced
+/// times across `[time0, time1]`, the mesh's own buffers being the first.
+#[derive(Debug, Clone)]
+struct MeshMotion<S: VertexScalar> {
+    positions: Vec<Arc<Vec<[S; 3]>>>,
+    normals: Vec<Arc<Vec<[S; 3]>>>,
+    time0: f64,
+    time1: f64,
+}
+
+/// Probability of each triangle to be picked for a light sample, in mesh order.
+#[derive(Debug, Clone)]
+struct TriangleLights {
+    pmf: Vec<f64>,
+    cdf: Vec<f64>,
+}
+
+impl TriangleLights {
+    fn new(weights: Vec<f64>) -> Option<TriangleLights> {
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 || !total.is_finite() {
+            return None;
+        }
+        let pmf: Vec<f64> = weights.iter().map(|w| w / total).collect();
+        let cdf = pmf
+            .iter()
+            .scan(0.0, |sum, p| {
+                *sum += p;
+                Some(*sum)
+            })
+            .collect();
+        Some(TriangleLights { pmf, cdf })
+    }
+
+    fn sample(&self, xi: f64) -> usize {
+        self.cdf
+            .partition_point(|&c| c <= xi)
+            .min(self.cdf.len() - 1)
+    }
+}
+
+/// Shared-vertex triangle mesh. Vertex buffers sit behind `Arc`s so several
+/// meshes can reference them, triangles are index triples, and the mesh keeps
+/// its own flat BVH over triangle indices instead of boxing every triangle.
+pub struct TriangleMesh<M: Material, S: VertexScalar = f64> {
+    positions: Arc<Vec<[S; 3]>>,
+    normals: Option<Arc<Vec<[S; 3]>>>,
+    uvs: Option<Arc<Vec<[S; 2]>>>,
+    triangles: Vec<[u32; 3]>,
+    nodes: Vec<MeshNode>,
+    motion: Option<MeshMotion<S>>,
+    lights: Option<TriangleLights>,
+    mat: M,
+}
+
+impl<M: Material, S: VertexScalar> TriangleMesh<M, S> {
+    pub fn new(
+        positions: Arc<Vec<[S; 3]>>,
+        triangles: Vec<[u32; 3]>,
+        mat: M,
+    ) -> Result<TriangleMesh<M, S>, String> {
+        if triangles
+            .iter()
+            .flatten()
+            .any(|&i| i as usize >= positions.len())
+        {
+            return Err("mesh index out of range".to_string());
+        }
+        let surface = triangles_surface(
+            triangles
+                .iter()
+                .map(|triangle| triangle.map(|i| to_vector(positions[i as usize]))),
+        );
+        let mut mesh = TriangleMesh {
+            positions,
+            normals: None,
+            uvs: None,
+            triangles,
+            nodes: Vec::new(),
+            motion: None,
+            lights: None,
+            mat: mat.with_emitter_surface(surface),
+        };
+        mesh.build_bvh();
+        Ok(mesh)
+    }
+
+    pub fn from_data(data: &MeshData, mat: M) -> Result<TriangleMesh<M, S>, String> {
+        data.validate()?;
+        let positions = data.positions.iter().map(|&p| from_vector(p)).collect();
+        let triangles = data
+            .indices
+            .chunks_exact(3)
+            .map(|face| [face[0], face[1], face[2]])
+            .collect();
+        let mut mesh = TriangleMesh::new(Arc::new(positions), triangles, mat)?;
+        if let Some(normals) = &data.normals {
+            let normals = normals.iter().map(|&n| from_vector(n)).collect();
+            mesh = mesh.with_normals(Arc::new(normals))?;
+        }
+        if let Some(uvs) = &data.uvs {
+            let uvs = uvs
+                .iter()
+                .map(|&(u, v)| [S::from_f64(u), S::from_f64(v)])
+                .collect();
+            mesh = mesh.with_uvs(Arc::new(uvs))?;
+        }
+        Ok(mesh)
+    }
+
+    /// Animated mesh with one `MeshData` per time sample, spread evenly
+    /// across `[time0, time1]`. All samples share the first one's topology and
+    /// texture coordinates; normals are used if every sample has them.
+    pub fn from_data_animated(
+        frames: &[MeshData],
+        time0: f64,
+        time1: f64,
+        mat: M,
+    ) -> Result<TriangleMesh<M, S>, String> {
+        let first = frames
+            .first()
+            .ok_or_else(|| "animated mesh needs at least one frame".to_string())?;
+        let mut mesh = TriangleMesh::from_data(first, mat)?;
+        if frames.len() == 1 {
+            return Ok(mesh);
+        }
+        let buffer = |values: &Vec<Vector3>| -> Arc<Vec<[S; 3]>> {
+            Arc::new(values.iter().map(|&v| from_vector(v)).collect())
+        };
+        let positions = frames[1..].iter().map(|f| buffer(&f.positions)).collect();
+        let normals = if frames.iter().all(|f| f.normals.is_some()) {
+            Some(
+                frames[1..]
+                    .iter()
+                    .map(|f| buffer(f.normals.as_ref().unwrap()))
+                    .collect(),
+            )
+        } else {
+            mesh.normals = None;
+            None
+        };
+        mesh.with_motion(positions, normals, time0, time1)
+    }
+
+    /// Deformation blur: `positions` (and `normals` for meshes with normals)
+    /// are further vertex samples after the mesh's own, evenly spaced so that
+    /// the first sample is at `time0` and the last at `time1`. Vertices move
+    /// linearly between samples.
+    pub fn with_motion(
+        mut self,
+        positions: Vec<Arc<Vec<[S; 3]>>>,
+        normals: Option<Vec<Arc<Vec<[S; 3]>>>>,
+        time0: f64,
+        time1: f64,
+    ) -> Result<TriangleMesh<M, S>, String> {
+        let count = self.positions.len();
+        if positions.iter().any(|p| p.len() != count) {
+            return Err("mesh motion sample vertex count does not match mesh".to_string());
+        }
+        let normals = match (&self.normals, normals) {
+            (None, None) => Vec::new(),
+            (Some(_), Some(normals)) => {
+                if normals.len() != positions.len() || normals.iter().any(|n| n.len() != count) {
+                    return Err("mesh motion normals do not match the position samples".to_string());
+                }
+                normals
+            }
+            (Some(_), None) => {
+                return Err("mesh with normals needs normals for every motion sample".to_string())
+            }
+            (None, Some(_)) => {
+                return Err("mesh motion normals given for a mesh without normals".to_string())
+            }
+        };
+        let mut all_positions = vec![self.positions.clone()];
+        all_positions.extend(positions);
+        let mut all_normals = Vec::new();
+        if let Some(own) = &self.normals {
+            all_normals.push(own.clone());
+            all_normals.extend(normals);
+        }
+        self.motion = Some(MeshMotion {
+            positions: all_positions,
+            normals: all_normals,
+            time0,
+            time1,
+        });
+        self.build_bvh();
+        Ok(self)
+    }
+
+    pub fn with_normals(mut self, normals: Arc<Vec<[S; 3]>>) -> Result<TriangleMesh<M, S>, String> {
+        if normals.len() != self.positions.len() {
+            return Err("mesh normal count does not match vertex count".to_string());
+        }
+        if self.motion.is_some() {
+            return Err("mesh normals must be set before its motion".to_string());
+        }
+        self.normals = Some(normals);
+        Ok(self)
+    }
+
+    pub fn with_uvs(mut self, uvs: Arc<Vec<[S; 2]>>) -> Result<TriangleMesh<M, S>, String> {
+        if uvs.len() != self.positions.len() {
+            return Err("mesh uv count does not match vertex count".to_string());
+        }
+        self.uvs = Some(uvs);
+        Ok(self)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Picks the triangles to sample as a light by the power they emit,
+    /// their area times their luminance averaged over `resolution` x
+    /// `resolution` points, instead of all alike.
+    pub fn with_importance_sampling(mut self, resolution: usize) -> TriangleMesh<M, S> {
+        let resolution = resolution.max(1);
+        let weights = (0..self.triangles.len())
+            .map(|index| {
+                let [v0, v1, v2] = self.vertices(&self.triangles[index], 0.0);
+                let normal = (v1 - v0).cross(v2 - v0);
+                let area = 0.5 * normal.length();
+                if area <= 0.0 {
+                    return 0.0;
+                }
+                let normal = normal / (2.0 * area);
+                let mut luminance = 0.0;
+                for j in 0..resolution {
+                    for i in 0..resolution {
+                        let (b1, b2) = square_to_triangle(
+                            (i as f64 + 0.5) / resolution as f64,
+                            (j as f64 + 0.5) / resolution as f64,
+                        );
+                        // looked at from the front, as `emitted_luminance` does
+                        let p = v0 + b1 * (v1 - v0) + b2 * (v2 - v0);
+                        let probe = Ray::new(p + normal, -normal, 0.0);
+                        let rec = self.record(index, &probe, 1.0, b1, b2);
+                        let c = rec.mat.emitted(&rec);
+                        luminance += 0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b();
+                    }
+                }
+                area * luminance / (resolution * resolution) as f64
+            })
+            .collect();
+        self.lights = TriangleLights::new(weights);
+        self
+    }
+
+    fn light_pmf(&self, index: usize) -> f64 {
+        match &self.lights {
+            Some(lights) => lights.pmf[index],
+            None => 1.0 / self.triangles.len() as f64,
+        }
+    }
+
+    /// Index of the motion sample before `time` and the weight of the one after it.
+    fn motion_sample(motion: &MeshMotion<S>, time: f64) -> (usize, f64) {
+        let last = motion.positions.len() - 1;
+        let span = motion.time1 - motion.time0;
+        let x = if span > 0.0 {
+            ((time - motion.time0) / span).clamp(0.0, 1.0) * last as f64
+        } else {
+            0.0
+        };
+        let sample = (x.floor() as usize).min(last.saturating_sub(1));
+        (sample, x - sample as f64)
+    }
+
+    fn vertices(&self, triangle: &[u32; 3], time: f64) -> [Point; 3] {
+        match &self.motion {
+            Some(motion) => {
+                let (sample, w) = TriangleMesh::<M, S>::motion_sample(motion, time);
+                let (p0, p1) = (&motion.positions[sample], &motion.positions[sample + 1]);
+                triangle.map(|i| to_vector(p0[i as usize]).lerp(to_vector(p1[i as usize]), w))
+            }
+            None => triangle.map(|i| to_vector(self.positions[i as usize])),
+        }
+    }
+
+    fn shading_normals(&self, triangle: &[u32; 3], time: f64) -> Option<[Vector3; 3]> {
+        let normals = self.normals.as_ref()?;
+        Some(match &self.motion {
+            Some(motion) => {
+                let (sample, w) = TriangleMesh::<M, S>::motion_sample(motion, time);
+                let (n0, n1) = (&motion.normals[sample], &motion.normals[sample + 1]);
+                triangle.map(|i| to_vector(n0[i as usize]).lerp(to_vector(n1[i as usize]), w))
+            }
+            None => triangle.map(|i| to_vector(normals[i as usize])),
+        })
+    }
+
+    /// Box of the triangle over all motion samples; vertices move linearly
+    /// between samples, so that bounds it at every time.
+    fn triangle_box(&self, triangle: &[u32; 3]) -> Aabb {
+        let samples = match &self.motion {
+            Some(motion) => motion.positions.iter().collect(),
+            None => vec![&self.positions],
+        };
+        let mut min = Vector3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = -min;
+        for positions in samples {
+            for &i in triangle {
+                let p = to_vector(positions[i as usize]);
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(p[axis]);
+                    max[axis] = max[axis].max(p[axis]);
+                }
+            }
+        }
+        let pad = Vector3::one() * 0.0001;
+        Aabb::new(min - pad, max + pad)
+    }
+
+    fn build_bvh(&mut self) {
+        self.nodes.clear();
+        if self.triangles.is_empty() {
+            return;
+        }
+        let boxes: Vec<Aabb> = self
+            .triangles
+            .iter()
+            .map(|tri| self.triangle_box(tri))
+            .collect();
+        let mut order: Vec<u32> = (0..self.triangles.len() as u32).collect();
+
+        self.nodes.push(MeshNode {
+            bbox: boxes[0],
+            start: 0,
+            count: 0,
+        });
+        // (node index, first triangle, triangle count)
+        let mut todo = vec![(0, 0, order.len())];
+        while let Some((node, start, count)) = todo.pop() {
+            let bbox = order[start..start + count]
+                .iter()
+                .map(|&i| boxes[i as usize])
+                .reduce(|acc, b| Aabb::surrounding_box(&acc, &b))
+                .unwrap();
+
+            if count <= MAX_LEAF_TRIANGLES {
+                self.nodes[node] = MeshNode {
+                    bbox,
+                    start: start as u32,
+                    count: count as u32,
+                };
+                continue;
+            }
+
+            let centroid = |i: u32| {
+                let b = boxes[i as usize];
+                (b.min + b.max) * 0.5
+            };
+            let axis = (0..3)
+                .max_by(|&a, &b| {
+                    let extent = |axis: usize| bbox.max[axis] - bbox.min[axis];
+                    extent(a).partial_cmp(&extent(b)).unwrap()
+                })
+                .unwrap();
+            let half = count / 2;
+            order[start..start + count].select_nth_unstable_by(half, |&a, &b| {
+                centroid(a)[axis].partial_cmp(&centroid(b)[axis]).unwrap()
+            });
+
+            let left = self.nodes.len();
+            let placeholder = MeshNode {
+                bbox,
+                start: 0,
+                count: 0,
+            };
+            self.nodes.push(placeholder);
+            self.nodes.push(placeholder);
+            self.nodes[node] = MeshNode {
+                bbox,
+                start: left as u32,
+                count: 0,
+            };
+            todo.push((left, start, half));
+            todo.push((left + 1, start + half, count - half));
+        }
+
+        // store triangles in leaf order so leaves index contiguous ranges
+        self.triangles = order.iter().map(|&i| self.triangles[i as usize]).collect();
+        if let Some(lights) = &self.lights {
+            self.lights =
+                TriangleLights::new(order.iter().map(|&i| lights.pmf[i as usize]).collect());
+        }
+    }
+
+    /// Index, t and the barycentric weights of the second and third vertex
+    /// of the closest triangle `r` hits.
+    fn closest(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(usize, f64, f64, f64)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest: Option<(usize, f64, f64, f64)> = None;
+        let mut closest_so_far = t_max;
+        let mut stack = vec![0usize];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !node.bbox.hit(r, t_min, closest_so_far) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start as usize);
+                stack.push(node.start as usize + 1);
+                continue;
+            }
+            for index in node.start as usize..(node.start + node.count) as usize {
+                let vertices = self.vertices(&self.triangles[index], r.time());
+                if let Some((t, b1, b2)) = intersect_triangle(r, &vertices, t_min, closest_so_far) {
+                    closest_so_far = t;
+                    closest = Some((index, t, b1, b2));
+                }
+            }
+        }
+        closest
+    }
+
+    fn record(&self, index: usize, r: &Ray, t: f64, b1: f64, b2: f64) -> HitRecord<'_> {
+        let triangle = self.triangles[index];
+
+        let [v0, v1, v2] = self.vertices(&triangle, r.time());
+        let b0 = 1.0 - b1 - b2;
+        let normal = (v1 - v0).cross(v2 - v0).normalize();
+        let (u, v) = match &self.uvs {
+            Some(uvs) => {
+                let [uv0, uv1, uv2] = triangle.map(|i| uvs[i as usize]);
+                (
+                    b0 * uv0[0].to_f64() + b1 * uv1[0].to_f64() + b2 * uv2[0].to_f64(),
+                    b0 * uv0[1].to_f64() + b1 * uv1[1].to_f64() + b2 * uv2[1].to_f64(),
+                )
+            }
+            None => (b1, b2),
+        };
+        let (e1, e2) = (v1 - v0, v2 - v0);
+        let (tangent, bitangent) = match &self.uvs {
+            Some(uvs) => {
+                let [uv0, uv1, uv2] = triangle.map(|i| uvs[i as usize]);
+                let delta = |uv: [S; 2]| {
+                    (
+                        uv[0].to_f64() - uv0[0].to_f64(),
+                        uv[1].to_f64() - uv0[1].to_f64(),
+                    )
+                };
+                triangle_tangents(e1, e2, delta(uv1), delta(uv2))
+            }
+            None => (e1, e2),
+        };
+
+        let mut rec = HitRecord {
+            p: r.at(t),
+            normal,
+            t,
+            primitive: 0,
+            u,
+            v,
+            front_face: false,
+            tangent,
+            bitangent,
+            mat: &self.mat,
+        };
+        rec.set_face_normal(r, normal);
+        if let Some([n0, n1, n2]) = self.shading_normals(&triangle, r.time()) {
+            let shading = (b0 * n0 + b1 * n1 + b2 * n2).normalize();
+            rec.normal = if rec.front_face { shading } else { -shading };
+        }
+        rec
+    }
+}
+
+impl<M: Material, S: VertexScalar> Hittable for TriangleMesh<M, S> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (index, t, b1, b2) = self.closest(r, t_min, t_max)?;
+        Some(self.record(index, r, t, b1, b2))
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        self.nodes.first().map(|root| root.bbox)
+    }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        // samples land on any triangle, so every one the direction crosses counts
+        let r = Ray::new(o, v, time);
+        let mut t_min = 0.001;
+        let mut pdf = 0.0;
+        for _ in 0..MAX_LIGHT_CROSSINGS {
+            let (index, t, _, _) = match self.closest(&r, t_min, f64::INFINITY) {
+                Some(hit) => hit,
+                None => break,
+            };
+            let [v0, v1, v2] = self.vertices(&self.triangles[index], time);
+            let n = (v1 - v0).cross(v2 - v0);
+            let area = 0.5 * n.length();
+            let distance_squared = t.powi(2) * v.length_squared();
+            let cosine = v.dot(n).abs() / (v.length() * n.length());
+            if cosine > 0.0 && area > 0.0 {
+                pdf += self.light_pmf(index) * distance_squared / (cosine * area);
+            }
+            t_min = t + 1e-9 * t.abs().max(1.0);
+        }
+        pdf
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        let mut rng = rand::thread_rng();
+        if self.triangles.is_empty() {
+            return Vector3::new(1.0, 0.0, 0.0);
+        }
+        let index = match &self.lights {
+            Some(lights) => lights.sample(rng.gen()),
+            None => rng.gen_range(0..self.triangles.len()),
+        };
+        let (b1, b2) = square_to_triangle(rng.gen(), rng.gen());
+        let [v0, v1, v2] = self.vertices(&self.triangles[index], time);
+        v0 + b1 * (v1 - v0) + b2 * (v2 - v0) - o
+    }
+}
@@ -0,0 +1,47 @@
+use super::*;
+
+/// Limits which rays see `hittable`, e.g. a light fixture's housing that
+/// should not block shadow rays, or a backdrop only visible in reflections.
+///
+/// `HittableList` and `BVH` skip objects whose visibility doesn't include the
+/// ray's type, and `HittableList` only samples the lights that have
+/// `Visibility::LIGHT_SAMPLING`.
+#[derive(Debug, Clone)]
+pub struct Visible<H: Hittable> {
+    hittable: H,
+    visibility: Visibility,
+}
+
+impl<H: Hittable> Visible<H> {
+    pub fn new(hittable: H, visibility: Visibility) -> Visible<H> {
+        Visible {
+            hittable,
+            visibility,
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for Visible<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        if !self.visibility.sees(r.ray_type()) {
+            return None;
+        }
+        self.hittable.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.hittable.bounding_box(time0, time1)
+    }
+
+    fn pdf_value(&self, o: Point, v: Vector3, time: f64) -> f64 {
+        self.hittable.pdf_value(o, v, time)
+    }
+
+    fn random(&self, o: Vector3, time: f64) -> Vector3 {
+        self.hittable.random(o, time)
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+}
@@ -9,6 +9,7 @@ use image::*;
 use rand::Rng;
 use rayon::prelude::*;
 use std::io::{stderr, Write};
+use std::sync::Arc;
 
 use camera::*;
 use geometry::*;
@@ -44,9 +45,10 @@ fn ray_color(
         //                 * ray_color(&specular_ray, background, world, lights, depth - 1);
         //         }
         //         ScatterRecord::Scatter { pdf, attenuation } => {
-        //             let hittable_pdf = PDF::hittable_pdf(rec.p, lights);
+        //             let hittable_pdf = PDF::hittable_pdf(rec.p, r.time(), lights);
         //             let mixture_pdf = PDF::mixture_pdf(&hittable_pdf, &pdf);
-        //             let scattered = Ray::new(rec.p, mixture_pdf.generate(), r.time());
+        //             let (direction, ray_type) = mixture_pdf.sample();
+        //             let scattered = Ray::new(rec.p, direction, r.time()).with_type(ray_type);
         //             let pdf_value = mixture_pdf.value(scattered.dir());
         //             return emitted
         //                 + attenuation
@@ -55,9 +57,10 @@ fn ray_color(
         //                     / pdf_value;
         //         }
         //         ScatterRecord::Microfacet { pdf } => {
-        //             let hittable_pdf = PDF::hittable_pdf(rec.p, lights);
+        //             let hittable_pdf = PDF::hittable_pdf(rec.p, r.time(), lights);
         //             let mixture_pdf = PDF::mixture_pdf(&hittable_pdf, &pdf);
-        //             let scattered = Ray::new(rec.p, mixture_pdf.generate(), r.time());
+        //             let (direction, ray_type) = mixture_pdf.sample();
+        //             let scattered = Ray::new(rec.p, direction, r.time()).with_type(ray_type);
         //             let pdf_value = mixture_pdf.value(scattered.dir());
         //             return emitted
         //                 + rec.mat.brdf(r, &scattered, &rec)
@@ -74,10 +77,63 @@ fn ray_color(
     }
 }
 
+// same as ray_color, carrying the radiance at the `wavelengths` the ray is traced at
+fn ray_spectrum(
+    r: &Ray,
+    wavelengths: &SampledWavelengths,
+    background: Color,
+    world: &dyn Hittable,
+    depth: u64,
+) -> SampledSpectrum {
+    if depth == 0 {
+        return SampledSpectrum::zero();
+    }
+
+    if let Some(rec) = world.hit(r, 0.001, f64::INFINITY) {
+        let emitted = rec.mat.emitted_spectrum(&rec, wavelengths);
+
+        if let Some((attenuation, scattered)) = rec.mat.scatter(r, &rec) {
+            let scattered = scattered.inherit_wavelengths(r);
+            let after = scattered.wavelengths().unwrap_or(*wavelengths);
+            emitted
+                + SampledSpectrum::from_rgb(attenuation, wavelengths)
+                    * wavelengths.reweight(&after)
+                    * ray_spectrum(&scattered, &after, background, world, depth - 1)
+        } else {
+            emitted
+        }
+    } else {
+        SampledSpectrum::from_rgb_illuminant(background, wavelengths)
+    }
+}
+
+/// How light is carried along paths.
+enum Pipeline {
+    Rgb,
+    /// Per wavelength, with colors uplifted to spectra and converted back to
+    /// RGB at the film.
+    Spectral,
+}
+
 enum Scene {
     TwoSphere,
     CornellBox,
     CornelBoxTest,
+    Gltf,
+    TriangleMesh,
+    PlanarShapes,
+    Quadrics,
+    Csg,
+    Terrain,
+    Subdivision,
+    Displacement,
+    Curves,
+    MotionBlur,
+    Visibility,
+    LayeredMaterials,
+    BumpMapping,
+    AlphaMask,
+    Subsurface,
 }
 
 fn two_sphere() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
@@ -128,7 +184,7 @@ fn cornell_box() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
     let m_lemon_yellow = Lambertian::new(SolidTexture::new(Color::new(0.894, 0.941, 0.141)));
     let m_cotinga_purple = Lambertian::new(SolidTexture::new(Color::new(0.204, 0.000, 0.349)));
     let m_dielectric = Dielectric::new(1.5);
-    let m_metal = Metallic::new(Color::new(0.8, 0.85, 0.88), 0.0);
+    let m_metal = Conductor::aluminium(0.0);
     let m_light = DiffuseLight::new(SolidTexture::new(Color::new(15.0, 15.0, 15.0)));
     let m_pbr = PBR::new(
         SolidTexture::new(Color::new(0.6, 0.7, 0.2)),
@@ -222,6 +278,568 @@ fn cornell_box() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
 
     (Box::new(world), Box::new(lights))
 }
+
+/// Walls and ceiling light of the Cornell box, for scenes showing off a few
+/// objects inside it.
+fn cornell_walls(world: &mut HittableList, lights: &mut HittableList) {
+    let m_red = Lambertian::new(SolidTexture::new(Color::new(0.65, 0.05, 0.05)));
+    let m_white = Lambertian::new(SolidTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let m_green = Lambertian::new(SolidTexture::new(Color::new(0.12, 0.45, 0.15)));
+    let m_light = DiffuseLight::new(SolidTexture::new(Color::new(15.0, 15.0, 15.0)));
+    let rect_light = AARect::new(Plane::XZ, 213.0, 343.0, 227.0, 332.0, 554.0, m_light);
+
+    world.add(AARect::new(
+        Plane::YZ,
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        m_green,
+    ));
+    world.add(AARect::new(Plane::YZ, 0.0, 555.0, 0.0, 555.0, 0.0, m_red));
+    world.add(AARect::new(Plane::XZ, 0.0, 555.0, 0.0, 555.0, 0.0, m_white));
+    world.add(AARect::new(
+        Plane::XZ,
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        m_white,
+    ));
+    world.add(AARect::new(
+        Plane::XY,
+        0.0,
+        555.0,
+        0.0,
+        555.0,
+        555.0,
+        m_white,
+    ));
+    world.add(rect_light.clone());
+    lights.add(rect_light);
+}
+
+fn cornell_camera(aspect_ratio: f64) -> Camera {
+    let lookfrom = Point::new(278.0, 278.0, -800.0);
+    let lookat = Point::new(278.0, 278.0, 0.0);
+    let vup = Vector3::new(0.0, 1.0, 0.0);
+    let dist_to_focus = 10.0;
+    let aperture = 0.0;
+    Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        40.0,
+        aspect_ratio,
+        aperture,
+        dist_to_focus,
+        0.0,
+        1.0,
+    )
+}
+
+/// Glass octahedron sharing its six vertices between eight faces, stored in
+/// single precision.
+fn triangle_mesh() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let (x, y, z, r): (f32, f32, f32, f32) = (278.0, 200.0, 278.0, 150.0);
+    let positions: Vec<[f32; 3]> = vec![
+        [x + r, y, z],
+        [x - r, y, z],
+        [x, y + r, z],
+        [x, y - r, z],
+        [x, y, z + r],
+        [x, y, z - r],
+    ];
+    let triangles = vec![
+        [0, 2, 4],
+        [2, 1, 4],
+        [1, 3, 4],
+        [3, 0, 4],
+        [2, 0, 5],
+        [1, 2, 5],
+        [3, 1, 5],
+        [0, 3, 5],
+    ];
+    let gem = TriangleMesh::new(Arc::new(positions), triangles, Dielectric::new(1.5)).unwrap();
+    world.add(gem);
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// Cards leaning on each other on an endless checkered floor, under a round
+/// light.
+fn planar_shapes() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+
+    let m_floor = Lambertian::new(CheckerTexture::new(
+        SolidTexture::new(Color::new(0.2, 0.3, 0.1)),
+        SolidTexture::new(Color::new(0.9, 0.9, 0.9)),
+    ));
+    let m_card = Lambertian::new(SolidTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let m_mirror = Metallic::new(Color::new(0.8, 0.85, 0.9), 0.0);
+    let m_light = DiffuseLight::new(SolidTexture::new(Color::new(10.0, 10.0, 10.0)));
+
+    world.add(InfinitePlane::new(
+        Point::zero(),
+        Vector3::new(0.0, 1.0, 0.0),
+        m_floor,
+    ));
+    world.add(Quad::new(
+        Point::new(-1.5, 0.0, 0.0),
+        Vector3::new(1.5, 2.5, 0.0),
+        Vector3::new(0.0, 0.0, 1.5),
+        m_card,
+    ));
+    world.add(Quad::new(
+        Point::new(1.5, 0.0, 0.0),
+        Vector3::new(-1.5, 2.5, 0.0),
+        Vector3::new(0.0, 0.0, 1.5),
+        m_card,
+    ));
+    world.add(Quad::new(
+        Point::new(-3.0, 0.0, -2.0),
+        Vector3::new(6.0, 0.0, 0.0),
+        Vector3::new(0.0, 3.0, 0.0),
+        m_mirror,
+    ));
+
+    let disk_light = Disk::new(
+        Point::new(0.0, 6.0, 2.0),
+        Vector3::new(0.0, -1.0, 0.0),
+        1.5,
+        m_light,
+    );
+    world.add(disk_light.clone());
+    lights.add(disk_light);
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// One of each quadric and a torus, the paraboloid cut open to show its inside.
+fn quadrics() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_white = Lambertian::new(SolidTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let m_blue = Lambertian::new(SolidTexture::new(Color::new(0.051, 0.459, 1.000)));
+    let m_metal = Metallic::new(Color::new(0.8, 0.85, 0.88), 0.1);
+    let m_glass = Dielectric::new(1.5);
+
+    world.add(Cylinder::new(Point::new(120.0, 0.0, 380.0), 60.0, 160.0, m_blue).capped(true));
+    world.add(Cone::new(Point::new(430.0, 0.0, 400.0), 80.0, 200.0, m_white).capped(true));
+    world.add(
+        Paraboloid::new(Point::new(140.0, 0.0, 150.0), 60.0, 120.0, m_metal).with_phi_max(270.0),
+    );
+    world.add(Hyperboloid::new(
+        Point::new(420.0, 110.0, 160.0),
+        30.0,
+        60.0,
+        220.0,
+        m_white,
+    ));
+    world.add(Torus::new(
+        Point::new(278.0, 30.0, 250.0),
+        80.0,
+        30.0,
+        m_glass,
+    ));
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// A die rounded off by intersecting a cube with a sphere, and a sphere with
+/// a cube taken out of it.
+fn csg() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_white = Lambertian::new(SolidTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let m_red = Lambertian::new(SolidTexture::new(Color::new(0.65, 0.05, 0.05)));
+    let m_glass = Dielectric::new(1.5);
+
+    world.add(Csg::intersection(
+        Cube::new(
+            Point::new(100.0, 0.0, 250.0),
+            Point::new(260.0, 160.0, 410.0),
+            m_white,
+        ),
+        Sphere::new(Point::new(180.0, 80.0, 330.0), 105.0, m_white),
+    ));
+    world.add(Csg::difference(
+        Sphere::new(Point::new(380.0, 110.0, 200.0), 110.0, m_glass),
+        Cube::new(
+            Point::new(380.0, 110.0, 60.0),
+            Point::new(520.0, 250.0, 200.0),
+            m_red,
+        ),
+    ));
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// Perlin noise terrain under the sky, with a blob of three metaballs
+/// floating over it.
+fn terrain() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let lights = HittableList::default();
+
+    let m_ground = Lambertian::new(SolidTexture::new(Color::new(0.35, 0.45, 0.2)));
+    let m_blob = Metallic::new(Color::new(0.9, 0.6, 0.3), 0.2);
+
+    let ground = Heightfield::from_perlin(
+        &Perlin::new(256),
+        0.3,
+        [256, 256],
+        Point::new(-10.0, 0.0, -10.0),
+        Vector3::new(20.0, 3.0, 20.0),
+        m_ground,
+    )
+    .unwrap();
+    world.add(ground);
+
+    // negative inside, where the balls' falloffs add up to more than one
+    let centers = [
+        Point::new(-1.0, 5.0, 0.0),
+        Point::new(1.0, 5.5, 0.0),
+        Point::new(0.0, 4.5, 1.0),
+    ];
+    let metaballs = move |p: Point| {
+        1.0 - centers
+            .iter()
+            .map(|&c| 1.0 / (p - c).length_squared().max(1e-9))
+            .sum::<f64>()
+    };
+    world.add(Isosurface::new(
+        metaballs,
+        Aabb::new(Point::new(-3.0, 2.5, -2.0), Point::new(3.0, 7.5, 3.0)),
+        m_blob,
+    ));
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// Cube cages refined three times: smoothly by Catmull-Clark, and by Loop
+/// with the edges around the top kept sharp.
+fn subdivision() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_white = Lambertian::new(SolidTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let m_blue = Lambertian::new(SolidTexture::new(Color::new(0.051, 0.459, 1.000)));
+
+    // corner i of the cube is at the bits of i, x lowest
+    let cube = |corner: Point, size: f64| {
+        let positions = (0..8)
+            .map(|i| {
+                let offset =
+                    Vector3::new((i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64);
+                corner + offset * size
+            })
+            .collect();
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+        ];
+        ControlCage::new(positions, faces).unwrap()
+    };
+
+    let smooth = cube(Point::new(90.0, 0.0, 250.0), 170.0)
+        .subdivide(SubdivisionScheme::CatmullClark, 3)
+        .to_mesh_data();
+    world.add(TriangleMesh::<_, f64>::from_data(&smooth, m_white).unwrap());
+
+    let creased = cube(Point::new(300.0, 0.0, 120.0), 170.0)
+        .with_crease(2, 6, f64::INFINITY)
+        .with_crease(6, 7, f64::INFINITY)
+        .with_crease(7, 3, f64::INFINITY)
+        .with_crease(3, 2, f64::INFINITY)
+        .subdivide(SubdivisionScheme::Loop, 3)
+        .to_mesh_data();
+    world.add(TriangleMesh::<_, f64>::from_data(&creased, m_blue).unwrap());
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// Rocky floor: a square of two triangles tessellated to a few pixels per
+/// edge as seen by `cornell_camera` and displaced by noise.
+fn displacement(image_height: u32) -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_rock = Lambertian::new(SolidTexture::new(Color::new(0.55, 0.5, 0.45)));
+
+    let slab = MeshData {
+        positions: vec![
+            Point::new(50.0, 20.0, 50.0),
+            Point::new(505.0, 20.0, 50.0),
+            Point::new(505.0, 20.0, 505.0),
+            Point::new(50.0, 20.0, 505.0),
+        ],
+        normals: None,
+        uvs: Some(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]),
+        colors: None,
+        indices: vec![0, 2, 1, 0, 3, 2],
+    };
+    let target = Tessellation::new(Point::new(278.0, 278.0, -800.0), 40.0, image_height, 4.0)
+        .with_max_level(6);
+    let rock = slab
+        .tessellate(&target)
+        .displace(&NoiseTexture::new(0.02, 256), 40.0, 0.5);
+    world.add(TriangleMesh::<_, f64>::from_data(&rock, m_rock).unwrap());
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// A tuft of hair fanning out from the floor, next to a few grass blades.
+fn curves() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_hair = Hair::new(SolidTexture::new(Color::new(0.45, 0.3, 0.15)), 0.3, 0.3);
+    let m_grass = Lambertian::new(SolidTexture::new(Color::new(0.12, 0.45, 0.15)));
+
+    // strands on a golden angle spiral, bending away from the middle
+    let root = Point::new(200.0, 0.0, 280.0);
+    let mut strands: Vec<Box<dyn Hittable>> = Vec::new();
+    for i in 0..300 {
+        let angle = i as f64 * 2.39996;
+        let spread = (i as f64 / 300.0).sqrt();
+        let out = Vector3::new(angle.cos(), 0.0, angle.sin());
+        let start = root + out * (20.0 * spread);
+        let control_points = [
+            start,
+            start + Vector3::new(0.0, 120.0, 0.0) + out * (20.0 * spread),
+            start + Vector3::new(0.0, 200.0, 0.0) + out * (80.0 * spread),
+            start + Vector3::new(0.0, 180.0, 0.0) + out * (160.0 * spread),
+        ];
+        let strand = Curve::new(control_points, [1.5, 0.3], CurveMode::Cylinder, m_hair);
+        for segment in strand.split(4) {
+            strands.push(Box::new(segment));
+        }
+    }
+    world.add(BVH::new(strands, 0.0, 1.0));
+
+    // blades facing the camera, twisting a quarter turn towards the tip
+    for i in 0..5 {
+        let start = Point::new(380.0 + 25.0 * i as f64, 0.0, 200.0);
+        let control_points = [
+            start,
+            start + Vector3::new(0.0, 80.0, 0.0),
+            start + Vector3::new(10.0, 160.0, 10.0),
+            start + Vector3::new(40.0, 220.0, 30.0),
+        ];
+        let normals = [Vector3::new(0.0, 0.0, -1.0), Vector3::new(-1.0, 0.0, 0.0)];
+        let blade = Curve::new(
+            control_points,
+            [20.0, 2.0],
+            CurveMode::Ribbon(normals),
+            m_grass,
+        );
+        world.add(blade);
+    }
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// A tumbling box and a ball squashing as it lands, blurred over the shutter.
+fn motion_blur() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_white = Lambertian::new(SolidTexture::new(Color::new(0.73, 0.73, 0.73)));
+    let m_blue = Lambertian::new(SolidTexture::new(Color::new(0.051, 0.459, 1.000)));
+
+    let axis = Vector3::new(1.0, 1.0, 0.0);
+    let tumbling = AnimatedTransform::new(
+        Cube::new(
+            Point::new(-60.0, -60.0, -60.0),
+            Point::new(60.0, 60.0, 60.0),
+            m_white,
+        ),
+        vec![
+            Keyframe::new(0.0).with_translation(Vector3::new(150.0, 250.0, 300.0)),
+            Keyframe::new(1.0)
+                .with_translation(Vector3::new(190.0, 220.0, 300.0))
+                .with_rotation(Quaternion::from_axis_angle(axis, 60.0)),
+        ],
+    )
+    .unwrap();
+    world.add(tumbling);
+
+    let landing = AnimatedTransform::new(
+        Sphere::new(Point::zero(), 1.0, m_blue),
+        vec![
+            Keyframe::new(0.0)
+                .with_translation(Vector3::new(400.0, 160.0, 200.0))
+                .with_scale(Vector3::new(70.0, 80.0, 70.0)),
+            Keyframe::new(0.6)
+                .with_translation(Vector3::new(400.0, 70.0, 200.0))
+                .with_scale(Vector3::new(70.0, 70.0, 70.0)),
+            Keyframe::new(1.0)
+                .with_translation(Vector3::new(400.0, 50.0, 200.0))
+                .with_scale(Vector3::new(90.0, 50.0, 90.0)),
+        ],
+    )
+    .unwrap();
+    world.add(landing);
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// A ball hidden from the camera that still shows in the mirror behind it.
+fn visibility() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_red = Lambertian::new(SolidTexture::new(Color::new(0.65, 0.05, 0.05)));
+    let m_mirror = Metallic::new(Color::new(0.9, 0.9, 0.9), 0.0);
+
+    world.add(AARect::new(
+        Plane::XY,
+        100.0,
+        455.0,
+        50.0,
+        405.0,
+        554.0,
+        m_mirror,
+    ));
+    world.add(Visible::new(
+        Sphere::new(Point::new(278.0, 100.0, 250.0), 100.0, m_red),
+        Visibility::ALL.without(Visibility::CAMERA),
+    ));
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// Clear coated paint and copper, and a ball blotched in white and gold.
+fn layered_materials() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let coat = RoughDielectric::new(1.5, 0.05);
+    let m_paint = Layered::new(
+        coat,
+        Lambertian::new(SolidTexture::new(Color::new(0.65, 0.05, 0.05))),
+    );
+    let m_coated_copper = Layered::new(coat, Conductor::copper(0.5));
+    let m_blotched = MixMaterial::new(
+        Lambertian::new(SolidTexture::new(Color::new(0.73, 0.73, 0.73))),
+        Conductor::gold(0.2),
+        NoiseTexture::new(0.05, 256),
+    );
+
+    world.add(Sphere::new(Point::new(130.0, 90.0, 250.0), 90.0, m_paint));
+    world.add(Sphere::new(
+        Point::new(278.0, 90.0, 400.0),
+        90.0,
+        m_coated_copper,
+    ));
+    world.add(Sphere::new(
+        Point::new(426.0, 90.0, 250.0),
+        90.0,
+        m_blotched,
+    ));
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// Rough stone and hammered metal: smooth spheres with noise for bumps.
+fn bump_mapping() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let stone = Lambertian::new(SolidTexture::new(Color::new(0.6, 0.55, 0.5)));
+    let m_stone = BumpMap::new(stone, NoiseTexture::new(0.1, 256), 8.0);
+    let m_hammered = BumpMap::new(Conductor::aluminium(0.1), NoiseTexture::new(0.04, 256), 6.0);
+
+    world.add(Sphere::new(Point::new(170.0, 110.0, 280.0), 110.0, m_stone));
+    world.add(Sphere::new(
+        Point::new(400.0, 110.0, 220.0),
+        110.0,
+        m_hammered,
+    ));
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// A panel eaten through by noise, with crisp holes, in front of a ball
+/// behind a veil that lets half the light through.
+fn alpha_mask() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    let m_panel = AlphaMask::new(
+        Lambertian::new(SolidTexture::new(Color::new(0.6, 0.35, 0.2))),
+        NoiseTexture::new(0.03, 256),
+    )
+    .with_mode(AlphaMode::Threshold(0.5));
+    let m_veil = AlphaMask::new(
+        Lambertian::new(SolidTexture::new(Color::new(0.9, 0.9, 0.9))),
+        SolidTexture::new(Color::one() * 0.5),
+    );
+    let m_blue = Lambertian::new(SolidTexture::new(Color::new(0.051, 0.459, 1.000)));
+
+    world.add(Quad::new(
+        Point::new(60.0, 0.0, 200.0),
+        Vector3::new(200.0, 0.0, -60.0),
+        Vector3::new(0.0, 350.0, 0.0),
+        m_panel,
+    ));
+    world.add(Sphere::new(Point::new(400.0, 90.0, 350.0), 90.0, m_blue));
+    world.add(Quad::new(
+        Point::new(290.0, 0.0, 200.0),
+        Vector3::new(220.0, 0.0, 0.0),
+        Vector3::new(0.0, 300.0, 0.0),
+        m_veil,
+    ));
+
+    (Box::new(world), Box::new(lights))
+}
+
+/// A candle of wax and a ball of skin, where red light goes furthest.
+fn subsurface() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    let mut world = HittableList::default();
+    let mut lights = HittableList::default();
+    cornell_walls(&mut world, &mut lights);
+
+    // the boundaries' own material is not used
+    let m_unused = Lambertian::new(SolidTexture::new(Color::one()));
+
+    let candle = Cylinder::new(Point::new(160.0, 0.0, 300.0), 60.0, 250.0, m_unused).capped(true);
+    world.add(Subsurface::new(
+        candle,
+        Color::new(0.9, 0.85, 0.7),
+        Color::one() * 15.0,
+    ));
+    let skin = Sphere::new(Point::new(390.0, 100.0, 220.0), 100.0, m_unused);
+    world.add(
+        Subsurface::new(skin, Color::new(0.85, 0.6, 0.5), Color::new(12.0, 5.0, 3.0))
+            .with_anisotropy(0.8),
+    );
+
+    (Box::new(world), Box::new(lights))
+}
 fn main() {
     // image settings
     const ASPECT_RATIO: f64 = 1.0;
@@ -230,6 +848,8 @@ fn main() {
     const SAMPLES_PER_PIXEL: u64 = 1000;
     const MAX_DEPTH: u64 = 100;
 
+    let pipeline = Pipeline::Rgb;
+
     // scene
     let scene = Scene::CornellBox;
     let (world, background, lights, camera) = match scene {
@@ -302,6 +922,112 @@ fn main() {
 
             (world, backgournd, lights, camera)
         }
+        Scene::Gltf => {
+            // lights and camera come from the file
+            let scene = GltfScene::load("scene.gltf", ASPECT_RATIO, 0.05).unwrap();
+            let camera = scene
+                .cameras
+                .into_iter()
+                .next()
+                .expect("scene.gltf has no camera");
+            let world: Box<dyn Hittable> = Box::new(scene.world);
+            let lights: Box<dyn Hittable> = Box::new(scene.lights);
+            let background = Color::new(0.7, 0.8, 1.0);
+
+            (world, background, lights, camera)
+        }
+        Scene::TriangleMesh => {
+            let (world, lights) = triangle_mesh();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::PlanarShapes => {
+            let (world, lights) = planar_shapes();
+            let background = Color::new(0.1, 0.1, 0.15);
+            let lookfrom = Point::new(0.0, 3.0, 12.0);
+            let lookat = Point::new(0.0, 1.0, 0.0);
+            let vup = Vector3::new(0.0, 1.0, 0.0);
+            let dist_to_focus = 10.0;
+            let aperture = 0.0;
+            let camera = Camera::new(
+                lookfrom,
+                lookat,
+                vup,
+                30.0,
+                ASPECT_RATIO,
+                aperture,
+                dist_to_focus,
+                0.0,
+                1.0,
+            );
+
+            (world, background, lights, camera)
+        }
+        Scene::Quadrics => {
+            let (world, lights) = quadrics();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::Csg => {
+            let (world, lights) = csg();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::Terrain => {
+            let (world, lights) = terrain();
+            let background = Color::new(0.7, 0.8, 1.0);
+            let lookfrom = Point::new(0.0, 8.0, 18.0);
+            let lookat = Point::new(0.0, 3.0, 0.0);
+            let vup = Vector3::new(0.0, 1.0, 0.0);
+            let dist_to_focus = 10.0;
+            let aperture = 0.0;
+            let camera = Camera::new(
+                lookfrom,
+                lookat,
+                vup,
+                35.0,
+                ASPECT_RATIO,
+                aperture,
+                dist_to_focus,
+                0.0,
+                1.0,
+            );
+
+            (world, background, lights, camera)
+        }
+        Scene::Subdivision => {
+            let (world, lights) = subdivision();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::Displacement => {
+            let (world, lights) = displacement(IMAGE_HEIGHT);
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::Curves => {
+            let (world, lights) = curves();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::MotionBlur => {
+            let (world, lights) = motion_blur();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::Visibility => {
+            let (world, lights) = visibility();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::LayeredMaterials => {
+            let (world, lights) = layered_materials();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::BumpMapping => {
+            let (world, lights) = bump_mapping();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::AlphaMask => {
+            let (world, lights) = alpha_mask();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
+        Scene::Subsurface => {
+            let (world, lights) = subsurface();
+            (world, Color::zero(), lights, cornell_camera(ASPECT_RATIO))
+        }
     };
 
     // create a new ImgBuf with
@@ -324,7 +1050,16 @@ fn main() {
 
                     let r = camera.get_ray(u, v);
 
-                    ray_color(&r, background, &world, &lights, MAX_DEPTH)
+                    match pipeline {
+                        Pipeline::Rgb => ray_color(&r, background, &world, &lights, MAX_DEPTH),
+                        Pipeline::Spectral => {
+                            // dispersive materials read the wavelengths off the ray
+                            let wavelengths = SampledWavelengths::sample_uniform(rng.gen::<f64>());
+                            let r = r.with_wavelengths(Some(wavelengths));
+                            ray_spectrum(&r, &wavelengths, background, world.as_ref(), MAX_DEPTH)
+                                .to_rgb(&wavelengths)
+                        }
+                    }
                 })
                 .sum();
             let pixel = imgbuf.get_pixel_mut(x, IMAGE_HEIGHT - 1 - y);
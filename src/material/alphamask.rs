@@ -0,0 +1,92 @@
+use crate::texture::ScalarTexture;
+
+use super::*;
+
+/// How `AlphaMask` turns opacity into holes.
+#[derive(Debug, Clone, Copy)]
+pub enum AlphaMode {
+    /// Kept with probability equal to the opacity, so partly opaque
+    /// texels come out as soft, noisy edges that converge with more samples.
+    Stochastic,
+    /// Kept where the opacity reaches the cutoff, for crisp edges.
+    Threshold(f64),
+}
+
+/// Cuts holes into any material where the `opacity` texture is below one,
+/// for leaves and fences made of textured quads. The opacity is read at the
+/// hit's (u, v), e.g. from the luminance of a black and white mask image.
+///
+/// `HittableList` and `BVH` skip cut out intersections and look further
+/// along the ray, so every ray, shadow rays included, passes the holes.
+#[derive(Debug, Clone, Copy)]
+pub struct AlphaMask<M: Material, T: ScalarTexture> {
+    inner: M,
+    opacity: T,
+    mode: AlphaMode,
+}
+
+impl<M: Material, T: ScalarTexture> AlphaMask<M, T> {
+    pub fn new(inner: M, opacity: T) -> AlphaMask<M, T> {
+        AlphaMask {
+            inner,
+            opacity,
+            mode: AlphaMode::Stochastic,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: AlphaMode) -> AlphaMask<M, T> {
+        self.mode = mode;
+        self
+    }
+}
+
+// the coin is hashed from the hit, so a hit tested again by each hittable it
+// is nested in is always kept or always skipped
+const ALPHA_SALT: u64 = 0x616c_7068_615f_6d61;
+
+impl<M: Material, T: ScalarTexture + Sync> Material for AlphaMask<M, T> {
+    fn is_cut_out(&self, r: &Ray, rec: &HitRecord) -> bool {
+        let opacity = self.opacity.value(rec.u, rec.v, &rec.p);
+        match self.mode {
+            AlphaMode::Stochastic => opacity <= hit_hash(ALPHA_SALT, r, rec),
+            AlphaMode::Threshold(cutoff) => opacity < cutoff,
+        }
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.inner.emitted(rec)
+    }
+
+    fn with_emitter_surface(mut self, surface: EmitterSurface) -> Self {
+        self.inner = self.inner.with_emitter_surface(surface);
+        self
+    }
+
+    fn needs_emitter_surface(&self) -> bool {
+        self.inner.needs_emitter_surface()
+    }
+
+    fn emitted_spectrum(
+        &self,
+        rec: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        self.inner.emitted_spectrum(rec, wavelengths)
+    }
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.inner.scatter(r_in, rec)
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        self.inner.scatter_mc_methode(r_in, rec)
+    }
+
+    fn brdf(&self, r_in: &Ray, r_out: &Ray, rec: &HitRecord) -> Vector3 {
+        self.inner.brdf(r_in, r_out, rec)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.inner.scattering_pdf(r_in, rec, scattered)
+    }
+}
@@ -0,0 +1,116 @@
+use crate::texture::ScalarTexture;
+
+use super::*;
+
+/// Perturbs the shading normal of `inner` as if the surface were raised by
+/// `height` times `strength` along its normal (Blinn's bump mapping). Any
+/// scalar texture works, e.g. the luminance of an `ImageTexture` looked up
+/// by (u, v) or a `NoiseTexture` looked up by position.
+///
+/// The slope is taken by finite differences over `delta` in (u, v), moving
+/// the lookup point along the hit's tangent and bitangent to match.
+#[derive(Debug, Clone, Copy)]
+pub struct BumpMap<M: Material, T: ScalarTexture> {
+    inner: M,
+    height: T,
+    strength: f64,
+    delta: f64,
+}
+
+impl<M: Material, T: ScalarTexture> BumpMap<M, T> {
+    pub fn new(inner: M, height: T, strength: f64) -> BumpMap<M, T> {
+        BumpMap {
+            inner,
+            height,
+            strength,
+            delta: 0.0005,
+        }
+    }
+
+    /// Step of the finite differences; around a texel of the height map is
+    /// right for images.
+    pub fn with_delta(mut self, delta: f64) -> BumpMap<M, T> {
+        self.delta = delta.max(1e-8);
+        self
+    }
+
+    fn shade<'a>(&self, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        // the surface is raised along the outward normal, the tangents don't
+        // flip with the hit normal on back faces
+        let outward = if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        };
+        // shapes without tangents still get bumps, just not aligned with (u, v)
+        let (dpdu, dpdv) = if rec.tangent.near_zero() || rec.bitangent.near_zero() {
+            let frame = ONB::build_from_w_u_v(&outward, &rec.tangent, &rec.bitangent);
+            (frame.u(), frame.v())
+        } else {
+            (rec.tangent, rec.bitangent)
+        };
+
+        let d = self.delta;
+        let h = self.height.value(rec.u, rec.v, &rec.p);
+        let h_u = self.height.value(rec.u + d, rec.v, &(rec.p + dpdu * d));
+        let h_v = self.height.value(rec.u, rec.v + d, &(rec.p + dpdv * d));
+
+        // derivatives of the displaced point, ignoring the change of the normal
+        let dpdu = dpdu + outward * ((h_u - h) / d * self.strength);
+        let dpdv = dpdv + outward * ((h_v - h) / d * self.strength);
+        let normal = dpdu.cross(dpdv).normalize();
+        let normal = if normal.dot(outward) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        if !normal.x().is_finite() {
+            return rec.clone();
+        }
+        let normal = if rec.front_face { normal } else { -normal };
+        HitRecord { normal, ..*rec }
+    }
+}
+
+impl<M: Material, T: ScalarTexture + Sync> Material for BumpMap<M, T> {
+    fn is_cut_out(&self, r: &Ray, rec: &HitRecord) -> bool {
+        self.inner.is_cut_out(r, rec)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.inner.emitted(rec)
+    }
+
+    fn with_emitter_surface(mut self, surface: EmitterSurface) -> Self {
+        self.inner = self.inner.with_emitter_surface(surface);
+        self
+    }
+
+    fn needs_emitter_surface(&self) -> bool {
+        self.inner.needs_emitter_surface()
+    }
+
+    fn emitted_spectrum(
+        &self,
+        rec: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        self.inner.emitted_spectrum(rec, wavelengths)
+    }
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.inner.scatter(r_in, &self.shade(rec))
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        self.inner.scatter_mc_methode(r_in, &self.shade(rec))
+    }
+
+    fn brdf(&self, r_in: &Ray, r_out: &Ray, rec: &HitRecord) -> Vector3 {
+        self.inner.brdf(r_in, r_out, &self.shade(rec))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.inner.scattering_pdf(r_in, &self.shade(rec), scattered)
+    }
+}
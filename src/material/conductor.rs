@@ -0,0 +1,109 @@
+use rand::Rng;
+
+use super::*;
+
+/// Rough metal with a GGX microfacet surface. The color comes from the
+/// complex index of refraction `eta + i k` per RGB channel, the blur from
+/// `roughness` in [0, 1]; 0 is a mirror.
+///
+/// Reflections are sampled from the visible normals, so no sample is wasted
+/// on facets facing away from the viewer. Light that would bounce between
+/// the facets more than once is added back as in Turquin, "Practical
+/// multiple scattering compensation for microfacet models", which keeps a
+/// white rough metal from going dark.
+#[derive(Debug, Clone, Copy)]
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    roughness: f64,
+    anisotropic: f64,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            roughness: roughness.clamp(0.0, 1.0),
+            anisotropic: 0.0,
+        }
+    }
+
+    pub fn gold(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::new(0.143119, 0.374957, 1.44248),
+            Color::new(3.98316, 2.38572, 1.60322),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::new(0.155265, 0.116723, 0.138342),
+            Color::new(4.82835, 3.12225, 2.14696),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::new(0.200438, 0.924033, 1.10221),
+            Color::new(3.91295, 2.45285, 2.14219),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: f64) -> Conductor {
+        Conductor::new(
+            Color::new(1.65746, 0.880369, 0.521229),
+            Color::new(9.22387, 6.26952, 4.837),
+            roughness,
+        )
+    }
+
+    /// Stretches the highlight along the hit's tangent, as on brushed metal.
+    pub fn with_anisotropic(mut self, anisotropic: f64) -> Conductor {
+        self.anisotropic = anisotropic.clamp(0.0, 1.0);
+        self
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Vector3)> {
+        let uvw = ONB::build_from_w_u(&rec.normal, &rec.tangent);
+        let wo = uvw.to_local(&-r_in.dir().normalize());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let ggx = Ggx::anisotropic(self.roughness, self.anisotropic);
+        let mut rng = rand::thread_rng();
+        let h = ggx.sample_visible_normal(wo, rng.gen::<f64>(), rng.gen::<f64>());
+        let wi = (-wo).reflect(h);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // f cos / pdf of the single bounce, then the missing bounces scaled by F0
+        let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+        let f0 = fresnel_conductor(1.0, self.eta, self.k);
+        let albedo = ggx.albedo(wo.z());
+        let compensation = Color::one() + f0 * (1.0 / albedo - 1.0);
+        let attenuation = fresnel * compensation * (ggx.g2(wo, wi) / ggx.g1(wo));
+
+        Some((attenuation, uvw.local(&wi)))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let (attenuation, direction) = self.sample(r_in, rec)?;
+        Some((attenuation, Ray::new(rec.p, direction, r_in.time())))
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        let (attenuation, direction) = self.sample(r_in, rec)?;
+        Some(ScatterRecord::Specular {
+            specular_ray: Ray::new(rec.p, direction, r_in.time()),
+            attenuation,
+        })
+    }
+}
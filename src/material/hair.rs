@@ -0,0 +1,201 @@
+use rand::Rng;
+
+use super::*;
+
+// Hair scattering after Chiang et al., "A Practical and Controllable Hair and
+// Fur Model for Production Path Tracing" (as in pbrt-v3). The fiber is a
+// dielectric cylinder: light reflects off the surface (R), passes through
+// (TT), reflects once inside (TRT) or bounces around longer, and each of those
+// lobes is sampled in longitudinal and azimuthal angle around the fiber.
+
+const HAIR_LOBES: usize = 4;
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b()
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+/// Sample of a logistic distribution with scale `s` restricted to [a, b].
+fn sample_trimmed_logistic(u: f64, s: f64, a: f64, b: f64) -> f64 {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+    x.clamp(a, b)
+}
+
+/// Fiber material for `Curve`s. `color` is the color of a single scattering
+/// event, converted into the absorption inside the fiber; `beta_m` and
+/// `beta_n` in (0, 1] are the longitudinal and azimuthal roughness.
+///
+/// The frame is built from the hit's tangent and v, which runs across the
+/// fiber, so it is meant for `CurveMode::Flat` and `CurveMode::Cylinder`.
+#[derive(Debug, Clone, Copy)]
+pub struct Hair<T: Texture> {
+    color: T,
+    beta_m: f64,
+    beta_n: f64,
+    alpha: f64,
+    eta: f64,
+}
+
+impl<T: Texture> Hair<T> {
+    pub fn new(color: T, beta_m: f64, beta_n: f64) -> Hair<T> {
+        Hair {
+            color,
+            beta_m: beta_m.clamp(0.01, 1.0),
+            beta_n: beta_n.clamp(0.01, 1.0),
+            alpha: degress_to_radians(2.0),
+            eta: 1.55,
+        }
+    }
+
+    /// Tilt of the cuticle scales, which shifts the lobes along the fiber.
+    pub fn with_alpha(mut self, degrees: f64) -> Hair<T> {
+        self.alpha = degress_to_radians(degrees);
+        self
+    }
+
+    pub fn with_eta(mut self, eta: f64) -> Hair<T> {
+        self.eta = eta;
+        self
+    }
+
+    fn sigma_a(&self, color: Color) -> Color {
+        let b = self.beta_n;
+        let d = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3)
+            + 5.574 * b.powi(4)
+            + 0.245 * b.powi(5);
+        let mut sigma_a = Color::zero();
+        for i in 0..3 {
+            sigma_a[i] = (color[i].clamp(1e-4, 1.0).ln() / d).powi(2);
+        }
+        sigma_a
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> (Color, Vector3) {
+        let mut rng = rand::thread_rng();
+        let wo = -r_in.dir().normalize();
+
+        // x along the fiber, y across it in the direction v grows, z towards the viewer
+        let x = if rec.tangent.near_zero() {
+            ONB::build_from_w(&rec.normal).u()
+        } else {
+            rec.tangent.normalize()
+        };
+        let y = (-wo).cross(x);
+        let y = if y.near_zero() {
+            ONB::build_from_w(&x).u()
+        } else {
+            y.normalize()
+        };
+        let z = y.cross(x);
+
+        let sin_theta_o = wo.dot(x).clamp(-1.0, 1.0);
+        let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+        let phi_o = wo.dot(z).atan2(wo.dot(y));
+        let h = (2.0 * rec.v - 1.0).clamp(-1.0, 1.0);
+        let gamma_o = h.asin();
+
+        // refraction into the fiber, projected onto the cross section
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(0.0).sqrt();
+        let eta_p =
+            (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o.max(1e-6);
+        let sin_gamma_t = (h / eta_p).clamp(-1.0, 1.0);
+        let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).max(0.0).sqrt();
+        let gamma_t = sin_gamma_t.asin();
+
+        // attenuation of each lobe
+        let sigma_a = self.sigma_a(self.color.get_hit_color(rec));
+        let mut transmittance = Color::zero();
+        for i in 0..3 {
+            transmittance[i] = (-sigma_a[i] * 2.0 * cos_gamma_t / cos_theta_t.max(1e-6)).exp();
+        }
+        let f = fresnel_dielectric(cos_theta_o * gamma_o.cos(), self.eta);
+        let mut ap = [Color::zero(); HAIR_LOBES];
+        ap[0] = Color::one() * f;
+        ap[1] = (1.0 - f).powi(2) * transmittance;
+        ap[2] = ap[1] * transmittance * f;
+        for i in 0..3 {
+            let tf = transmittance[i] * f;
+            ap[3][i] = if tf < 1.0 {
+                ap[2][i] * tf / (1.0 - tf)
+            } else {
+                0.0
+            };
+        }
+
+        let weights = ap.map(luminance);
+        let total: f64 = weights.iter().sum();
+        let mut pick = rng.gen::<f64>() * total;
+        let mut p = HAIR_LOBES - 1;
+        for (lobe, &weight) in weights.iter().enumerate() {
+            if pick < weight {
+                p = lobe;
+                break;
+            }
+            pick -= weight;
+        }
+
+        // the cuticle tilt rotates the lobes by -2 alpha, alpha and 4 alpha
+        let tilt = match p {
+            0 => -2.0 * self.alpha,
+            1 => self.alpha,
+            2 => 4.0 * self.alpha,
+            _ => 0.0,
+        };
+        let (sin_tilt, cos_tilt) = tilt.sin_cos();
+        let sin_theta_op = sin_theta_o * cos_tilt + cos_theta_o * sin_tilt;
+        let cos_theta_op = (cos_theta_o * cos_tilt - sin_theta_o * sin_tilt).abs();
+
+        // longitudinal variance, wider for the lobes that travel through the fiber
+        let v0 = (0.726 * self.beta_m + 0.812 * self.beta_m.powi(2) + 3.7 * self.beta_m.powi(20))
+            .powi(2);
+        let v = match p {
+            0 => v0,
+            1 => 0.25 * v0,
+            _ => 4.0 * v0,
+        };
+        let u = rng.gen::<f64>().max(1e-5);
+        let cos_theta = 1.0 + v * (u + (1.0 - u) * (-2.0 / v).exp()).ln();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let cos_phi = (2.0 * PI * rng.gen::<f64>()).cos();
+        let sin_theta_i =
+            (-cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op).clamp(-1.0, 1.0);
+        let cos_theta_i = (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt();
+
+        // azimuthal scale
+        let s = (PI / 8.0).sqrt()
+            * (0.265 * self.beta_n + 1.194 * self.beta_n.powi(2) + 5.372 * self.beta_n.powi(22));
+        let dphi = if p < 3 {
+            let p = p as f64;
+            2.0 * p * gamma_t - 2.0 * gamma_o
+                + p * PI
+                + sample_trimmed_logistic(rng.gen::<f64>(), s, -PI, PI)
+        } else {
+            2.0 * PI * rng.gen::<f64>()
+        };
+        let phi_i = phi_o + dphi;
+
+        let wi = sin_theta_i * x + cos_theta_i * phi_i.cos() * y + cos_theta_i * phi_i.sin() * z;
+        let attenuation = ap.iter().fold(Color::zero(), |sum, &a| sum + a);
+        (attenuation, wi)
+    }
+}
+
+impl<T: Texture + Sync> Material for Hair<T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let (attenuation, direction) = self.sample(r_in, rec);
+        Some((attenuation, Ray::new(rec.p, direction, r_in.time())))
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        let (attenuation, direction) = self.sample(r_in, rec);
+        Some(ScatterRecord::Specular {
+            specular_ray: Ray::new(rec.p, direction, r_in.time()),
+            attenuation,
+        })
+    }
+}
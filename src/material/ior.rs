@@ -0,0 +1,83 @@
+use rand::Rng;
+
+use super::*;
+
+/// Index of refraction of a dielectric, which may vary with wavelength.
+/// Wavelengths in the formulas are in micrometers.
+#[derive(Debug, Clone, Copy)]
+pub enum Ior {
+    Constant(f64),
+    /// n = a + b / λ²
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    /// n² = 1 + Σ b λ² / (λ² - c)
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl Ior {
+    /// Borosilicate crown glass, the common optical glass.
+    pub fn bk7() -> Ior {
+        Ior::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    pub fn fused_silica() -> Ior {
+        Ior::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.00467914826, 0.0135120631, 97.9340025],
+        }
+    }
+
+    pub fn diamond() -> Ior {
+        Ior::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.0306, 0.01124, 0.0],
+        }
+    }
+
+    /// Index at `lambda` nanometers.
+    pub fn at(&self, lambda: f64) -> f64 {
+        let l2 = (lambda * 0.001).powi(2);
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>()).sqrt()
+            }
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+
+    /// Index to refract `r_in` with and the wavelengths to trace on with.
+    ///
+    /// A dispersive index splits the wavelengths of the path, so only its hero
+    /// wavelength goes on; the integrator weights the path for that when it
+    /// sees the secondary wavelengths terminated. A ray without wavelengths
+    /// gets a freshly sampled set.
+    pub fn for_ray(&self, r_in: &Ray) -> (f64, Option<SampledWavelengths>) {
+        if !self.is_dispersive() {
+            return (self.at(550.0), None);
+        }
+        let mut wavelengths = r_in
+            .wavelengths()
+            .unwrap_or_else(|| SampledWavelengths::sample_uniform(rand::thread_rng().gen()));
+        wavelengths.terminate_secondary();
+        (self.at(wavelengths.hero()), Some(wavelengths))
+    }
+}
+
+impl From<f64> for Ior {
+    fn from(n: f64) -> Ior {
+        Ior::Constant(n)
+    }
+}
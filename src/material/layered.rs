@@ -0,0 +1,106 @@
+use super::*;
+
+// bounces between the coat and the base before the path is given up
+const MAX_LAYER_BOUNCES: usize = 16;
+
+/// A dielectric `coat` over any `base` material, like varnish on wood or the
+/// clear coat of car paint. The coat's absorption tints the light on its way
+/// to the base and back, over the coat's absorption distance as thickness;
+/// the coat must not be thin-walled.
+///
+/// The layers are evaluated stochastically: light reflects off the coat or
+/// enters it, then walks between the base and the underside of the coat,
+/// each interface sampled as on its own, until it leaves the coat again.
+/// Emission of the base shines through the coat unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct Layered<B: Material> {
+    coat: RoughDielectric,
+    base: B,
+}
+
+impl<B: Material> Layered<B> {
+    pub fn new(coat: RoughDielectric, base: B) -> Layered<B> {
+        Layered { coat, base }
+    }
+
+    // length of a straight crossing of the coat in `direction`
+    fn crossing(&self, direction: Vector3, normal: Vector3) -> f64 {
+        let cos = direction.normalize().dot(normal).abs().max(1e-6);
+        self.coat.thickness() / cos
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let (mut attenuation, ray) = self.coat.scatter(r_in, rec)?;
+        let mut ray = ray.inherit_wavelengths(r_in);
+        if ray.dir().dot(rec.normal) > 0.0 {
+            return Some((attenuation, ray));
+        }
+
+        for _ in 0..MAX_LAYER_BOUNCES {
+            let (base_attenuation, up) = self.base.scatter(&ray, rec)?;
+            let up = up.inherit_wavelengths(&ray);
+            attenuation *= base_attenuation;
+            if up.dir().dot(rec.normal) <= 0.0 {
+                // a transmissive base lets the light through the whole stack
+                return Some((attenuation, up));
+            }
+
+            // seen from inside the coat, with the distance travelled through it
+            // down and back up to be absorbed over
+            let distance =
+                self.crossing(ray.dir(), rec.normal) + self.crossing(up.dir(), rec.normal);
+            let underside = HitRecord {
+                normal: -rec.normal,
+                front_face: false,
+                t: distance / up.dir().length(),
+                ..*rec
+            };
+            let (coat_attenuation, next) = self.coat.scatter(&up, &underside)?;
+            attenuation *= coat_attenuation;
+            ray = next.inherit_wavelengths(&up);
+            if ray.dir().dot(rec.normal) > 0.0 {
+                return Some((attenuation, ray));
+            }
+        }
+        None
+    }
+}
+
+impl<B: Material> Material for Layered<B> {
+    fn is_cut_out(&self, r: &Ray, rec: &HitRecord) -> bool {
+        self.base.is_cut_out(r, rec)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.base.emitted(rec)
+    }
+
+    fn with_emitter_surface(mut self, surface: EmitterSurface) -> Self {
+        self.base = self.base.with_emitter_surface(surface);
+        self
+    }
+
+    fn needs_emitter_surface(&self) -> bool {
+        self.base.needs_emitter_surface()
+    }
+
+    fn emitted_spectrum(
+        &self,
+        rec: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        self.base.emitted_spectrum(rec, wavelengths)
+    }
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.sample(r_in, rec)
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        let (attenuation, specular_ray) = self.sample(r_in, rec)?;
+        Some(ScatterRecord::Specular {
+            specular_ray,
+            attenuation,
+        })
+    }
+}
@@ -11,29 +11,31 @@ pub fn schlick_fresnel(x: f64) -> f64 {
     y_2 * y_2 * y
 }
 
-pub fn smith_ggx_anisotropic(cos_theta: f64, cos_phi: f64, sin_phi: f64, ax: f64, ay: f64) -> f64 {
-    let tan_theta_2 = (1.0 - cos_theta.powi(2)) / cos_theta.powi(2).max(0.001);
-    let lambda_s =
-        -0.5 + 0.5 * (1.0 + (ax * cos_phi).powi(2) + (ay * sin_phi).powi(2) * tan_theta_2).sqrt();
-    1.0 / (1.0 + lambda_s)
+// The microfacet terms take a direction's components in the shading frame:
+// along the normal (cos_theta) and along the tangent and bitangent (x, y).
+
+/// Smith masking G1 of the anisotropic GGX distribution.
+pub fn smith_ggx_anisotropic(cos_theta: f64, x: f64, y: f64, ax: f64, ay: f64) -> f64 {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let tan_theta_2 = ((ax * x).powi(2) + (ay * y).powi(2)) / cos_theta.powi(2);
+    let lambda = -0.5 + 0.5 * (1.0 + tan_theta_2).sqrt();
+    1.0 / (1.0 + lambda)
 }
 
 pub fn smith_ggx(cos_theta: f64, a: f64) -> f64 {
-    smith_ggx_anisotropic(cos_theta, 1.0, 0.0, a, a)
+    let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+    smith_ggx_anisotropic(cos_theta, sin_theta, 0.0, a, a)
 }
 
-pub fn gtr_2_anisotropic(cos_theta: f64, cos_phi: f64, sin_phi: f64, ax: f64, ay: f64) -> f64 {
-    1.0 / (PI
-        * ax
-        * ay
-        * (1.0 - cos_theta.powi(2))
-        * ((cos_phi / ax).powi(2) + (sin_phi / ay).powi(2))
-        + cos_theta.powi(2))
-    .powi(2)
+pub fn gtr_2_anisotropic(cos_theta: f64, x: f64, y: f64, ax: f64, ay: f64) -> f64 {
+    1.0 / (PI * ax * ay * ((x / ax).powi(2) + (y / ay).powi(2) + cos_theta.powi(2)).powi(2))
 }
 
 pub fn gtr_1(n_dot_h: f64, a: f64) -> f64 {
-    (a.powi(2) - 1.0) / 2.0 * PI * a.ln() * ((a * n_dot_h).powi(2) + (1.0 - n_dot_h * n_dot_h))
+    let a2 = a * a;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * n_dot_h * n_dot_h))
 }
 
 pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
@@ -60,12 +62,12 @@ impl<T: Texture + Sync> Material for Lambertian<T> {
 
         let scatted = Ray::new(rec.p, scatter_direction, r_in.time());
 
-        Some((self.albedo.get_color(rec.u, rec.v, &rec.p), scatted))
+        Some((self.albedo.get_hit_color(rec), scatted))
     }
     fn scatter_mc_methode(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let rec = ScatterRecord::Scatter {
             pdf: PDF::cosine_pdf(rec.normal),
-            attenuation: self.albedo.get_color(rec.u, rec.v, &rec.p),
+            attenuation: self.albedo.get_hit_color(rec),
         };
         Some(rec)
     }
@@ -122,12 +124,12 @@ impl Material for Metallic {
 
 #[derive(Debug, Clone)]
 pub struct Dielectric {
-    pub ir: f64,
+    pub ir: Ior,
 }
 
 impl Dielectric {
-    pub fn new(ir: f64) -> Dielectric {
-        Dielectric { ir }
+    pub fn new(ir: impl Into<Ior>) -> Dielectric {
+        Dielectric { ir: ir.into() }
     }
 }
 
@@ -140,12 +142,8 @@ fn reflectance(cosine: f64, ref_index: f64) -> f64 {
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
         let mut rng = rand::thread_rng();
-        // let attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+        let (ir, wavelengths) = self.ir.for_ray(r_in);
+        let refraction_ratio = if rec.front_face { 1.0 / ir } else { ir };
 
         let unit_direction = r_in.dir().normalize();
         let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
@@ -159,18 +157,15 @@ impl Material for Dielectric {
                 unit_direction.refract(rec.normal, refraction_ratio)
             };
 
-        let scattered = Ray::new(rec.p, direction, r_in.time());
-        Some((Color::new(1.0, 1.0, 1.0), scattered))
+        let scattered = Ray::new(rec.p, direction, r_in.time()).with_wavelengths(wavelengths);
+        Some((Color::one(), scattered))
     }
 
     fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let mut rng = rand::thread_rng();
         let attenuation = Color::one();
-        let refraction_ratio = if rec.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
-        };
+        let (ir, wavelengths) = self.ir.for_ray(r_in);
+        let refraction_ratio = if rec.front_face { 1.0 / ir } else { ir };
         let unit_direction = r_in.dir().normalize();
         let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
@@ -183,7 +178,7 @@ impl Material for Dielectric {
                 unit_direction.refract(rec.normal, refraction_ratio)
             };
 
-        let scattered = Ray::new(rec.p, direction, r_in.time());
+        let scattered = Ray::new(rec.p, direction, r_in.time()).with_wavelengths(wavelengths);
         let f_rec = ScatterRecord::Specular {
             specular_ray: scattered,
             attenuation,
@@ -192,14 +187,130 @@ impl Material for Dielectric {
     }
 }
 
+/// Brightness of an emitter in physical units, taking a scene radiance of 1
+/// as one nit and scene lengths as meters. Both are for where the emission
+/// texture is white.
+#[derive(Debug, Clone, Copy)]
+pub enum Intensity {
+    /// Luminance in nits (candela per square meter).
+    Nits(f64),
+    /// Radiant power in the visible range leaving the whole surface of the
+    /// shape the light is put on. Shapes of unknown area, such as signed
+    /// distance fields, refuse it.
+    Watts(f64),
+}
+
+/// Surface of a shape that a power is spread over, see
+/// `Material::with_emitter_surface`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmitterSurface {
+    pub area: f64,
+    /// Whether the surface encloses a solid, so that nothing its inside emits
+    /// gets out.
+    pub closed: bool,
+    /// Whether it is the whole surface of a shape built from parts, which wins
+    /// over the surfaces the parts hand over themselves.
+    pub shared: bool,
+}
+
+impl EmitterSurface {
+    pub fn open(area: f64) -> EmitterSurface {
+        EmitterSurface {
+            area,
+            closed: false,
+            shared: false,
+        }
+    }
+
+    pub fn closed(area: f64) -> EmitterSurface {
+        EmitterSurface {
+            closed: true,
+            ..EmitterSurface::open(area)
+        }
+    }
+
+    /// Marks the surface as the whole of a shape built from parts, for the
+    /// shape to hand over before building them.
+    pub fn shared(mut self) -> EmitterSurface {
+        self.shared = true;
+        self
+    }
+}
+
+/// Lambertian emitter, by default from both sides of the surface.
+/// Without an intensity the texture color is the emitted radiance.
 #[derive(Debug, Clone)]
 pub struct DiffuseLight<T: Texture> {
     albedo: T,
+    spectrum: Option<LightSpectrum>,
+    intensity: Option<Intensity>,
+    two_sided: bool,
+    surface: Option<EmitterSurface>,
+    scale: f64,
 }
 
 impl<T: Texture> DiffuseLight<T> {
     pub fn new(albedo: T) -> DiffuseLight<T> {
-        DiffuseLight { albedo }
+        DiffuseLight {
+            albedo,
+            spectrum: None,
+            intensity: None,
+            two_sided: true,
+            surface: None,
+            scale: 1.0,
+        }
+    }
+
+    /// Emits `spectrum` at unit luminance, scaled and tinted by the texture.
+    pub fn with_spectrum(mut self, spectrum: Spectrum) -> DiffuseLight<T> {
+        self.spectrum = Some(LightSpectrum::new(spectrum));
+        self.rescale()
+    }
+
+    /// Color of a blackbody at `kelvin`, e.g. 2700 for a warm bulb or 6500
+    /// for daylight.
+    pub fn with_temperature(self, kelvin: f64) -> DiffuseLight<T> {
+        self.with_spectrum(Spectrum::Blackbody(kelvin))
+    }
+
+    pub fn with_intensity(mut self, intensity: Intensity) -> DiffuseLight<T> {
+        self.intensity = Some(intensity);
+        self.rescale()
+    }
+
+    /// Whether the back of the surface emits as well; `false` leaves only the
+    /// side the geometric normal points to. A given power is shared between
+    /// the sides that emit, except on closed shapes where only the outside
+    /// lights the scene.
+    pub fn with_two_sided(mut self, two_sided: bool) -> DiffuseLight<T> {
+        self.two_sided = two_sided;
+        self.rescale()
+    }
+
+    // the spectrum, sides and surface change what a power means, so the scale
+    // is worked out again whenever one of them is set
+    fn rescale(mut self) -> DiffuseLight<T> {
+        self.scale = match self.intensity {
+            None => 1.0,
+            Some(Intensity::Nits(nits)) => nits,
+            Some(Intensity::Watts(watts)) => {
+                // a light not put on a shape yet counts as one square meter
+                let surface = self.surface.unwrap_or(EmitterSurface::open(1.0));
+                // the RGB pipeline's white is D65
+                let efficacy = self
+                    .spectrum
+                    .map_or(Spectrum::D65, |s| s.spectrum())
+                    .luminous_efficacy();
+                let sides = if self.two_sided && !surface.closed {
+                    2.0
+                } else {
+                    1.0
+                };
+                // a Lambertian surface sends out pi times its radiance per area
+                watts * efficacy / (PI * surface.area.max(1e-12) * sides)
+            }
+        };
+        self
     }
 }
 
@@ -207,8 +318,41 @@ impl<T: Texture + Sync> Material for DiffuseLight<T> {
     fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
         None
     }
+    fn with_emitter_surface(mut self, surface: EmitterSurface) -> Self {
+        if self.surface.map_or(false, |s| s.shared) && !surface.shared {
+            return self;
+        }
+        self.surface = Some(surface);
+        self.rescale()
+    }
+    fn needs_emitter_surface(&self) -> bool {
+        matches!(self.intensity, Some(Intensity::Watts(_)))
+    }
     fn emitted(&self, rec: &HitRecord) -> Color {
-        self.albedo.get_color(rec.u, rec.v, &rec.p)
+        if !self.two_sided && !rec.front_face {
+            return Color::zero();
+        }
+        let color = self.albedo.get_hit_color(rec) * self.scale;
+        match &self.spectrum {
+            Some(spectrum) => color * spectrum.rgb(),
+            None => color,
+        }
+    }
+    fn emitted_spectrum(
+        &self,
+        rec: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        if !self.two_sided && !rec.front_face {
+            return SampledSpectrum::zero();
+        }
+        let color = self.albedo.get_hit_color(rec) * self.scale;
+        match &self.spectrum {
+            Some(spectrum) => {
+                SampledSpectrum::from_rgb(color, wavelengths) * spectrum.sample(wavelengths)
+            }
+            None => SampledSpectrum::from_rgb_illuminant(color, wavelengths),
+        }
     }
 }
 
@@ -226,18 +370,18 @@ impl<T: Texture> Isotropic<T> {
 impl<T: Texture + Sync> Material for Isotropic<T> {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
         let scattered = Ray::new(rec.p, Vector3::random_in_unit_sphere(), r_in.time());
-        Some((self.albedo.get_color(rec.u, rec.v, &rec.p), scattered))
+        Some((self.albedo.get_hit_color(rec), scattered))
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct PBR<T: Texture> {
     base_color: T,        // 基本颜色
-    metallic: f64,        // 漫反射和次表面散射的lerp因子
-    subsurface: f64,      // 金属度
+    metallic: f64,        // 金属度
+    subsurface: f64,      // 漫反射和次表面散射的lerp因子
     specular: f64,        // 高光度
-    roughness: f64,       // 高光色向基本色靠拢的程度
-    specular_tint: f64,   // 粗糙度
+    roughness: f64,       // 粗糙度
+    specular_tint: f64,   // 高光色向基本色靠拢的程度
     anisotropic: f64,     // 各向异性
     sheen: f64,           // 光泽度，纺织物边缘明亮程度
     sheen_tint: f64,      // sheen向基本色靠拢程度
@@ -275,7 +419,34 @@ impl<T: Texture> PBR<T> {
     }
 }
 
+impl<T: Texture> PBR<T> {
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord) -> PDF<'static> {
+        PDF::brdf_pdf(
+            rec.normal,
+            r_in.dir(),
+            self.roughness,
+            self.anisotropic,
+            self.clearcoat,
+            self.clearcoat_gloss,
+        )
+    }
+}
+
 impl<T: Texture + Sync> Material for PBR<T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        // importance sample the lobes, weighting by brdf * cos / pdf
+        let pdf = self.pdf(r_in, rec);
+        let direction = pdf.generate();
+        let cosine = direction.normalize().dot(rec.normal);
+        let pdf_value = pdf.value(direction);
+        if cosine <= 0.0 || pdf_value <= 0.0 {
+            return None;
+        }
+        let scattered = Ray::new(rec.p, direction, r_in.time());
+        let attenuation = self.brdf(r_in, &scattered, rec) * cosine / pdf_value;
+        Some((attenuation, scattered))
+    }
+
     fn brdf(&self, r_in: &Ray, r_out: &Ray, rec: &HitRecord) -> Vector3 {
         let l = -r_in.dir().normalize();
         let v = r_out.dir().normalize();
@@ -288,7 +459,7 @@ impl<T: Texture + Sync> Material for PBR<T> {
         let n_dot_v = n.dot(v); // theta_o
         let n_dot_l = n.dot(l); // theta_i
 
-        if n_dot_l < 0.0 || n_dot_v < 0.0 {
+        if n_dot_l <= 0.0 || n_dot_v <= 0.0 {
             return Vector3::default();
         }
 
@@ -297,12 +468,11 @@ impl<T: Texture + Sync> Material for PBR<T> {
         let l_dot_h = l.dot(h);
 
         // color
-        let base_color = self.base_color.get_color(rec.u, rec.v, &rec.p);
-        let linear_color = mon_to_linear(base_color);
+        let base_color = self.base_color.get_hit_color(rec);
         let luminance_color =
-            0.2126 * linear_color.x() + 0.7152 * linear_color.y() + 0.0722 * linear_color.z();
+            0.2126 * base_color.x() + 0.7152 * base_color.y() + 0.0722 * base_color.z();
         let c_tint = if luminance_color > 0.0 {
-            linear_color / luminance_color
+            base_color / luminance_color
         } else {
             Vector3::one()
         };
@@ -315,24 +485,25 @@ impl<T: Texture + Sync> Material for PBR<T> {
         // fresnel
         let fresnel_l = schlick_fresnel(n_dot_l);
         let fresnel_v = schlick_fresnel(n_dot_v);
-        let fresnel_h = schlick_fresnel(n_dot_h);
+        let fresnel_h = schlick_fresnel(l_dot_h);
 
-        // diffuse
-        let fresnel_90 = 0.5 + 2.0 * l_dot_h.powi(2) * self.roughness;
-        let fresnel_diffuse = self.base_color.get_color(rec.u, rec.v, &rec.p) / PI
-            * (1.0 + (fresnel_90 - 1.0) * fresnel_l)
-            * (1.0 + (fresnel_90 - 1.0) * fresnel_v);
+        // diffuse, renormalized as in Lagarde and de Rousiers, "Moving Frostbite
+        // to PBR", so the retro-reflection of rough surfaces adds no energy
+        let energy_bias = lerp(0.0, 0.5, self.roughness);
+        let energy_factor = lerp(1.0, 1.0 / 1.51, self.roughness);
+        let fresnel_90 = energy_bias + 2.0 * l_dot_h.powi(2) * self.roughness;
+        let fresnel_diffuse = (1.0 + (fresnel_90 - 1.0) * fresnel_l)
+            * (1.0 + (fresnel_90 - 1.0) * fresnel_v)
+            * energy_factor;
 
         // bssdf
         let fresnel_90 = l_dot_h.powi(2) * self.roughness;
         let fresnel_ss =
             (1.0 + (fresnel_90 - 1.0) * fresnel_l) * (1.0 + (fresnel_90 - 1.0) * fresnel_v);
-        let fresnel_subsurface = 1.25
-            * self.base_color.get_color(rec.u, rec.v, &rec.p)
-            * (fresnel_ss * (1.0 / (n_dot_v + n_dot_l) - 0.5) + 0.5);
+        let fresnel_subsurface = 1.25 * (fresnel_ss * (1.0 / (n_dot_v + n_dot_l) - 0.5) + 0.5);
 
         // specular
-        let c_st = Vector3::one().lerp(c_tint, self.sheen_tint);
+        let c_st = Vector3::one().lerp(c_tint, self.specular_tint);
         let c_specular = (0.08 * self.specular * c_st).lerp(base_color, self.metallic);
         let specular_f = c_specular + (Vector3::one() - c_specular) * fresnel_h;
         let specular_g = smith_ggx_anisotropic(n_dot_l, l.dot(x), l.dot(y), ax, ay)
@@ -344,29 +515,27 @@ impl<T: Texture + Sync> Material for PBR<T> {
         let fresnel_sheen = c_sht * self.sheen * fresnel_h;
 
         // clearcoat
-        let clearcoat_f = lerp(fresnel_h, 1.0, 0.04);
+        let clearcoat_f = lerp(0.04, 1.0, fresnel_h);
         let clearcoat_g = smith_ggx(n_dot_l, 0.25) * smith_ggx(n_dot_v, 0.25);
-        let clearcoat_d = gtr_1(n_dot_h, lerp(0.1, 0.01, self.clearcoat_gloss));
+        let clearcoat_d = gtr_1(n_dot_h, lerp(0.1, 0.001, self.clearcoat_gloss));
+
+        // light reflected by the dielectric's specular doesn't reach the diffuse base
+        let c_dielectric = 0.08 * self.specular * c_st;
+        let transmitted =
+            Vector3::one() - (c_dielectric + (Vector3::one() - c_dielectric) * fresnel_h);
 
         (1.0 - self.metallic)
-            * (linear_color / PI * fresnel_diffuse.lerp(fresnel_subsurface, self.subsurface)
+            * (transmitted * base_color / PI
+                * lerp(fresnel_diffuse, fresnel_subsurface, self.subsurface)
                 + fresnel_sheen)
             + specular_d * specular_g * specular_f / (4.0 * n_dot_v * n_dot_l)
             + self.clearcoat * 0.25 * Vector3::one() * clearcoat_d * clearcoat_f * clearcoat_g
                 / (4.0 * n_dot_v * n_dot_l)
     }
 
-    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
-        let rec = ScatterRecord::Microfacet {
-            pdf: PDF::brdf_pdf(
-                rec.normal,
-                r_in.dir(),
-                self.roughness,
-                self.anisotropic,
-                self.clearcoat,
-                self.clearcoat_gloss,
-            ),
-        };
-        Some(rec)
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        Some(ScatterRecord::Microfacet {
+            pdf: self.pdf(r_in, rec),
+        })
     }
 }
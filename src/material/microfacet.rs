@@ -0,0 +1,193 @@
+use super::*;
+
+// Shared pieces of the microfacet materials. Directions are in the local
+// shading frame with the normal along z, pointing away from the surface.
+
+/// Anisotropic GGX (Trowbridge-Reitz) distribution of microfacet normals.
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    ax: f64,
+    ay: f64,
+}
+
+impl Ggx {
+    /// `roughness` in [0, 1] is squared into the distribution's alpha, which
+    /// keeps the perceived roughness roughly linear in the parameter.
+    pub fn new(roughness: f64) -> Ggx {
+        let alpha = (roughness * roughness).max(0.0001);
+        Ggx {
+            ax: alpha,
+            ay: alpha,
+        }
+    }
+
+    /// Stretches the distribution along the tangent for `anisotropic` > 0.
+    pub fn anisotropic(roughness: f64, anisotropic: f64) -> Ggx {
+        let aspect = (1.0 - anisotropic.clamp(0.0, 1.0) * 0.9).sqrt();
+        let alpha = roughness * roughness;
+        Ggx {
+            ax: (alpha / aspect).max(0.0001),
+            ay: (alpha * aspect).max(0.0001),
+        }
+    }
+
+    /// Density of microfacet normals `h`.
+    pub fn d(&self, h: Vector3) -> f64 {
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+        let e = (h.x() / self.ax).powi(2) + (h.y() / self.ay).powi(2) + h.z().powi(2);
+        1.0 / (PI * self.ax * self.ay * e * e)
+    }
+
+    fn lambda(&self, w: Vector3) -> f64 {
+        if w.z() == 0.0 {
+            return f64::INFINITY;
+        }
+        let tan_theta_2 = ((self.ax * w.x()).powi(2) + (self.ay * w.y()).powi(2)) / w.z().powi(2);
+        0.5 * ((1.0 + tan_theta_2).sqrt() - 1.0)
+    }
+
+    /// Smith masking of direction `w`.
+    pub fn g1(&self, w: Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height correlated masking-shadowing of `wo` and `wi`.
+    pub fn g2(&self, wo: Vector3, wi: Vector3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal visible from `wo` (Heitz 2018), with
+    /// density `visible_pdf`. `wo` must be above the surface.
+    pub fn sample_visible_normal(&self, wo: Vector3, u1: f64, u2: f64) -> Vector3 {
+        // stretch into the configuration of a hemisphere of unit roughness
+        let vh = Vector3::new(self.ax * wo.x(), self.ay * wo.y(), wo.z()).normalize();
+        let len_sq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_sq > 0.0 {
+            Vector3::new(-vh.y(), vh.x(), 0.0) / len_sq.sqrt()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        // uniform point on the projected disk, squeezed onto the visible half
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        Vector3::new(self.ax * nh.x(), self.ay * nh.y(), nh.z().max(1e-6)).normalize()
+    }
+
+    /// Density of `sample_visible_normal` returning `h` for `wo`.
+    pub fn visible_pdf(&self, wo: Vector3, h: Vector3) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z()
+    }
+}
+
+/// Fresnel reflectance of a conductor with complex index of refraction
+/// `eta + i k`, per channel, for unpolarized light.
+pub fn fresnel_conductor(cos_i: f64, eta: Color, k: Color) -> Color {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let mut reflectance = Color::zero();
+    for i in 0..3 {
+        let eta2 = eta[i] * eta[i];
+        let k2 = k[i] * k[i];
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        reflectance[i] = 0.5 * (rp + rs);
+    }
+    reflectance
+}
+
+/// Fresnel reflectance of a dielectric interface for unpolarized light.
+/// `eta` is the index of refraction on the far side over the one on the side
+/// `cos_i` is measured on; a negative `cos_i` swaps the sides.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(-1.0, 1.0);
+    let (cos_i, eta) = if cos_i < 0.0 {
+        (-cos_i, 1.0 / eta)
+    } else {
+        (cos_i, eta)
+    };
+    let sin_t = (1.0 - cos_i * cos_i).max(0.0).sqrt() / eta;
+    if sin_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t * sin_t).max(0.0).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// resolution of the directional albedo table, in cos(theta) and roughness
+const ALBEDO_SIZE: usize = 32;
+// the table is integrated over ALBEDO_SAMPLES^2 stratified visible normals per entry
+const ALBEDO_SAMPLES: usize = 16;
+
+impl Ggx {
+    /// Fraction of light this surface reflects in a single bounce towards
+    /// `cos_theta` if its facets were perfect mirrors. The rest would bounce
+    /// between the facets before leaving. Anisotropic lobes are looked up as
+    /// the isotropic lobe of the same roughness, which underestimates the
+    /// bounces along their long axis.
+    pub fn albedo(&self, cos_theta: f64) -> f64 {
+        static TABLE: std::sync::OnceLock<Vec<f64>> = std::sync::OnceLock::new();
+        let table = TABLE.get_or_init(|| {
+            let mut table = vec![0.0; ALBEDO_SIZE * ALBEDO_SIZE];
+            for j in 0..ALBEDO_SIZE {
+                let ggx = Ggx::new(j as f64 / (ALBEDO_SIZE - 1) as f64);
+                for i in 0..ALBEDO_SIZE {
+                    let cos_o = (i as f64 / (ALBEDO_SIZE - 1) as f64).max(0.01);
+                    let wo = Vector3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+                    let mut sum = 0.0;
+                    for a in 0..ALBEDO_SAMPLES {
+                        for b in 0..ALBEDO_SAMPLES {
+                            let u1 = (a as f64 + 0.5) / ALBEDO_SAMPLES as f64;
+                            let u2 = (b as f64 + 0.5) / ALBEDO_SAMPLES as f64;
+                            let h = ggx.sample_visible_normal(wo, u1, u2);
+                            let wi = (-wo).reflect(h);
+                            if wi.z() > 0.0 {
+                                sum += ggx.g2(wo, wi) / ggx.g1(wo);
+                            }
+                        }
+                    }
+                    table[j * ALBEDO_SIZE + i] = sum / (ALBEDO_SAMPLES * ALBEDO_SAMPLES) as f64;
+                }
+            }
+            table
+        });
+
+        // bilinear lookup
+        let roughness = (self.ax * self.ay).sqrt().sqrt();
+        let scale = (ALBEDO_SIZE - 1) as f64;
+        let x = cos_theta.clamp(0.0, 1.0) * scale;
+        let y = roughness.clamp(0.0, 1.0) * scale;
+        let (i, j) = (
+            (x as usize).min(ALBEDO_SIZE - 2),
+            (y as usize).min(ALBEDO_SIZE - 2),
+        );
+        let (fx, fy) = (x - i as f64, y - j as f64);
+        let at = |i: usize, j: usize| table[j * ALBEDO_SIZE + i];
+        lerp(
+            lerp(at(i, j), at(i + 1, j), fx),
+            lerp(at(i, j + 1), at(i + 1, j + 1), fx),
+            fy,
+        )
+    }
+}
@@ -0,0 +1,87 @@
+use super::*;
+
+/// Blend of two materials, `b` where the `weight` texture is white and `a`
+/// where it is black, e.g. rust on metal driven by a mask. The weight is
+/// the texture's gray level, the mean of its channels.
+///
+/// Each scattering event picks one of the two materials with the weight as
+/// its probability, so on average the surface reflects the blend. The pick
+/// is hashed from the hit, so the BRDF and pdf evaluated for a sampled
+/// direction are those of the material that sampled it. The same pick cuts
+/// holes where the picked material is an `AlphaMask`. Emission is blended
+/// directly.
+#[derive(Debug, Clone, Copy)]
+pub struct MixMaterial<A: Material, B: Material, T: Texture> {
+    a: A,
+    b: B,
+    weight: T,
+}
+
+impl<A: Material, B: Material, T: Texture> MixMaterial<A, B, T> {
+    pub fn new(a: A, b: B, weight: T) -> MixMaterial<A, B, T> {
+        MixMaterial { a, b, weight }
+    }
+
+    fn weight(&self, rec: &HitRecord) -> f64 {
+        let w = self.weight.get_hit_color(rec);
+        ((w.r() + w.g() + w.b()) / 3.0).clamp(0.0, 1.0)
+    }
+
+    fn pick(&self, r_in: &Ray, rec: &HitRecord) -> &dyn Material {
+        if hit_hash(MIX_SALT, r_in, rec) < self.weight(rec) {
+            &self.b
+        } else {
+            &self.a
+        }
+    }
+}
+
+// keeps the pick independent of an `AlphaMask` coin at the same hit
+const MIX_SALT: u64 = 0x6d69_785f_7069_636b;
+
+impl<A: Material, B: Material, T: Texture + Sync> Material for MixMaterial<A, B, T> {
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let w = self.weight(rec);
+        self.a.emitted(rec) * (1.0 - w) + self.b.emitted(rec) * w
+    }
+
+    fn with_emitter_surface(mut self, surface: EmitterSurface) -> Self {
+        self.a = self.a.with_emitter_surface(surface);
+        self.b = self.b.with_emitter_surface(surface);
+        self
+    }
+
+    fn needs_emitter_surface(&self) -> bool {
+        self.a.needs_emitter_surface() || self.b.needs_emitter_surface()
+    }
+
+    fn emitted_spectrum(
+        &self,
+        rec: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        let w = self.weight(rec);
+        self.a.emitted_spectrum(rec, wavelengths) * (1.0 - w)
+            + self.b.emitted_spectrum(rec, wavelengths) * w
+    }
+
+    fn is_cut_out(&self, r: &Ray, rec: &HitRecord) -> bool {
+        self.pick(r, rec).is_cut_out(r, rec)
+    }
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.pick(r_in, rec).scatter(r_in, rec)
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        self.pick(r_in, rec).scatter_mc_methode(r_in, rec)
+    }
+
+    fn brdf(&self, r_in: &Ray, r_out: &Ray, rec: &HitRecord) -> Vector3 {
+        self.pick(r_in, rec).brdf(r_in, r_out, rec)
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.pick(r_in, rec).scattering_pdf(r_in, rec, scattered)
+    }
+}
@@ -1,9 +1,32 @@
+pub use self::alphamask::*;
+pub use self::bumpmap::*;
+pub use self::conductor::*;
+pub use self::hair::*;
+pub use self::ior::*;
+pub use self::layered::*;
 pub use self::material::*;
+pub use self::microfacet::*;
+pub use self::mixmaterial::*;
+pub use self::normalmap::*;
 pub use self::onb::*;
 pub use self::pdf::*;
+pub use self::roughdielectric::*;
+pub mod alphamask;
+pub mod bumpmap;
+pub mod conductor;
+pub mod hair;
+pub mod ior;
+pub mod layered;
 pub mod material;
+pub mod microfacet;
+pub mod mixmaterial;
+pub mod normalmap;
 pub mod onb;
 pub mod pdf;
+pub mod roughdielectric;
+
+#[cfg(test)]
+mod tests;
 use crate::geometry::hitrecord::ScatterRecord;
 use crate::{geometry::HitRecord, geometry::Hittable, texture::Texture, utility::*};
 
@@ -11,9 +34,35 @@ pub trait Material: Sync {
     fn emitted(&self, _rec: &HitRecord) -> Color {
         Color::new(0.0, 0.0, 0.0)
     }
+    /// Surface the material is put on, handed over by the shape, see
+    /// `Intensity::Watts`. Shapes hand it over again when a builder changes
+    /// their surface.
+    fn with_emitter_surface(self, _surface: EmitterSurface) -> Self
+    where
+        Self: Sized,
+    {
+        self
+    }
+    /// Whether the brightness depends on `with_emitter_surface`, so that shapes
+    /// of unknown area can refuse the material.
+    fn needs_emitter_surface(&self) -> bool {
+        false
+    }
+    /// Emission for the spectral pipeline, by default `emitted` uplifted.
+    fn emitted_spectrum(
+        &self,
+        rec: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        SampledSpectrum::from_rgb_illuminant(self.emitted(rec), wavelengths)
+    }
     fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
         None
     }
+    /// Whether `r` passes through a hole in the surface at the hit, see `AlphaMask`.
+    fn is_cut_out(&self, _r: &Ray, _rec: &HitRecord) -> bool {
+        false
+    }
 
     fn scatter_mc_methode(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         None
@@ -25,3 +74,19 @@ pub trait Material: Sync {
         0.0
     }
 }
+
+/// Uniform in [0, 1) from where `r` hits, the same for every call with that
+/// ray and hit. Transforms hand on the hit's t, (u, v) and time unchanged, so
+/// nested hittables see the same value. `salt` keeps separate uses
+/// independent.
+pub(crate) fn hit_hash(salt: u64, r: &Ray, rec: &HitRecord) -> f64 {
+    let mut h = salt;
+    for x in [rec.u, rec.v, rec.t, r.time()] {
+        // splitmix64 finalizer
+        h = (h ^ x.to_bits()).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+    }
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,94 @@
+use super::*;
+
+/// Perturbs the shading normal of `inner` from a tangent space normal map,
+/// colors in [0, 1] standing for the components in [-1, 1] along the hit's
+/// tangent, bitangent and normal, as most tools bake them (green is +v).
+///
+/// `strength` scales the tilt away from the surface normal; 1 keeps the map
+/// as it is, 0 turns it off.
+#[derive(Debug, Clone, Copy)]
+pub struct NormalMap<M: Material, T: Texture> {
+    inner: M,
+    map: T,
+    strength: f64,
+}
+
+impl<M: Material, T: Texture> NormalMap<M, T> {
+    pub fn new(inner: M, map: T) -> NormalMap<M, T> {
+        NormalMap {
+            inner,
+            map,
+            strength: 1.0,
+        }
+    }
+
+    pub fn with_strength(mut self, strength: f64) -> NormalMap<M, T> {
+        self.strength = strength.max(0.0);
+        self
+    }
+
+    fn shade<'a>(&self, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let color = self.map.get_hit_color(rec);
+        let local = Vector3::new(
+            (2.0 * color.x() - 1.0) * self.strength,
+            (2.0 * color.y() - 1.0) * self.strength,
+            (2.0 * color.z() - 1.0).max(1e-3),
+        );
+        // the map is baked against the outward normal, the tangents don't flip
+        // with the hit normal on back faces
+        let outward = if rec.front_face {
+            rec.normal
+        } else {
+            -rec.normal
+        };
+        let frame = ONB::build_from_w_u_v(&outward, &rec.tangent, &rec.bitangent);
+        let normal = frame.local(&local).normalize();
+        HitRecord {
+            normal: if rec.front_face { normal } else { -normal },
+            ..*rec
+        }
+    }
+}
+
+impl<M: Material, T: Texture + Sync> Material for NormalMap<M, T> {
+    fn is_cut_out(&self, r: &Ray, rec: &HitRecord) -> bool {
+        self.inner.is_cut_out(r, rec)
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.inner.emitted(rec)
+    }
+
+    fn with_emitter_surface(mut self, surface: EmitterSurface) -> Self {
+        self.inner = self.inner.with_emitter_surface(surface);
+        self
+    }
+
+    fn needs_emitter_surface(&self) -> bool {
+        self.inner.needs_emitter_surface()
+    }
+
+    fn emitted_spectrum(
+        &self,
+        rec: &HitRecord,
+        wavelengths: &SampledWavelengths,
+    ) -> SampledSpectrum {
+        self.inner.emitted_spectrum(rec, wavelengths)
+    }
+
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.inner.scatter(r_in, &self.shade(rec))
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        self.inner.scatter_mc_methode(r_in, &self.shade(rec))
+    }
+
+    fn brdf(&self, r_in: &Ray, r_out: &Ray, rec: &HitRecord) -> Vector3 {
+        self.inner.brdf(r_in, r_out, &self.shade(rec))
+    }
+
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        self.inner.scattering_pdf(r_in, &self.shade(rec), scattered)
+    }
+}
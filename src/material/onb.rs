@@ -19,6 +19,29 @@ impl ONB {
         ONB { axis: [u, v, w] }
     }
 
+    /// Frame around `n` with u along `tangent`, where the tangent is usable.
+    pub fn build_from_w_u(n: &Vector3, tangent: &Vector3) -> ONB {
+        let w = n.normalize();
+        let u = *tangent - w * w.dot(*tangent);
+        if u.length_squared() < 1e-12 {
+            return ONB::build_from_w(n);
+        }
+        let u = u.normalize();
+        ONB {
+            axis: [u, w.cross(u), w],
+        }
+    }
+
+    /// Frame around `n` with u along `tangent` and v on the side of
+    /// `bitangent`, which makes it left-handed for mirrored texture space.
+    pub fn build_from_w_u_v(n: &Vector3, tangent: &Vector3, bitangent: &Vector3) -> ONB {
+        let mut onb = ONB::build_from_w_u(n, tangent);
+        if onb.v().dot(*bitangent) < 0.0 {
+            onb.axis[1] = -onb.axis[1];
+        }
+        onb
+    }
+
     pub fn u(&self) -> Vector3{
         self.axis[0]
     }
@@ -33,5 +56,10 @@ impl ONB {
     pub fn local(&self, a: &Vector3) -> Vector3{
         a.x() * self.u() + a.y() * self.v() + a.z() * self.w()
     }
+
+    /// Inverse of `local`: coordinates of the world vector `a` in this frame.
+    pub fn to_local(&self, a: &Vector3) -> Vector3 {
+        Vector3::new(a.dot(self.u()), a.dot(self.v()), a.dot(self.w()))
+    }
 }
 
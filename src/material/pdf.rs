@@ -17,27 +17,27 @@ fn spherical_direction(sin_theta: f64, cos_theta: f64, sin_phi: f64, cos_phi: f6
     Vector3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta)
 }
 
-fn gtr_1_direction(r_in: Vector3, clearcoat_gloss: f64) -> Vector3 {
+/// Half vector distributed as GTR1 times its cosine, in the local frame.
+fn gtr_1_half_vector(clearcoat_gloss: f64) -> Vector3 {
     let mut rng = rand::thread_rng();
     let r1 = rng.gen_range(0.0..1.0);
     let r2 = rng.gen_range(0.0..1.0);
     let a = lerp(0.1, 0.001, clearcoat_gloss);
     let a2 = a * a;
-    let cos_theta = f64::sqrt(f64::max(0.001, (1.0 - a2.powf(1.0 - r1)) / (1.0 - a2)));
-    let sin_theta = f64::sqrt(f64::max(0.001, 1.0 - cos_theta * cos_theta));
+    let cos_theta = f64::sqrt(f64::max(0.0, (1.0 - a2.powf(1.0 - r1)) / (1.0 - a2)));
+    let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
     let phi = f64::consts::PI * 2.0 * r2;
-    let wh = spherical_direction(sin_theta, cos_theta, f64::sin(phi), f64::cos(phi));
-
-    Vector3::reflect(r_in, wh)
+    spherical_direction(sin_theta, cos_theta, f64::sin(phi), f64::cos(phi))
 }
 
-fn gtr_2_aniso_direction(r_in: Vector3, roughness: f64, anisotropic: f64) -> Vector3 {
+/// Half vector distributed as anisotropic GTR2 times its cosine, in the local frame.
+fn gtr_2_aniso_half_vector(roughness: f64, anisotropic: f64) -> Vector3 {
     let mut rng = rand::thread_rng();
     let r1 = rng.gen_range(0.0..1.0);
     let r2 = rng.gen_range(0.0..1.0);
     let aspect = (1.0 - anisotropic * 0.9).sqrt();
-    let ax = (roughness.powi(2) / aspect).max(0.001);
-    let ay = (roughness.powi(2) * aspect).max(0.001);
+    let ax = (roughness.powi(2) / aspect).max(0.0001);
+    let ay = (roughness.powi(2) * aspect).max(0.0001);
     let mut phi = f64::atan(ay / ax * f64::tan(2.0 * f64::consts::PI * r2 + 0.5 * f64::consts::PI));
     if r2 > 0.5 {
         phi += f64::consts::PI;
@@ -49,10 +49,17 @@ fn gtr_2_aniso_direction(r_in: Vector3, roughness: f64, anisotropic: f64) -> Vec
     let a2 = 1.0 / (cos_phi * cos_phi / ax_2 + sin_phi * sin_phi / ay_2);
     let tan_theta_2 = a2 * r1 / (1.0 - r1);
     let cos_theta = 1.0 / (1.0 + tan_theta_2).sqrt();
-    let sin_theta = f64::sqrt(f64::max(0.001, 1.0 - cos_theta * cos_theta));
-    let wh = spherical_direction(sin_theta, cos_theta, f64::sin(phi), f64::cos(phi));
+    let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
+    spherical_direction(sin_theta, cos_theta, f64::sin(phi), f64::cos(phi))
+}
 
-    Vector3::reflect(r_in, wh)
+/// Probabilities of picking the diffuse, specular and clearcoat lobe.
+fn brdf_lobe_weights(clearcoat: f64) -> [f64; 3] {
+    if clearcoat > 0.0 {
+        [1.0 / 3.0; 3]
+    } else {
+        [0.5, 0.5, 0.0]
+    }
 }
 
 pub enum PDF<'a> {
@@ -69,6 +76,7 @@ pub enum PDF<'a> {
     },
     Hittable {
         origin: Point,
+        time: f64,
         hittable: &'a Box<dyn Hittable>,
     },
     Mixture {
@@ -102,8 +110,13 @@ impl<'a> PDF<'a> {
         }
     }
 
-    pub fn hittable_pdf(origin: Point, hittable: &'a Box<dyn Hittable>) -> PDF<'a> {
-        PDF::Hittable { origin, hittable }
+    /// Directions towards `hittable` as it is posed at `time`.
+    pub fn hittable_pdf(origin: Point, time: f64, hittable: &'a Box<dyn Hittable>) -> PDF<'a> {
+        PDF::Hittable {
+            origin,
+            time,
+            hittable,
+        }
     }
 
     pub fn mixture_pdf(p0: &'a PDF, p1: &'a PDF) -> PDF<'a> {
@@ -129,31 +142,31 @@ impl<'a> PDF<'a> {
                 let n = uvw.w();
                 let x = uvw.u();
                 let y = uvw.v();
-                let n_dot_l = n.dot(l);
                 let h = (l + v).normalize();
                 let n_dot_h = n.dot(h);
+                let l_dot_h = l.dot(h);
 
-                if n_dot_h <= 0.0 {
+                if n_dot_h <= 0.0 || l_dot_h <= 0.0 {
                     return 0.0;
                 }
                 // diffuse
                 let diffuse_pdf = cosine / PI;
 
+                // the half vector pdfs turn into pdfs of the reflected direction by 1 / (4 l.h)
                 // specular
                 let aspect = (1.0 - anisotropic * 0.9).sqrt();
                 let ax = (roughness.powi(2) / aspect).max(0.0001);
                 let ay = (roughness.powi(2) * aspect).max(0.0001);
-                let specular_pdf = gtr_2_anisotropic(n_dot_h, h.dot(x), h.dot(y), ax, ay)
-                    * f64::abs(n_dot_h)
-                    * 0.25
-                    / n_dot_l;
+                let specular_pdf =
+                    gtr_2_anisotropic(n_dot_h, h.dot(x), h.dot(y), ax, ay) * n_dot_h * 0.25
+                        / l_dot_h;
 
                 // clearcoat
                 let clearcoat_pdf =
-                    gtr_1(n_dot_h, lerp(0.1, 0.001, *clearcoat_gloss)) * f64::abs(n_dot_h) * 0.25
-                        / n_dot_l;
+                    gtr_1(n_dot_h, lerp(0.1, 0.001, *clearcoat_gloss)) * n_dot_h * 0.25 / l_dot_h;
 
-                (diffuse_pdf + specular_pdf + clearcoat_pdf) / 3.0
+                let [w_diffuse, w_specular, w_clearcoat] = brdf_lobe_weights(*clearcoat);
+                w_diffuse * diffuse_pdf + w_specular * specular_pdf + w_clearcoat * clearcoat_pdf
             }
             PDF::Cosine { uvw } => {
                 let cosine = r_out.normalize().dot(uvw.w());
@@ -163,12 +176,22 @@ impl<'a> PDF<'a> {
                     0.0
                 }
             }
-            PDF::Hittable { origin, hittable } => hittable.pdf_value(*origin, r_out),
+            PDF::Hittable {
+                origin,
+                time,
+                hittable,
+            } => hittable.pdf_value(*origin, r_out, *time),
             PDF::Mixture { p0, p1 } => 0.5 * p0.value(r_out) + 0.5 * p1.value(r_out),
         }
     }
 
     pub fn generate(&self) -> Vector3 {
+        self.sample().0
+    }
+
+    /// A direction and the type of the ray traced along it: directions
+    /// towards a hittable are shadow rays, the others reflections.
+    pub fn sample(&self) -> (Vector3, RayType) {
         match self {
             PDF::BRDF {
                 uvw,
@@ -178,23 +201,30 @@ impl<'a> PDF<'a> {
                 clearcoat,
                 clearcoat_gloss,
             } => {
+                let [w_diffuse, w_specular, _] = brdf_lobe_weights(*clearcoat);
                 let rng = rand::thread_rng().gen_range(0.0..1.0);
-                if rng < 0.333 {
-                    uvw.local(&random_cosine_direction())
-                } else if rng < 0.666 {
-                    uvw.local(&&gtr_1_direction(*r_in, *clearcoat_gloss))
-                } else {
-                    uvw.local(&gtr_2_aniso_direction(*r_in, *roughness, *anisotropic))
+                if rng < w_diffuse {
+                    return (uvw.local(&random_cosine_direction()), RayType::Reflection);
                 }
+                let h = if rng < w_diffuse + w_specular {
+                    uvw.local(&gtr_2_aniso_half_vector(*roughness, *anisotropic))
+                } else {
+                    uvw.local(&gtr_1_half_vector(*clearcoat_gloss))
+                };
+                (r_in.normalize().reflect(h), RayType::Reflection)
             }
-            PDF::Cosine { uvw } => uvw.local(&random_cosine_direction()),
-            PDF::Hittable { origin, hittable } => hittable.random(*origin),
+            PDF::Cosine { uvw } => (uvw.local(&random_cosine_direction()), RayType::Reflection),
+            PDF::Hittable {
+                origin,
+                time,
+                hittable,
+            } => (hittable.random(*origin, *time), RayType::Shadow),
             PDF::Mixture { p0, p1 } => {
                 let mut rng = rand::thread_rng();
                 if rng.gen::<bool>() {
-                    p0.generate()
+                    p0.sample()
                 } else {
-                    p1.generate()
+                    p1.sample()
                 }
             }
         }
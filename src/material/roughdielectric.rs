@@ -0,0 +1,142 @@
+use rand::Rng;
+
+use super::*;
+
+/// Glass with a GGX microfacet surface (Walter et al., "Microfacet Models for
+/// Refraction through Rough Surfaces"), for frosted glass, and optionally
+/// tinted by absorption inside. `roughness` 0 is polished glass.
+///
+/// Solid objects must be closed and have their normals pointing out: light
+/// is absorbed over the distance it travels between entering and leaving.
+/// Thin-walled glass has no inside; each side of the surface is air, so rays
+/// pass through without bending, as through a window pane.
+#[derive(Debug, Clone, Copy)]
+pub struct RoughDielectric {
+    ir: Ior,
+    roughness: f64,
+    absorption: Color,
+    thickness: f64,
+    thin_walled: bool,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: impl Into<Ior>, roughness: f64) -> RoughDielectric {
+        RoughDielectric {
+            ir: ir.into(),
+            roughness: roughness.clamp(0.0, 1.0),
+            absorption: Color::zero(),
+            thickness: 0.0,
+            thin_walled: false,
+        }
+    }
+
+    /// Light that travelled `distance` through the glass is left with `tint`
+    /// of itself (Beer-Lambert). Thin-walled glass is taken to be `distance`
+    /// thick, so it is `tint` looking straight through and deeper at an angle.
+    pub fn with_absorption(mut self, tint: Color, distance: f64) -> RoughDielectric {
+        let distance = distance.max(1e-6);
+        for i in 0..3 {
+            self.absorption[i] = -tint[i].clamp(1e-6, 1.0).ln() / distance;
+        }
+        self.thickness = distance;
+        self
+    }
+
+    pub fn with_thin_walled(mut self, thin_walled: bool) -> RoughDielectric {
+        self.thin_walled = thin_walled;
+        self
+    }
+
+    /// Distance the absorption tint is given over.
+    pub fn thickness(&self) -> f64 {
+        self.thickness
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        Color::new(
+            (-self.absorption.x() * distance).exp(),
+            (-self.absorption.y() * distance).exp(),
+            (-self.absorption.z() * distance).exp(),
+        )
+    }
+
+    fn sample(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let mut rng = rand::thread_rng();
+        let (ir, wavelengths) = self.ir.for_ray(r_in);
+        // the hit normal faces the incoming ray, so wo is always above the surface
+        let uvw = ONB::build_from_w_u(&rec.normal, &rec.tangent);
+        let wo = uvw.to_local(&-r_in.dir().normalize());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let ggx = Ggx::new(self.roughness);
+        let h = ggx.sample_visible_normal(wo, rng.gen::<f64>(), rng.gen::<f64>());
+        let cos_o = wo.dot(h);
+        let reflected = (-wo).reflect(h);
+
+        // with facet directions sampled from the visible normals and the
+        // reflection/transmission choice made by the Fresnel term, f cos / pdf
+        // is only the shadowing of the outgoing direction
+        let (direction, mut attenuation) = if self.thin_walled {
+            if reflected.z() <= 0.0 {
+                return None;
+            }
+            // light bouncing back and forth inside the sheet adds to both sides
+            let r = fresnel_dielectric(cos_o, ir);
+            let t = 1.0 - r;
+            let r = if r < 1.0 {
+                r + t * t * r / (1.0 - r * r)
+            } else {
+                r
+            };
+            if rng.gen::<f64>() < r {
+                (reflected, Color::one())
+            } else {
+                // the second interface undoes the bending of the first
+                let through = Vector3::new(reflected.x(), reflected.y(), -reflected.z());
+                let cos_t = (1.0 - (1.0 - wo.z() * wo.z()) / (ir * ir)).max(1e-6).sqrt();
+                (through, self.transmittance(self.thickness / cos_t))
+            }
+        } else {
+            let eta = if rec.front_face { ir } else { 1.0 / ir };
+            // reflections stay on the side they came from, transmissions cross over
+            if rng.gen::<f64>() < fresnel_dielectric(cos_o, eta) {
+                if reflected.z() <= 0.0 {
+                    return None;
+                }
+                (reflected, Color::one())
+            } else {
+                let refracted = (-wo).refract(h, 1.0 / eta);
+                if refracted.z() >= 0.0 {
+                    return None;
+                }
+                (refracted, Color::one())
+            }
+        };
+        attenuation *= ggx.g2(wo, direction) / ggx.g1(wo);
+
+        // a back face hit ends a path through the inside of a solid
+        if !self.thin_walled && !rec.front_face {
+            let distance = rec.t * r_in.dir().length();
+            attenuation *= self.transmittance(distance);
+        }
+
+        let scattered = Ray::new(rec.p, uvw.local(&direction), r_in.time());
+        Some((attenuation, scattered.with_wavelengths(wavelengths)))
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.sample(r_in, rec)
+    }
+
+    fn scatter_mc_methode(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord<'_>> {
+        let (attenuation, specular_ray) = self.sample(r_in, rec)?;
+        Some(ScatterRecord::Specular {
+            specular_ray,
+            attenuation,
+        })
+    }
+}
@@ -0,0 +1,547 @@
+use rand::Rng;
+
+use super::*;
+use crate::geometry::{Sphere, Subsurface};
+use crate::texture::SolidTexture;
+
+const SAMPLES: usize = 40_000;
+const ROUGHNESS: [f64; 5] = [0.05, 0.2, 0.5, 0.8, 1.0];
+const METALLIC: [f64; 3] = [0.0, 0.5, 1.0];
+// cosines of the viewing angle
+const VIEWS: [f64; 3] = [1.0, 0.5, 0.2];
+// subsurface walks are long, and a few thousand of them are plenty
+const WALKS: usize = 2_000;
+
+fn white_pbr(metallic: f64, roughness: f64) -> PBR<SolidTexture> {
+    PBR::new(
+        SolidTexture::new(Color::one()),
+        metallic,
+        0.0,
+        0.5,
+        roughness,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+    )
+}
+
+/// Ray arriving at the origin from above at `cos` to the +y normal.
+fn view_ray(cos: f64) -> Ray {
+    let sin = (1.0 - cos * cos).sqrt();
+    Ray::new(
+        Point::new(sin, cos, 0.0),
+        Vector3::new(-sin, -cos, 0.0),
+        0.0,
+    )
+}
+
+fn hit_record(mat: &dyn Material) -> HitRecord<'_> {
+    HitRecord {
+        p: Point::zero(),
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        t: 1.0,
+        primitive: 0,
+        u: 0.5,
+        v: 0.5,
+        front_face: true,
+        tangent: Vector3::new(1.0, 0.0, 0.0),
+        bitangent: Vector3::new(0.0, 0.0, 1.0),
+        mat,
+    }
+}
+
+/// Light reflected towards the viewer when every direction sees a white
+/// environment of radiance 1, i.e. the albedo.
+fn furnace_scatter(mat: &dyn Material, cos: f64) -> Color {
+    let r = view_ray(cos);
+    let rec = hit_record(mat);
+    let mut sum = Color::zero();
+    for _ in 0..SAMPLES {
+        if let Some((attenuation, _)) = mat.scatter(&r, &rec) {
+            sum += attenuation;
+        }
+    }
+    sum / SAMPLES as f64
+}
+
+/// The same albedo from `PDF::BRDF` samples weighted by `brdf` directly.
+fn furnace_pdf(pbr: &PBR<SolidTexture>, roughness: f64, cos: f64) -> Color {
+    let r = view_ray(cos);
+    let rec = hit_record(pbr);
+    let pdf = PDF::brdf_pdf(rec.normal, r.dir(), roughness, 0.0, 0.0, 0.0);
+    let mut sum = Color::zero();
+    for _ in 0..SAMPLES {
+        let direction = pdf.generate();
+        let value = pdf.value(direction);
+        let cosine = direction.normalize().dot(rec.normal);
+        if value > 0.0 && cosine > 0.0 {
+            let scattered = Ray::new(rec.p, direction, 0.0);
+            sum += pbr.brdf(&r, &scattered, &rec) * cosine / value;
+        }
+    }
+    sum / SAMPLES as f64
+}
+
+fn check_albedo(name: &str, albedo: Color, metallic: f64, roughness: f64, cos: f64) {
+    // 2% leaves room for the noise of the estimate
+    for i in 0..3 {
+        assert!(
+            albedo[i] <= 1.02,
+            "{}: metallic {} roughness {} cos {} reflects {}",
+            name,
+            metallic,
+            roughness,
+            cos,
+            albedo[i]
+        );
+    }
+    // a smooth white metal is a mirror, and a mirror loses nothing
+    if metallic == 1.0 && roughness <= 0.05 {
+        assert!(
+            (albedo.x() - 1.0).abs() < 0.02,
+            "{}: smooth white metal at cos {} reflects {}",
+            name,
+            cos,
+            albedo.x()
+        );
+    }
+}
+
+#[test]
+fn pbr_white_furnace() {
+    for metallic in METALLIC {
+        for roughness in ROUGHNESS {
+            let pbr = white_pbr(metallic, roughness);
+            for cos in VIEWS {
+                let albedo = furnace_scatter(&pbr, cos);
+                check_albedo("scatter", albedo, metallic, roughness, cos);
+                let albedo = furnace_pdf(&pbr, roughness, cos);
+                check_albedo("pdf", albedo, metallic, roughness, cos);
+            }
+        }
+    }
+}
+
+#[test]
+fn pbr_pdf_matches_its_samples() {
+    let mut rng = rand::thread_rng();
+    for roughness in ROUGHNESS {
+        for cos in VIEWS {
+            let r = view_ray(cos);
+            let normal = Vector3::new(0.0, 1.0, 0.0);
+            let pdf = PDF::brdf_pdf(normal, r.dir(), roughness, 0.0, 0.0, 0.0);
+
+            // the pdf over the sphere of directions, stratified in z and azimuth;
+            // a uniform grid can't resolve the spike of a smoother lobe
+            if roughness >= 0.5 {
+                let strata = 200;
+                let rows = SAMPLES / strata;
+                let mut integral = 0.0;
+                for i in 0..SAMPLES {
+                    let z = 1.0 - 2.0 * ((i / strata) as f64 + rng.gen::<f64>()) / rows as f64;
+                    let phi = 2.0 * PI * ((i % strata) as f64 + rng.gen::<f64>()) / strata as f64;
+                    let s = (1.0 - z * z).max(0.0).sqrt();
+                    integral += pdf.value(Vector3::new(s * phi.cos(), z, s * phi.sin()));
+                }
+                let integral = integral * 4.0 * PI / SAMPLES as f64;
+                // lobes leaking below the surface only lose samples
+                assert!(
+                    integral <= 1.01,
+                    "roughness {} cos {}: pdf integrates to {}",
+                    roughness,
+                    cos,
+                    integral
+                );
+            }
+
+            // samples above the surface must have the density they were drawn with:
+            // E[1 / pdf] over them is the solid angle of the hemisphere
+            let mut solid_angle = 0.0;
+            for _ in 0..SAMPLES {
+                let direction = pdf.generate();
+                if direction.dot(normal) > 0.0 {
+                    let value = pdf.value(direction);
+                    assert!(value > 0.0, "sampled {:?} has no density", direction);
+                    solid_angle += 1.0 / value;
+                }
+            }
+            let solid_angle = solid_angle / SAMPLES as f64;
+            assert!(
+                (solid_angle - 2.0 * PI).abs() < 0.05 * 2.0 * PI,
+                "roughness {} cos {}: samples cover {} sr",
+                roughness,
+                cos,
+                solid_angle
+            );
+        }
+    }
+}
+
+/// Albedo through `scatter_mc_methode`, weighting each sampled direction
+/// with `brdf` or `scattering_pdf` as the integrator does. Every sample hits
+/// somewhere else, so hashed choices vary between them.
+fn furnace_mc(mat: &dyn Material, cos: f64) -> Color {
+    let mut rng = rand::thread_rng();
+    let r = view_ray(cos);
+    let mut sum = Color::zero();
+    for _ in 0..SAMPLES {
+        let rec = HitRecord {
+            u: rng.gen(),
+            v: rng.gen(),
+            ..hit_record(mat)
+        };
+        let (pdf, attenuation) = match mat.scatter_mc_methode(&r, &rec) {
+            Some(ScatterRecord::Scatter { pdf, attenuation }) => (pdf, Some(attenuation)),
+            Some(ScatterRecord::Microfacet { pdf }) => (pdf, None),
+            _ => unreachable!("expected a sampled scatter"),
+        };
+        let scattered = Ray::new(rec.p, pdf.generate(), 0.0);
+        let cosine = scattered.dir().normalize().dot(rec.normal);
+        let value = pdf.value(scattered.dir());
+        if cosine <= 0.0 || value <= 0.0 {
+            continue;
+        }
+        sum += match attenuation {
+            Some(attenuation) => attenuation * mat.scattering_pdf(&r, &rec, &scattered) / value,
+            None => mat.brdf(&r, &scattered, &rec) * cosine / value,
+        };
+    }
+    sum / SAMPLES as f64
+}
+
+#[test]
+fn mix_material_reflects_the_blend_of_its_materials() {
+    let lambertian = Lambertian::new(SolidTexture::new(Color::one()));
+    let metal = white_pbr(1.0, 0.05);
+    let mix = MixMaterial::new(lambertian, metal, SolidTexture::new(Color::one() * 0.25));
+    for cos in VIEWS {
+        let blend = furnace_mc(&lambertian, cos) * 0.75 + furnace_mc(&metal, cos) * 0.25;
+        let albedo = furnace_mc(&mix, cos);
+        assert!(
+            (albedo.x() - blend.x()).abs() < 0.03,
+            "cos {}: mix reflects {}, its materials {}",
+            cos,
+            albedo.x(),
+            blend.x()
+        );
+    }
+}
+
+/// Height rising along u.
+struct Ramp;
+
+impl Texture for Ramp {
+    fn get_color(&self, u: f64, _v: f64, _p: &Point) -> Color {
+        Color::one() * u
+    }
+}
+
+/// Checks that `mat` tilts the +y surface at the origin towards `tilted`
+/// seen from the front, and towards `-tilted` seen from the back.
+fn check_back_face_tilt(name: &str, mat: &dyn Material, tilted: Vector3) {
+    let tilted = tilted.normalize();
+    let front = hit_record(mat);
+    let back = HitRecord {
+        normal: -front.normal,
+        front_face: false,
+        ..front
+    };
+    let r = view_ray(1.0);
+    for (side, rec, direction) in [("front", front, tilted), ("back", back, -tilted)] {
+        // a Lambertian's pdf peaks at 1 / pi along its shading normal
+        let pdf = mat.scattering_pdf(&r, &rec, &Ray::new(rec.p, direction, 0.0));
+        assert!(
+            (pdf - 1.0 / PI).abs() < 1e-6,
+            "{} {}: shading normal is off {:?} (pdf {})",
+            name,
+            side,
+            direction,
+            pdf
+        );
+    }
+}
+
+#[test]
+fn maps_tilt_back_faces_like_front_faces() {
+    let lambertian = Lambertian::new(SolidTexture::new(Color::one()));
+    // tilted halfway towards the tangent, +x
+    let normal_map = NormalMap::new(lambertian, SolidTexture::new(Color::new(1.0, 0.5, 1.0)));
+    check_back_face_tilt("normal map", &normal_map, Vector3::new(1.0, 1.0, 0.0));
+    // the height rises along +x, so the normal leans back towards -x
+    let bump_map = BumpMap::new(lambertian, Ramp, 1.0);
+    check_back_face_tilt("bump map", &bump_map, Vector3::new(-1.0, 1.0, 0.0));
+}
+
+#[test]
+fn mix_material_cuts_the_holes_of_its_pick() {
+    let mut rng = rand::thread_rng();
+    let lambertian = Lambertian::new(SolidTexture::new(Color::one()));
+    let hole = AlphaMask::new(lambertian, SolidTexture::new(Color::zero()));
+    let mix = MixMaterial::new(hole, lambertian, SolidTexture::new(Color::one() * 0.25));
+    let r = view_ray(1.0);
+    let mut cut = 0;
+    for _ in 0..SAMPLES {
+        let rec = HitRecord {
+            u: rng.gen(),
+            v: rng.gen(),
+            ..hit_record(&mix)
+        };
+        if mix.is_cut_out(&r, &rec) {
+            cut += 1;
+        }
+    }
+    let cut = cut as f64 / SAMPLES as f64;
+    assert!((cut - 0.75).abs() < 0.02, "cut {} of the hits", cut);
+}
+
+#[test]
+fn conductor_white_furnace() {
+    // a conductor without a real part reflects everything at every angle
+    let (eta, k) = (Color::zero(), Color::one());
+    assert!((fresnel_conductor(0.3, eta, k).x() - 1.0).abs() < 1e-9);
+    for roughness in ROUGHNESS {
+        let conductor = Conductor::new(eta, k, roughness);
+        for cos in VIEWS {
+            // the light lost to single scattering must all be added back
+            let albedo = furnace_scatter(&conductor, cos);
+            assert!(
+                (albedo.x() - 1.0).abs() < 0.03,
+                "roughness {} cos {}: white conductor reflects {}",
+                roughness,
+                cos,
+                albedo.x()
+            );
+        }
+    }
+}
+
+#[test]
+fn rough_dielectric_white_furnace() {
+    for roughness in ROUGHNESS {
+        let glass = RoughDielectric::new(1.5, roughness);
+        for front_face in [true, false] {
+            let rec = HitRecord {
+                front_face,
+                ..hit_record(&glass)
+            };
+            for cos in VIEWS {
+                let r = view_ray(cos);
+                let mut sum = Color::zero();
+                for _ in 0..SAMPLES {
+                    if let Some((attenuation, _)) = glass.scatter(&r, &rec) {
+                        sum += attenuation;
+                    }
+                }
+                // reflected and transmitted light together, lost only to shadowing
+                let albedo = sum.x() / SAMPLES as f64;
+                assert!(
+                    albedo <= 1.02,
+                    "roughness {} front {} cos {}: glass scatters {}",
+                    roughness,
+                    front_face,
+                    cos,
+                    albedo
+                );
+                if roughness <= 0.05 {
+                    assert!(
+                        (albedo - 1.0).abs() < 0.02,
+                        "front {} cos {}: polished glass scatters {}",
+                        front_face,
+                        cos,
+                        albedo
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn thin_glass_passes_light_straight_through() {
+    let tint = Color::new(0.8, 0.5, 0.2);
+    let pane = RoughDielectric::new(1.5, 0.0)
+        .with_thin_walled(true)
+        .with_absorption(tint, 0.01);
+    let rec = hit_record(&pane);
+    let r = view_ray(1.0);
+    let mut reflected = 0;
+    let (mut through, mut transmitted) = (Vector3::zero(), Color::zero());
+    for _ in 0..SAMPLES {
+        let (attenuation, scattered) = pane.scatter(&r, &rec).unwrap();
+        let direction = scattered.dir().normalize();
+        if direction.y() > 0.0 {
+            reflected += 1;
+        } else {
+            through += direction;
+            transmitted += attenuation;
+        }
+    }
+    // the tail of even the smoothest GGX bends a few rays a little
+    let count = (SAMPLES - reflected) as f64;
+    let through = through / count;
+    assert!(through.y() < -0.999, "pane bent the light to {:?}", through);
+    let transmitted = transmitted / count;
+    for i in 0..3 {
+        assert!(
+            (transmitted[i] - tint[i]).abs() < 1e-3,
+            "pane lets {} of channel {} through, not {}",
+            transmitted[i],
+            i,
+            tint[i]
+        );
+    }
+
+    // both sides reflect 4%, and light bouncing between them adds up
+    let expected = 0.04 + 0.96 * 0.96 * 0.04 / (1.0 - 0.04 * 0.04);
+    let reflected = reflected as f64 / SAMPLES as f64;
+    assert!(
+        (reflected - expected).abs() < 0.005,
+        "pane reflects {}, not {}",
+        reflected,
+        expected
+    );
+}
+
+#[test]
+fn sellmeier_glasses_have_their_catalog_index() {
+    // refractive indices at the helium d line, 587.6 nm
+    for (name, ior, n_d) in [
+        ("bk7", Ior::bk7(), 1.5168),
+        ("fused silica", Ior::fused_silica(), 1.4585),
+        ("diamond", Ior::diamond(), 2.4175),
+    ] {
+        let n = ior.at(587.6);
+        assert!(
+            (n - n_d).abs() < 1e-3,
+            "{} has index {}, not {}",
+            name,
+            n,
+            n_d
+        );
+        // normal dispersion bends blue more than red
+        assert!(
+            ior.at(450.0) > n && n > ior.at(650.0),
+            "{} doesn't disperse",
+            name
+        );
+        assert!(ior.is_dispersive());
+    }
+    assert!(!Ior::from(1.5).is_dispersive());
+}
+
+#[test]
+fn dispersive_glass_traces_on_the_hero_wavelength() {
+    let r = view_ray(1.0).with_wavelengths(Some(SampledWavelengths::sample_uniform(0.3)));
+    let (n, wavelengths) = Ior::bk7().for_ray(&r);
+    let wavelengths = wavelengths.unwrap();
+    assert!(wavelengths.secondary_terminated());
+    assert_eq!(wavelengths.hero(), r.wavelengths().unwrap().hero());
+    assert_eq!(n, Ior::bk7().at(wavelengths.hero()));
+
+    // a constant index leaves the ray's wavelengths alone
+    let (n, wavelengths) = Ior::from(1.5).for_ray(&r);
+    assert_eq!(n, 1.5);
+    assert!(wavelengths.is_none());
+}
+
+#[test]
+fn rgb_survives_the_trip_through_a_spectrum() {
+    for rgb in [
+        Color::one(),
+        Color::new(0.8, 0.2, 0.1),
+        Color::new(0.1, 0.6, 0.2),
+        Color::new(0.2, 0.3, 0.9),
+        Color::new(0.5, 0.5, 0.5),
+        // emission above one keeps its color
+        Color::new(4.0, 2.0, 1.0),
+    ] {
+        // lit by the white of sRGB, a surface shows its own color
+        let mut back = Color::zero();
+        for i in 0..SAMPLES {
+            let wavelengths = SampledWavelengths::sample_uniform((i as f64 + 0.5) / SAMPLES as f64);
+            back += SampledSpectrum::from_rgb_illuminant(rgb, &wavelengths).to_rgb(&wavelengths);
+        }
+        let back = back / SAMPLES as f64;
+        for i in 0..3 {
+            // the sigmoid fit is close, not exact
+            assert!(
+                (back[i] - rgb[i]).abs() < 0.02 * rgb[i].max(1.0),
+                "{:?} came back as {:?}",
+                rgb,
+                back
+            );
+        }
+        // a reflectance spectrum never reflects more than all or less than nothing
+        if rgb.x() <= 1.0 {
+            let sigmoid = RgbSigmoid::new(rgb);
+            for lambda in (380..=780).step_by(10) {
+                let value = sigmoid.at(lambda as f64);
+                assert!(
+                    (0.0..=1.0).contains(&value),
+                    "{:?} at {} nm is {}",
+                    rgb,
+                    lambda,
+                    value
+                );
+            }
+        }
+    }
+}
+
+/// Light leaving `ball` after entering it from the top at `cos`, and how far
+/// from the entry point it leaves on average.
+fn subsurface_walk<H: Hittable>(ball: &Subsurface<H>, cos: f64) -> (Color, f64) {
+    let view = view_ray(cos);
+    let r = Ray::new(view.origin() * 2.0, view.dir(), 0.0);
+    let rec = ball.hit(&r, 0.001, f64::INFINITY).unwrap();
+    let (mut sum, mut spread, mut exits) = (Color::zero(), 0.0, 0);
+    for _ in 0..WALKS {
+        if let Some((attenuation, out)) = ball.scatter(&r, &rec) {
+            // leaving through the surface of the unit ball, away from it
+            assert!((out.origin().length() - 1.0).abs() < 1e-6);
+            assert!(out.dir().dot(out.origin()) > 0.0);
+            sum += attenuation;
+            spread += (out.origin() - rec.p).length();
+            exits += 1;
+        }
+    }
+    (sum / WALKS as f64, spread / exits as f64)
+}
+
+#[test]
+fn subsurface_walk_comes_out_with_its_color() {
+    let lambertian = Lambertian::new(SolidTexture::new(Color::one()));
+    let ball = |color: f64, mean_free_path: f64| {
+        Subsurface::new(
+            Sphere::new(Point::zero(), 1.0, lambertian),
+            Color::one() * color,
+            Color::one() * mean_free_path,
+        )
+    };
+    for color in [0.9, 0.5, 0.2] {
+        // without a boundary to reflect light back in, the walk alone has
+        // to give the color; the fit it is inverted with is close, not exact
+        let matched = ball(color, 0.01).with_interface(RoughDielectric::new(1.0, 0.0));
+        let glass = ball(color, 0.01);
+        for cos in VIEWS {
+            let (albedo, _) = subsurface_walk(&matched, cos);
+            assert!(
+                (albedo.x() - color).abs() < 0.1,
+                "color {} cos {}: walk gives {}",
+                color,
+                cos,
+                albedo.x()
+            );
+            let (through_glass, _) = subsurface_walk(&glass, cos);
+            assert!(through_glass.x() <= albedo.x() + 0.02);
+        }
+    }
+
+    // light comes out further from where it went in in a clearer material
+    let (_, near) = subsurface_walk(&ball(0.8, 0.01), 1.0);
+    let (_, far) = subsurface_walk(&ball(0.8, 0.04), 1.0);
+    assert!(far > 2.0 * near, "spread {} at 0.01, {} at 0.04", near, far);
+}
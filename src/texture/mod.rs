@@ -5,7 +5,38 @@ pub use self::perlin::*;
 pub mod texture;
 pub mod perlin;
 
+use crate::geometry::HitRecord;
 use crate::utility::{macros::*, *};
+use std::sync::Arc;
+
 pub trait Texture {
     fn get_color(&self, u: f64, v: f64, p: &Point) -> Color;
+    /// Color at a hit, by default `get_color` at its (u, v) and point.
+    /// Textures of per-part attributes read the part that was hit as well,
+    /// see `ParticleColorTexture`.
+    fn get_hit_color(&self, rec: &HitRecord) -> Color {
+        self.get_color(rec.u, rec.v, &rec.p)
+    }
+}
+
+impl<T: Texture + ?Sized> Texture for Arc<T> {
+    fn get_color(&self, u: f64, v: f64, p: &Point) -> Color {
+        (**self).get_color(u, v, p)
+    }
+    fn get_hit_color(&self, rec: &HitRecord) -> Color {
+        (**self).get_hit_color(rec)
+    }
+}
+
+/// Single channel lookup, e.g. heights for displacement. Color textures
+/// provide their luminance, so image and noise textures work as they are.
+pub trait ScalarTexture {
+    fn value(&self, u: f64, v: f64, p: &Point) -> f64;
+}
+
+impl<T: Texture + ?Sized> ScalarTexture for T {
+    fn value(&self, u: f64, v: f64, p: &Point) -> f64 {
+        let color = self.get_color(u, v, p);
+        0.2126 * color.r() + 0.7152 * color.g() + 0.0722 * color.b()
+    }
 }
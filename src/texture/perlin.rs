@@ -20,7 +20,7 @@ fn generate_rgb(resolution: usize) -> Vec<Vector3> {
 
 pub fn permute(p: &mut Vec<usize>, resolution: usize) {
     let mut rng = rand::thread_rng();
-    for iter in (resolution - 1)..=0 {
+    for iter in (1..resolution).rev() {
         let target = rng.gen_range(0..=iter);
         let tmp = p[iter];
         p[iter] = p[target];
@@ -45,10 +45,10 @@ pub fn perlin_interp(c: &mut [[[Vector3; 2]; 2]; 2], u: f64, v: f64, w: f64) ->
     let ww = w * w * (3.0 - 2.0 * w);
     let mut accum = 0.0;
 
-    for iter in 0..=2 {
-        for jter in 0..=2 {
-            for kter in 0..=2 {
-                let weight_v = Vector3::new(u - iter as f64, v - jter as f64, ww - kter as f64);
+    for iter in 0..=1 {
+        for jter in 0..=1 {
+            for kter in 0..=1 {
+                let weight_v = Vector3::new(u - iter as f64, v - jter as f64, w - kter as f64);
                 accum += (iter as f64 * uu + (1.0 - iter as f64) * (1.0 - uu))
                     * (jter as f64 * vv + (1.0 - jter as f64) * (1.0 - vv))
                     * (kter as f64 * ww + (1.0 - kter as f64) * (1.0 - ww))
@@ -84,18 +84,21 @@ impl Perlin {
         let v = p.y() * scale - (p.y() * scale).floor();
         let w = p.z() * scale - (p.z() * scale).floor();
 
-        let i = (p.x() * scale).floor() as usize;
-        let j = (p.y() * scale).floor() as usize;
-        let k = (p.z() * scale).floor() as usize;
+        // wrap lattice coordinates so negative points index the tables too
+        let wrap = |x: f64, d: usize| {
+            ((x.floor() as i64 + d as i64).rem_euclid(self.resolution as i64)) as usize
+        };
+        let (x, y, z) = (p.x() * scale, p.y() * scale, p.z() * scale);
 
         let mut c = [[[Vector3::new(0.0, 0.0, 0.0); 2]; 2]; 2];
 
         for di in 0..=1 {
             for dj in 0..=1 {
                 for dk in 0..=1 {
-                    c[di][dj][dk] = self.rand_vec[self.perm_x[(i + di) & 255]
-                        ^ self.perm_y[(j + dj) & 255]
-                        ^ self.perm_z[(k + dk) & 255]];
+                    let index = self.perm_x[wrap(x, di)]
+                        ^ self.perm_y[wrap(y, dj)]
+                        ^ self.perm_z[wrap(z, dk)];
+                    c[di][dj][dk] = self.rand_vec[index % self.resolution];
                 }
             }
         }
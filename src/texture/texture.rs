@@ -22,6 +22,78 @@ impl Texture for SolidTexture {
 }
 
 
+#[derive(Debug, Clone, Copy)]
+pub struct ScaledTexture<T: Texture> {
+    texture: T,
+    scale: Color,
+}
+
+impl<T: Texture> ScaledTexture<T> {
+    pub fn new(texture: T, scale: Color) -> ScaledTexture<T> {
+        ScaledTexture { texture, scale }
+    }
+}
+
+impl<T: Texture> Texture for ScaledTexture<T> {
+    fn get_color(&self, u: f64, v: f64, p: &Point) -> Color {
+        self.texture.get_color(u, v, p) * self.scale
+    }
+    fn get_hit_color(&self, rec: &HitRecord) -> Color {
+        self.texture.get_hit_color(rec) * self.scale
+    }
+}
+
+/// Interpolates per-vertex colors of a triangle, reading (u, v) as the
+/// barycentric weights of the second and third vertex.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexColorTexture {
+    colors: [Color; 3],
+}
+
+impl VertexColorTexture {
+    pub fn new(colors: [Color; 3]) -> VertexColorTexture {
+        VertexColorTexture { colors }
+    }
+}
+
+impl Texture for VertexColorTexture {
+    fn get_color(&self, u: f64, v: f64, _p: &Point) -> Color {
+        (1.0 - u - v) * self.colors[0] + u * self.colors[1] + v * self.colors[2]
+    }
+}
+
+/// Per-particle colors of a point cloud, for the particle a hit is on. A
+/// single color applies to every particle, as does the first one when the
+/// texture is looked up away from a hit.
+#[derive(Debug, Clone)]
+pub struct ParticleColorTexture {
+    colors: Arc<Vec<Color>>,
+}
+
+impl ParticleColorTexture {
+    pub fn new(colors: Vec<Color>) -> ParticleColorTexture {
+        ParticleColorTexture {
+            colors: Arc::new(colors),
+        }
+    }
+}
+
+impl Texture for ParticleColorTexture {
+    fn get_color(&self, _u: f64, _v: f64, _p: &Point) -> Color {
+        self.colors[0]
+    }
+    fn get_hit_color(&self, rec: &HitRecord) -> Color {
+        match self.colors.len() {
+            1 => self.colors[0],
+            _ => self
+                .colors
+                .get(rec.primitive)
+                .copied()
+                .unwrap_or_else(Color::zero),
+        }
+    }
+}
+
 pub struct CheckerTexture<T: Texture, S: Texture> {
     odd: T,
     even: S,
@@ -42,6 +114,15 @@ impl<T: Texture, S: Texture> Texture for CheckerTexture<T, S> {
             self.even.get_color(u, v, p)
         }
     }
+    fn get_hit_color(&self, rec: &HitRecord) -> Color {
+        let p = rec.p;
+        let sines = (10.0 * p.x()).sin() * (10.0 * p.y()).sin() * (10.0 * p.z()).sin();
+        if sines < 0.0 {
+            self.odd.get_hit_color(rec)
+        } else {
+            self.even.get_hit_color(rec)
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -10,19 +10,37 @@ impl Aabb {
         Aabb { min, max }
     }
 
-    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
         for iter in 0..=2 {
             let inv_d = 1.0 / r.dir()[iter];
             let t0 = (self.min[iter] - r.origin()[iter]) * inv_d;
             let t1 = (self.max[iter] - r.origin()[iter]) * inv_d;
             let (t0, t1) = if inv_d < 0.0 { (t1, t0) } else { (t0, t1) };
-            if t_max.min(t0) <= t_min.max(t1) {
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
                 return false;
             }
         }
         true
     }
 
+    /// Parametric range `[t0, t1]` of the ray inside the box, within `[t_min, t_max]`.
+    pub fn clip(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        for iter in 0..=2 {
+            let inv_d = 1.0 / r.dir()[iter];
+            let t0 = (self.min[iter] - r.origin()[iter]) * inv_d;
+            let t1 = (self.max[iter] - r.origin()[iter]) * inv_d;
+            let (t0, t1) = if inv_d < 0.0 { (t1, t0) } else { (t0, t1) };
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+
     pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
         let min = Vector3::new(
             f64::min(box0.min.x(), box1.min.x()),
@@ -0,0 +1,95 @@
+use super::*;
+use std::ops;
+
+/// Row-major 4x4 affine transform, `m[row][col]`.
+#[derive(Debug, Clone, Copy)]
+pub struct Matrix4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4 {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Builds a matrix from column-major storage, as used by glTF.
+    pub fn from_cols(cols: [[f32; 4]; 4]) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (col, column) in cols.iter().enumerate() {
+            for (row, value) in column.iter().enumerate() {
+                m[row][col] = *value as f64;
+            }
+        }
+        Matrix4 { m }
+    }
+
+    pub fn transform_point(&self, p: Point) -> Point {
+        let m = &self.m;
+        Point::new(
+            m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3],
+            m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3],
+            m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3],
+        )
+    }
+
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let m = &self.m;
+        Vector3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    /// Normals transform by the inverse transpose of the linear part. The
+    /// cofactor matrix is that up to the determinant, so only its sign matters.
+    pub fn transform_normal(&self, n: Vector3) -> Vector3 {
+        let m = &self.m;
+        let c = |r0: usize, r1: usize, c0: usize, c1: usize| {
+            m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+        };
+        let cofactor = [
+            [c(1, 2, 1, 2), -c(1, 2, 0, 2), c(1, 2, 0, 1)],
+            [-c(0, 2, 1, 2), c(0, 2, 0, 2), -c(0, 2, 0, 1)],
+            [c(0, 1, 1, 2), -c(0, 1, 0, 2), c(0, 1, 0, 1)],
+        ];
+        let sign = self.determinant().signum();
+        Vector3::new(
+            cofactor[0][0] * n.x() + cofactor[0][1] * n.y() + cofactor[0][2] * n.z(),
+            cofactor[1][0] * n.x() + cofactor[1][1] * n.y() + cofactor[1][2] * n.z(),
+            cofactor[2][0] * n.x() + cofactor[2][1] * n.y() + cofactor[2][2] * n.z(),
+        ) * sign
+    }
+
+    /// Determinant of the linear (upper 3x3) part.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
+
+impl ops::Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, line) in m.iter_mut().enumerate() {
+            for (col, value) in line.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * other.m[k][col]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
@@ -1,15 +1,28 @@
 pub use self::aabb::*;
 pub use self::color::*;
 pub use self::macros::*;
+pub use self::matrix::*;
+pub use self::quaternion::*;
 pub use self::ray::*;
+pub use self::rgbspectrum::*;
+pub use self::solver::*;
+pub use self::spectrum::*;
 pub use self::vector::*;
 
 pub mod aabb;
 pub mod color;
 pub mod macros;
+pub mod matrix;
+pub mod quaternion;
 pub mod ray;
+pub mod rgbspectrum;
+pub mod solver;
+pub mod spectrum;
 pub mod vector;
 
+#[cfg(test)]
+mod tests;
+
 pub type Point = Vector3;
 pub type Color = Vector3;
 
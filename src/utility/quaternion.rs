@@ -0,0 +1,96 @@
+use super::*;
+use std::ops;
+
+/// Unit quaternion for rotations that have to be interpolated.
+#[derive(Debug, Clone, Copy)]
+pub struct Quaternion {
+    w: f64,
+    v: Vector3,
+}
+
+impl Quaternion {
+    pub fn identity() -> Quaternion {
+        Quaternion {
+            w: 1.0,
+            v: Vector3::zero(),
+        }
+    }
+
+    /// Rotation by `degrees` around `axis`, counterclockwise looking down the axis.
+    pub fn from_axis_angle(axis: Vector3, degrees: f64) -> Quaternion {
+        let half = 0.5 * degress_to_radians(degrees);
+        Quaternion {
+            w: half.cos(),
+            v: axis.normalize() * half.sin(),
+        }
+    }
+
+    pub fn dot(self, other: Quaternion) -> f64 {
+        self.w * other.w + self.v.dot(other.v)
+    }
+
+    pub fn normalize(self) -> Quaternion {
+        let length = self.dot(self).sqrt();
+        Quaternion {
+            w: self.w / length,
+            v: self.v / length,
+        }
+    }
+
+    pub fn conjugate(self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            v: -self.v,
+        }
+    }
+
+    /// Angle in radians of the rotation from `self` to `other`, along the shorter arc.
+    pub fn angle_to(self, other: Quaternion) -> f64 {
+        2.0 * self.dot(other).abs().clamp(0.0, 1.0).acos()
+    }
+
+    /// Spherical interpolation at constant angular speed, along the shorter arc.
+    pub fn slerp(self, other: Quaternion, t: f64) -> Quaternion {
+        let mut cos = self.dot(other);
+        let mut other = other;
+        // q and -q are the same rotation, take the one closer to self
+        if cos < 0.0 {
+            cos = -cos;
+            other = Quaternion {
+                w: -other.w,
+                v: -other.v,
+            };
+        }
+        if cos > 0.9995 {
+            return Quaternion {
+                w: self.w + (other.w - self.w) * t,
+                v: self.v.lerp(other.v, t),
+            }
+            .normalize();
+        }
+        let theta = cos.acos();
+        let sin = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin;
+        let b = (t * theta).sin() / sin;
+        Quaternion {
+            w: a * self.w + b * other.w,
+            v: a * self.v + b * other.v,
+        }
+    }
+
+    pub fn rotate(self, v: Vector3) -> Vector3 {
+        let t = 2.0 * self.v.cross(v);
+        v + self.w * t + self.v.cross(t)
+    }
+}
+
+impl ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+    /// Rotation by `other` followed by `self`.
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.v.dot(other.v),
+            v: self.w * other.v + other.w * self.v + self.v.cross(other.v),
+        }
+    }
+}
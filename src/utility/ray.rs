@@ -1,14 +1,95 @@
 use super::*;
+use std::ops;
+
+/// What a ray is traced for, so objects can hide from some kinds of rays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayType {
+    /// Primary rays leaving the camera.
+    Camera,
+    /// Rays scattered off a surface, reflected or refracted.
+    Reflection,
+    /// Rays sampled towards a light, see `PDF::sample`.
+    Shadow,
+}
+
+/// Set of ray types an object is seen by, plus whether lights are sampled
+/// towards it. Flags combine with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility(u8);
+
+impl Visibility {
+    pub const NONE: Visibility = Visibility(0);
+    pub const CAMERA: Visibility = Visibility(1);
+    pub const REFLECTION: Visibility = Visibility(1 << 1);
+    pub const SHADOW: Visibility = Visibility(1 << 2);
+    /// Included in the light sampling of the `HittableList` holding it.
+    pub const LIGHT_SAMPLING: Visibility = Visibility(1 << 3);
+    pub const ALL: Visibility = Visibility(0b1111);
+
+    pub fn contains(self, other: Visibility) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn without(self, other: Visibility) -> Visibility {
+        Visibility(self.0 & !other.0)
+    }
+
+    pub fn sees(self, ray_type: RayType) -> bool {
+        self.contains(match ray_type {
+            RayType::Camera => Visibility::CAMERA,
+            RayType::Reflection => Visibility::REFLECTION,
+            RayType::Shadow => Visibility::SHADOW,
+        })
+    }
+}
+
+impl ops::BitOr for Visibility {
+    type Output = Visibility;
+    fn bitor(self, other: Visibility) -> Visibility {
+        Visibility(self.0 | other.0)
+    }
+}
+
 pub struct Ray {
     origin: Point,
     dir: Vector3,
     time: f64,
+    ray_type: RayType,
+    wavelengths: Option<SampledWavelengths>,
 }
 
 impl Ray {
+    /// Rays start out as `RayType::Reflection`, the type of everything a
+    /// material scatters; the camera marks its own rays.
     pub fn new(origin: Vector3, dir: Vector3, time: f64) -> Ray {
-        Ray { origin, dir, time }
+        Ray {
+            origin,
+            dir,
+            time,
+            ray_type: RayType::Reflection,
+            wavelengths: None,
+        }
+    }
+
+    pub fn with_type(mut self, ray_type: RayType) -> Ray {
+        self.ray_type = ray_type;
+        self
     }
+
+    /// Wavelengths the path is traced at. Rays without them are left to
+    /// `inherit_wavelengths` from the ray they were scattered from.
+    pub fn with_wavelengths(mut self, wavelengths: Option<SampledWavelengths>) -> Ray {
+        self.wavelengths = wavelengths;
+        self
+    }
+
+    pub fn inherit_wavelengths(mut self, parent: &Ray) -> Ray {
+        if self.wavelengths.is_none() {
+            self.wavelengths = parent.wavelengths;
+        }
+        self
+    }
+
     pub fn at(&self, t: f64) -> Point {
         let ret = self.dir * t + self.origin;
         ret
@@ -25,4 +106,12 @@ impl Ray {
     pub fn time(&self) -> f64 {
         self.time
     }
+
+    pub fn ray_type(&self) -> RayType {
+        self.ray_type
+    }
+
+    pub fn wavelengths(&self) -> Option<SampledWavelengths> {
+        self.wavelengths
+    }
 }
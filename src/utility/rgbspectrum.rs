@@ -0,0 +1,249 @@
+use super::*;
+
+// RGB to spectrum uplifting after Jakob and Hanika, "A Low-Dimensional
+// Function Space for Efficient Spectral Upsampling". A reflectance is the
+// sigmoid of a quadratic in wavelength, which is smooth and stays in [0, 1].
+// The quadratics reproducing each RGB under D65 are fit once into a table
+// indexed by the largest component and the other two relative to it, and
+// interpolated from there.
+
+// table entries along each axis
+const TABLE_RES: usize = 16;
+// wavelengths the fit integrates over, 5 nm apart
+const FIT_STEPS: usize = 81;
+
+fn sigmoid(x: f64) -> f64 {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+fn smoothstep(x: f64) -> f64 {
+    x * x * (3.0 - 2.0 * x)
+}
+
+// the table is denser towards dark and bright colors
+fn scale_node(k: usize) -> f64 {
+    smoothstep(smoothstep(k as f64 / (TABLE_RES - 1) as f64))
+}
+
+// wavelength mapped onto [0, 1] over the visible range
+fn normalized_lambda(lambda: f64) -> f64 {
+    (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+fn polynomial(c: [f64; 3], t: f64) -> f64 {
+    (c[0] * t + c[1]) * t + c[2]
+}
+
+/// Smooth reflectance spectrum with the color `rgb`, components in [0, 1].
+#[derive(Debug, Clone, Copy)]
+pub struct RgbSigmoid {
+    c: [f64; 3],
+}
+
+impl RgbSigmoid {
+    pub fn new(rgb: Color) -> RgbSigmoid {
+        let rgb = Color::new(
+            rgb.r().clamp(0.0, 1.0),
+            rgb.g().clamp(0.0, 1.0),
+            rgb.b().clamp(0.0, 1.0),
+        );
+        if rgb.r() == rgb.g() && rgb.g() == rgb.b() {
+            // a constant spectrum, the sigmoid inverted at the gray level
+            let v = rgb.r();
+            return RgbSigmoid {
+                c: [0.0, 0.0, (v - 0.5) / (v * (1.0 - v)).sqrt()],
+            };
+        }
+
+        let table = coefficient_table();
+        let max = (0..3)
+            .max_by(|&a, &b| rgb[a].total_cmp(&rgb[b]))
+            .unwrap_or(0);
+        let z = rgb[max];
+        let scale = (TABLE_RES - 1) as f64;
+        let x = rgb[(max + 1) % 3] / z * scale;
+        let y = rgb[(max + 2) % 3] / z * scale;
+        let xi = (x as usize).min(TABLE_RES - 2);
+        let yi = (y as usize).min(TABLE_RES - 2);
+        let zi = (0..TABLE_RES - 1)
+            .rev()
+            .find(|&k| scale_node(k) <= z)
+            .unwrap_or(0);
+        let (dx, dy) = (x - xi as f64, y - yi as f64);
+        // towards black the coefficients grow like 1 / sqrt(z), so that is
+        // what is interpolated in
+        let (z0, z1) = (scale_node(zi), scale_node(zi + 1));
+        let dz = if z0 > 0.0 {
+            (1.0 / z.sqrt() - 1.0 / z0.sqrt()) / (1.0 / z1.sqrt() - 1.0 / z0.sqrt())
+        } else {
+            (z - z0) / (z1 - z0)
+        };
+
+        let at = |i: usize, j: usize, k: usize| {
+            table[((max * TABLE_RES + k) * TABLE_RES + j) * TABLE_RES + i]
+        };
+        let mut c = [0.0; 3];
+        for (n, c) in c.iter_mut().enumerate() {
+            let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+            let face = |k: usize| {
+                lerp(
+                    lerp(at(xi, yi, k)[n], at(xi + 1, yi, k)[n], dx),
+                    lerp(at(xi, yi + 1, k)[n], at(xi + 1, yi + 1, k)[n], dx),
+                    dy,
+                )
+            };
+            *c = lerp(face(zi), face(zi + 1), dz);
+        }
+        RgbSigmoid { c }
+    }
+
+    /// Reflectance at `lambda` nanometers.
+    pub fn at(&self, lambda: f64) -> f64 {
+        sigmoid(polynomial(self.c, normalized_lambda(lambda)))
+    }
+}
+
+// what each fit wavelength contributes to the white balanced linear sRGB of
+// a reflectance lit by D65
+fn fit_weights() -> Vec<(f64, Color)> {
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / (FIT_STEPS - 1) as f64;
+    let mut weights: Vec<(f64, Color)> = (0..FIT_STEPS)
+        .map(|j| {
+            let lambda = LAMBDA_MIN + j as f64 * step;
+            // trapezoid rule
+            let w = if j == 0 || j == FIT_STEPS - 1 {
+                0.5
+            } else {
+                1.0
+            };
+            let xyz = cie_xyz(lambda) * Spectrum::D65.unnormalized(lambda) * w * step;
+            (normalized_lambda(lambda), xyz_to_rgb(xyz))
+        })
+        .collect();
+    // balance so that a unit reflectance comes out exactly white
+    let white = weights
+        .iter()
+        .fold(Color::zero(), |sum, &(_, rgb)| sum + rgb);
+    for (_, rgb) in &mut weights {
+        *rgb = Color::new(
+            rgb.r() / white.r(),
+            rgb.g() / white.g(),
+            rgb.b() / white.b(),
+        );
+    }
+    weights
+}
+
+// RGB of the coefficients and its derivatives with respect to each of them
+fn fit_rgb(c: [f64; 3], weights: &[(f64, Color)]) -> (Color, [Color; 3]) {
+    let mut rgb = Color::zero();
+    let mut jacobian = [Color::zero(); 3];
+    for &(t, w) in weights {
+        let x = polynomial(c, t);
+        rgb += w * sigmoid(x);
+        let slope = 0.5 / (1.0 + x * x).powf(1.5);
+        for (n, column) in jacobian.iter_mut().enumerate() {
+            *column += w * slope * t.powi(2 - n as i32);
+        }
+    }
+    (rgb, jacobian)
+}
+
+// Levenberg-Marquardt on the RGB residual, starting from `c`
+fn fit(target: Color, mut c: [f64; 3], weights: &[(f64, Color)]) -> [f64; 3] {
+    let (rgb, mut jacobian) = fit_rgb(c, weights);
+    let mut residual = target - rgb;
+    let mut damping = 1e-3;
+    for _ in 0..50 {
+        if residual.length_squared() < 1e-14 {
+            break;
+        }
+        // (J^T J + damping diag) step = J^T residual
+        let mut a = [[0.0; 3]; 3];
+        let mut b = [0.0; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                a[i][j] = jacobian[i].dot(jacobian[j]);
+            }
+            b[i] = jacobian[i].dot(residual);
+        }
+        for (i, row) in a.iter_mut().enumerate() {
+            row[i] *= 1.0 + damping;
+        }
+        let Some(step) = solve3(a, b) else { break };
+        let next = [c[0] + step[0], c[1] + step[1], c[2] + step[2]];
+        let (rgb, next_jacobian) = fit_rgb(next, weights);
+        let next_residual = target - rgb;
+        if next_residual.length_squared() < residual.length_squared() {
+            c = next;
+            residual = next_residual;
+            jacobian = next_jacobian;
+            damping = (damping * 0.3).max(1e-9);
+        } else {
+            damping *= 10.0;
+            if damping > 1e6 {
+                break;
+            }
+        }
+    }
+    c
+}
+
+fn solve3(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(a);
+    if d.abs() < 1e-300 {
+        return None;
+    }
+    // Cramer's rule
+    let mut x = [0.0; 3];
+    for (col, x) in x.iter_mut().enumerate() {
+        let mut m = a;
+        for row in 0..3 {
+            m[row][col] = b[row];
+        }
+        *x = det(m) / d;
+    }
+    Some(x)
+}
+
+fn coefficient_table() -> &'static [[f64; 3]] {
+    static TABLE: std::sync::OnceLock<Vec<[f64; 3]>> = std::sync::OnceLock::new();
+    TABLE.get_or_init(|| {
+        let weights = fit_weights();
+        let mut table = vec![[0.0; 3]; 3 * TABLE_RES * TABLE_RES * TABLE_RES];
+        for max in 0..3 {
+            for j in 0..TABLE_RES {
+                for i in 0..TABLE_RES {
+                    let x = i as f64 / (TABLE_RES - 1) as f64;
+                    let y = j as f64 / (TABLE_RES - 1) as f64;
+                    // walk up and down the brightness from a medium gray start,
+                    // each fit starting from its neighbour's solution
+                    let start = TABLE_RES / 5;
+                    let order = (start..TABLE_RES).chain((0..start).rev());
+                    let mut c = [0.0; 3];
+                    for k in order {
+                        if k == start - 1 {
+                            c = table[((max * TABLE_RES + start) * TABLE_RES + j) * TABLE_RES + i];
+                        }
+                        let z = scale_node(k);
+                        let mut rgb = Color::zero();
+                        rgb[max] = z;
+                        rgb[(max + 1) % 3] = x * z;
+                        rgb[(max + 2) % 3] = y * z;
+                        c = fit(rgb, c, &weights);
+                        table[((max * TABLE_RES + k) * TABLE_RES + j) * TABLE_RES + i] = c;
+                    }
+                }
+            }
+        }
+        table
+    })
+}
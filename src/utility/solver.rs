@@ -0,0 +1,133 @@
+use super::*;
+
+// Closed form polynomial roots after Schwarze, "Cubic and Quartic Roots",
+// Graphics Gems I. All functions return the real roots in ascending order.
+
+const EPSILON: f64 = 1e-9;
+
+/// Whether `x`, computed from terms of magnitude up to `scale`, is zero up to
+/// their rounding, so the tolerance follows the size of the roots.
+fn is_zero(x: f64, scale: f64) -> bool {
+    x.abs() <= EPSILON * scale
+}
+
+fn sorted(mut roots: Vec<f64>) -> Vec<f64> {
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    roots
+}
+
+/// a x^2 + b x + c = 0, falling back to the linear case when `a` vanishes.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return if b == 0.0 { vec![] } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    // avoid cancellation between b and the square root
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    sorted(vec![q / a, c / q])
+}
+
+/// x^3 + a x^2 + b x + c = 0
+fn solve_normed_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // substitute x = y - a/3 to eliminate the quadric term: y^3 + 3p y + 2q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+    let q_scale = (2.0 / 27.0 * (a * sq_a).abs() + (a * b).abs() / 3.0 + c.abs()) / 2.0;
+    let p_scale = (sq_a / 3.0 + b.abs()) / 3.0;
+
+    let roots = if is_zero(d, q_scale * q_scale + p_scale.powi(3)) {
+        if is_zero(q, q_scale) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.0).cos(),
+            -t * (phi - PI / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    roots.into_iter().map(|y| y - a / 3.0).collect()
+}
+
+/// x^4 + a x^3 + b x^2 + c x + d = 0
+fn solve_normed_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // substitute x = y - a/4 to eliminate the cubic term: y^4 + p y^2 + q y + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c / 4.0 + d;
+    let r_scale =
+        3.0 / 256.0 * sq_a * sq_a + (sq_a * b).abs() / 16.0 + (a * c).abs() / 4.0 + d.abs();
+
+    let roots = if is_zero(r, r_scale) {
+        // y (y^3 + p y + q) = 0
+        let mut roots = solve_normed_cubic(0.0, p, q);
+        roots.push(0.0);
+        roots
+    } else {
+        // one root of the resolvent cubic splits the quartic into two quadratics
+        let z = solve_normed_cubic(-p / 2.0, -r, r * p / 2.0 - q * q / 8.0)[0];
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u, z * z + r.abs()) {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if is_zero(v, 2.0 * z.abs() + p.abs()) {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if q < 0.0 { -v } else { v };
+
+        let mut roots = solve_quadratic(1.0, v, z - u);
+        roots.extend(solve_quadratic(1.0, -v, z + u));
+        roots
+    };
+
+    roots.into_iter().map(|y| y - a / 4.0).collect()
+}
+
+/// c4 x^4 + c3 x^3 + c2 x^2 + c1 x + c0 = 0. The closed form loses precision
+/// for ill-conditioned inputs, so every root is polished with Newton steps.
+pub fn solve_quartic(c4: f64, c3: f64, c2: f64, c1: f64, c0: f64) -> Vec<f64> {
+    if c4 == 0.0 {
+        return vec![];
+    }
+    let roots = solve_normed_quartic(c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+    let polish = |mut x: f64| {
+        for _ in 0..3 {
+            let f = (((c4 * x + c3) * x + c2) * x + c1) * x + c0;
+            let df = ((4.0 * c4 * x + 3.0 * c3) * x + 2.0 * c2) * x + c1;
+            if df == 0.0 {
+                break;
+            }
+            x -= f / df;
+        }
+        x
+    };
+    sorted(roots.into_iter().map(polish).collect())
+}
@@ -0,0 +1,352 @@
+use super::*;
+use std::ops;
+
+/// Range of visible wavelengths paths are sampled over, in nanometers.
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+/// Number of wavelengths carried by a path.
+pub const N_SPECTRUM_SAMPLES: usize = 4;
+
+/// Wavelengths a path is traced at: a hero wavelength and the others spread
+/// evenly over the visible range from it (Wilkie et al., "Hero Wavelength
+/// Spectral Sampling").
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledWavelengths {
+    lambda: [f64; N_SPECTRUM_SAMPLES],
+    pdf: [f64; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    /// Hero wavelength at `u` in [0, 1) across the visible range.
+    pub fn sample_uniform(u: f64) -> SampledWavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [0.0; N_SPECTRUM_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            let offset = (u + i as f64 / N_SPECTRUM_SAMPLES as f64).fract();
+            *l = LAMBDA_MIN + offset * range;
+        }
+        SampledWavelengths {
+            lambda,
+            pdf: [1.0 / range; N_SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn lambda(&self, i: usize) -> f64 {
+        self.lambda[i]
+    }
+
+    /// Density the wavelength was sampled with, 0 once it is terminated.
+    pub fn pdf(&self, i: usize) -> f64 {
+        self.pdf[i]
+    }
+
+    /// Keeps only the hero wavelength, for events such as dispersion that
+    /// send each wavelength a different way. The hero then stands in for all
+    /// of them.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in &mut self.pdf[1..] {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= N_SPECTRUM_SAMPLES as f64;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|&pdf| pdf == 0.0)
+    }
+
+    /// Weights carrying what is traced at `after`, a possibly terminated copy
+    /// of these wavelengths, over to the densities of these.
+    pub fn reweight(&self, after: &SampledWavelengths) -> SampledSpectrum {
+        let mut weight = SampledSpectrum::zero();
+        for i in 0..N_SPECTRUM_SAMPLES {
+            if after.pdf[i] > 0.0 {
+                weight[i] = self.pdf[i] / after.pdf[i];
+            }
+        }
+        weight
+    }
+}
+
+/// Values of a spectral quantity at the `SampledWavelengths` of a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampledSpectrum([f64; N_SPECTRUM_SAMPLES]);
+
+impl SampledSpectrum {
+    pub fn new(values: [f64; N_SPECTRUM_SAMPLES]) -> SampledSpectrum {
+        SampledSpectrum(values)
+    }
+
+    pub fn constant(value: f64) -> SampledSpectrum {
+        SampledSpectrum([value; N_SPECTRUM_SAMPLES])
+    }
+
+    pub fn zero() -> SampledSpectrum {
+        SampledSpectrum::constant(0.0)
+    }
+
+    pub fn one() -> SampledSpectrum {
+        SampledSpectrum::constant(1.0)
+    }
+
+    /// Spectrum of a surface color or other RGB weight. Components above
+    /// one, such as emission, keep their ratios.
+    pub fn from_rgb(rgb: Color, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        let m = rgb.r().max(rgb.g()).max(rgb.b());
+        let (scale, rgb) = if m > 1.0 {
+            (2.0 * m, rgb / (2.0 * m))
+        } else {
+            (1.0, rgb)
+        };
+        let sigmoid = RgbSigmoid::new(rgb);
+        SampledSpectrum(std::array::from_fn(|i| {
+            scale * sigmoid.at(wavelengths.lambda(i))
+        }))
+    }
+
+    /// Spectrum of light with color `rgb`, on a D65 white like sRGB.
+    pub fn from_rgb_illuminant(rgb: Color, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        static D65: std::sync::OnceLock<LightSpectrum> = std::sync::OnceLock::new();
+        let d65 = D65.get_or_init(|| LightSpectrum::new(Spectrum::D65));
+        SampledSpectrum::from_rgb(rgb, wavelengths) * d65.sample(wavelengths)
+    }
+
+    /// CIE XYZ of the radiance these values sample, Y = 1 for a unit
+    /// equal-energy spectrum.
+    pub fn to_xyz(self, wavelengths: &SampledWavelengths) -> Vector3 {
+        let mut xyz = Vector3::zero();
+        for i in 0..N_SPECTRUM_SAMPLES {
+            if wavelengths.pdf(i) > 0.0 {
+                xyz += cie_xyz(wavelengths.lambda(i)) * self.0[i] / wavelengths.pdf(i);
+            }
+        }
+        xyz / (N_SPECTRUM_SAMPLES as f64 * cie_y_integral())
+    }
+
+    /// Linear sRGB, balanced so that D65 light comes out white.
+    pub fn to_rgb(self, wavelengths: &SampledWavelengths) -> Color {
+        white_balance(xyz_to_rgb(self.to_xyz(wavelengths)))
+    }
+}
+
+impl ops::Index<usize> for SampledSpectrum {
+    type Output = f64;
+    fn index(&self, i: usize) -> &f64 {
+        &self.0[i]
+    }
+}
+
+impl ops::IndexMut<usize> for SampledSpectrum {
+    fn index_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self.0[i]
+    }
+}
+
+impl ops::Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn add(self, other: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum(std::array::from_fn(|i| self.0[i] + other.0[i]))
+    }
+}
+
+impl ops::Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, other: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum(std::array::from_fn(|i| self.0[i] * other.0[i]))
+    }
+}
+
+impl ops::Mul<f64> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, other: f64) -> SampledSpectrum {
+        SampledSpectrum(self.0.map(|v| v * other))
+    }
+}
+
+// CIE D65 relative spectral power at 10 nm steps from 380 to 780 nm
+const D65: [f64; 41] = [
+    49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0, 96.3342, 95.788,
+    88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842,
+    69.7213, 71.6091, 74.349, 61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054, 63.3828,
+];
+
+/// Spectral power distribution of a light source, scaled to unit luminance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spectrum {
+    /// Planck's law at a temperature in kelvin.
+    Blackbody(f64),
+    /// CIE standard illuminant D65, average daylight and the white of sRGB.
+    D65,
+    /// CIE standard illuminant A, an incandescent lamp.
+    IlluminantA,
+    /// CIE standard illuminant E, equal energy at every wavelength.
+    IlluminantE,
+}
+
+impl Spectrum {
+    // power at `lambda` nanometers, on a scale of its own
+    pub(crate) fn unnormalized(&self, lambda: f64) -> f64 {
+        match *self {
+            Spectrum::Blackbody(temperature) => blackbody(lambda, temperature),
+            Spectrum::D65 => {
+                let x = ((lambda - 380.0) / 10.0).clamp(0.0, 40.0);
+                let i = (x as usize).min(39);
+                D65[i] + (D65[i + 1] - D65[i]) * (x - i as f64)
+            }
+            Spectrum::IlluminantA => blackbody(lambda, 2856.0),
+            Spectrum::IlluminantE => 1.0,
+        }
+    }
+
+    /// Lumens per watt of the light's power in the visible range.
+    pub fn luminous_efficacy(self) -> f64 {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let (luminous, radiant) = (0..steps)
+            .map(|i| {
+                let lambda = LAMBDA_MIN + i as f64 + 0.5;
+                let power = self.unnormalized(lambda);
+                (power * cie_xyz(lambda).y(), power)
+            })
+            .fold((0.0, 0.0), |(l, r), (dl, dr)| (l + dl, r + dr));
+        // lumens per watt at 555 nm, where the eye is most sensitive
+        683.0 * luminous / radiant
+    }
+
+    /// Linear sRGB of the light at unit luminance, for the RGB pipeline.
+    pub fn to_rgb(self) -> Color {
+        white_balance(xyz_to_rgb(self.xyz()))
+    }
+
+    // CIE XYZ at unit luminance
+    fn xyz(&self) -> Vector3 {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let xyz = (0..steps)
+            .map(|i| {
+                let lambda = LAMBDA_MIN + i as f64 + 0.5;
+                cie_xyz(lambda) * self.unnormalized(lambda)
+            })
+            .fold(Vector3::zero(), |sum, xyz| sum + xyz);
+        xyz / xyz.y()
+    }
+}
+
+/// A `Spectrum` at unit luminance, normalized once up front since lights
+/// evaluate it at every hit.
+#[derive(Debug, Clone, Copy)]
+pub struct LightSpectrum {
+    spectrum: Spectrum,
+    scale: f64,
+    rgb: Color,
+}
+
+impl LightSpectrum {
+    pub fn new(spectrum: Spectrum) -> LightSpectrum {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let luminance = (0..steps)
+            .map(|i| {
+                let lambda = LAMBDA_MIN + i as f64 + 0.5;
+                spectrum.unnormalized(lambda) * cie_xyz(lambda).y()
+            })
+            .sum::<f64>()
+            / cie_y_integral();
+        LightSpectrum {
+            spectrum,
+            scale: 1.0 / luminance,
+            rgb: spectrum.to_rgb(),
+        }
+    }
+
+    /// Power at each of the `wavelengths`.
+    pub fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum(std::array::from_fn(|i| {
+            self.spectrum.unnormalized(wavelengths.lambda(i)) * self.scale
+        }))
+    }
+
+    /// The same light in linear sRGB.
+    pub fn rgb(&self) -> Color {
+        self.rgb
+    }
+
+    pub fn spectrum(&self) -> Spectrum {
+        self.spectrum
+    }
+}
+
+/// Planck's law at `lambda` nanometers, relative to the peak of the curve.
+fn blackbody(lambda: f64, temperature: f64) -> f64 {
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+    const C: f64 = 299792458.0;
+    const H: f64 = 6.62606957e-34;
+    const KB: f64 = 1.3806488e-23;
+    let planck = |lambda: f64| {
+        let l = lambda * 1e-9;
+        2.0 * H * C * C / (l.powi(5) * ((H * C / (l * KB * temperature)).exp() - 1.0))
+    };
+    // Wien's displacement law
+    let peak = 2.8977721e-3 / temperature * 1e9;
+    planck(lambda) / planck(peak)
+}
+
+/// Integral of the CIE Y matching function over the sampled range, the
+/// luminance of a unit equal-energy spectrum before normalization.
+pub fn cie_y_integral() -> f64 {
+    static INTEGRAL: std::sync::OnceLock<f64> = std::sync::OnceLock::new();
+    *INTEGRAL.get_or_init(|| {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        (0..steps)
+            .map(|i| cie_xyz(LAMBDA_MIN + i as f64 + 0.5).y())
+            .sum()
+    })
+}
+
+// scales linear sRGB so that D65 light, which the matching functions only
+// approximately put at sRGB white, comes out white
+fn white_balance(rgb: Color) -> Color {
+    static WHITE: std::sync::OnceLock<Color> = std::sync::OnceLock::new();
+    let white = WHITE.get_or_init(|| xyz_to_rgb(Spectrum::D65.xyz()));
+    Color::new(
+        rgb.r() / white.r(),
+        rgb.g() / white.g(),
+        rgb.b() / white.b(),
+    )
+}
+
+fn piecewise_gaussian(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let t = (x - mu) / if x < mu { sigma_low } else { sigma_high };
+    (-0.5 * t * t).exp()
+}
+
+/// CIE 1931 color matching functions at `lambda` nanometers, in the
+/// multi-lobe fit of Wyman, Sloan and Shirley, "Simple Analytic
+/// Approximations to the CIE XYZ Color Matching Functions".
+pub fn cie_xyz(lambda: f64) -> Vector3 {
+    Vector3::new(
+        1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+            + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2),
+        0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+            + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1),
+        1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+            + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+/// CIE XYZ to linear sRGB (D65 white).
+pub fn xyz_to_rgb(xyz: Vector3) -> Color {
+    Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.969266 * xyz.x() + 1.8760108 * xyz.y() + 0.041556 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
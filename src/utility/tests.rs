@@ -0,0 +1,85 @@
+use super::*;
+
+fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+    assert_eq!(roots.len(), expected.len(), "{:?} vs {:?}", roots, expected);
+    for (root, expected) in roots.iter().zip(expected) {
+        assert!(
+            (root - expected).abs() <= 1e-9 * expected.abs().max(1.0),
+            "{:?} vs {:?}",
+            roots,
+            expected
+        );
+    }
+}
+
+/// Coefficients, highest power first, of the monic polynomial with `roots`.
+fn from_roots(roots: &[f64]) -> Vec<f64> {
+    roots.iter().fold(vec![1.0], |coefficients, root| {
+        let mut next = coefficients.clone();
+        next.push(0.0);
+        for (i, c) in coefficients.iter().enumerate() {
+            next[i + 1] -= root * c;
+        }
+        next
+    })
+}
+
+fn quartic_roots(roots: [f64; 4], scale: f64) -> Vec<f64> {
+    let c = from_roots(&roots);
+    solve_quartic(
+        scale * c[0],
+        scale * c[1],
+        scale * c[2],
+        scale * c[3],
+        scale * c[4],
+    )
+}
+
+#[test]
+fn quadratics_have_their_known_roots() {
+    assert_roots(solve_quadratic(1.0, -3.0, 2.0), &[1.0, 2.0]);
+    assert_roots(solve_quadratic(2.0, 0.0, -8.0), &[-2.0, 2.0]);
+    assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+    assert_roots(solve_quadratic(0.0, 2.0, -1.0), &[0.5]);
+    // a leading coefficient small in absolute terms is still a quadratic
+    assert_roots(solve_quadratic(1e-14, 0.0, -1e-14), &[-1.0, 1.0]);
+}
+
+#[test]
+fn quartics_have_their_known_roots() {
+    assert_roots(
+        quartic_roots([1.0, 2.0, 3.0, 4.0], 1.0),
+        &[1.0, 2.0, 3.0, 4.0],
+    );
+    assert_roots(
+        quartic_roots([-3.0, -0.5, 0.25, 7.0], 2.0),
+        &[-3.0, -0.5, 0.25, 7.0],
+    );
+    // x^4 - 1 has two real roots
+    assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, -1.0), &[-1.0, 1.0]);
+    assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+}
+
+#[test]
+fn quartic_roots_keep_their_precision_at_any_scale() {
+    for scale in [1e-3, 1.0, 1e3] {
+        let roots = [1.0, 1.5, 3.0, 4.0].map(|root| root * scale);
+        assert_roots(quartic_roots(roots, 1.0), &roots);
+    }
+    let roots = [3500.0, 4500.0, 5500.0, 6500.0];
+    assert_roots(quartic_roots(roots, 1e-6), &roots);
+}
+
+#[test]
+fn refracted_rays_follow_snells_law() {
+    let normal = Vector3::new(0.0, 1.0, 0.0);
+    for eta in [1.0 / 1.5, 1.5 / 1.33] {
+        for sin_in in [0.0, 0.3, 0.6] {
+            let incoming = Vector3::new(sin_in, -(1.0 - sin_in * sin_in).sqrt(), 0.0);
+            let refracted = incoming.refract(normal, eta);
+            assert!((refracted.length() - 1.0).abs() < 1e-12);
+            assert!((refracted.x() - eta * sin_in).abs() < 1e-12);
+            assert!(refracted.y() < 0.0);
+        }
+    }
+}
@@ -130,7 +130,7 @@ impl Vector3 {
     pub fn refract(self, normal: Vector3, etai_over_etat: f64) -> Vector3 {
         let cos_theta = -self.dot(normal).min(1.0);
         let r_out_perp = (self + normal * cos_theta) * etai_over_etat;
-        let r_out_parallel = normal * -(1.0 - r_out_perp.length_squared().abs().sqrt());
+        let r_out_parallel = normal * -(1.0 - r_out_perp.length_squared()).abs().sqrt();
         r_out_perp + r_out_parallel
     }
 }